@@ -6,7 +6,7 @@ use statslicer::{benchmark, black_box, statslicer_main, Bencher, Parameter, Para
 
 use tnaps::{
     ComponentChange, ComponentCollection, CopyOnWriteComponentCollection, Entity,
-    InsertOptimizedComponentCollection, MutableComponentCollection,
+    InsertOptimizedComponentCollection, MutableComponentCollection, OrderedComponentCollection,
 };
 
 //////////////////////////////////////////// EntityType ////////////////////////////////////////////
@@ -55,16 +55,22 @@ impl Alignment {
 enum CollectionType {
     #[default]
     CopyOnWrite,
+    CopyOnWriteRebuild,
+    CopyOnWriteSmallBatch,
     InsertOptimized,
     Mutable,
+    Ordered,
 }
 
 impl CollectionType {
     fn as_str(&self) -> String {
         match self {
             CollectionType::CopyOnWrite => "cow",
+            CollectionType::CopyOnWriteRebuild => "cow_rebuild",
+            CollectionType::CopyOnWriteSmallBatch => "cow_small_batch",
             CollectionType::InsertOptimized => "ins",
             CollectionType::Mutable => "mut",
+            CollectionType::Ordered => "ord",
         }
         .to_string()
     }
@@ -195,6 +201,25 @@ fn bench_apply_inner<
     });
 }
 
+fn bench_apply_cow<E: Entity + FromGuacamole<()>, T: Clone + Debug + FromGuacamole<()>>(
+    params: &ApplyParameters,
+    b: &mut Bencher,
+) {
+    let mut guac = Guacamole::new(b.seed());
+    let (entities, mut collection): (Vec<E>, CopyOnWriteComponentCollection<E, T>) =
+        collection(params.components, &mut guac);
+    let changes: Vec<(E, ComponentChange<T>)> =
+        changes(b.size(), params.mutate_probability, &entities, &mut guac);
+    match params.collection_type {
+        CollectionType::CopyOnWriteSmallBatch => b.run(|| {
+            black_box(collection.apply_small_batch(black_box(changes)));
+        }),
+        _ => b.run(|| {
+            black_box(collection.apply_rebuild(black_box(changes)));
+        }),
+    }
+}
+
 fn bench_apply_component_type<
     E: Entity + FromGuacamole<()>,
     T: Clone + Debug + FromGuacamole<()>,
@@ -206,12 +231,18 @@ fn bench_apply_component_type<
         CollectionType::CopyOnWrite => {
             bench_apply_inner::<E, T, CopyOnWriteComponentCollection<E, T>>(params, b)
         }
+        CollectionType::CopyOnWriteRebuild | CollectionType::CopyOnWriteSmallBatch => {
+            bench_apply_cow::<E, T>(params, b)
+        }
         CollectionType::InsertOptimized => {
             bench_apply_inner::<E, T, InsertOptimizedComponentCollection<E, T>>(params, b)
         }
         CollectionType::Mutable => {
             bench_apply_inner::<E, T, MutableComponentCollection<E, T>>(params, b)
         }
+        CollectionType::Ordered => {
+            bench_apply_inner::<E, T, OrderedComponentCollection<E, T>>(params, b)
+        }
     }
 }
 
@@ -238,7 +269,14 @@ benchmark! {
         mutate_probability in &[0.0, 0.25, 0.5, 0.75, 1.0],
         entity_type in &[EntityType::U128, EntityType::U64, EntityType::U32],
         alignment in &[Alignment::Align16, Alignment::Align32, Alignment::Align64],
-        collection_type in &[CollectionType::CopyOnWrite, CollectionType::InsertOptimized, CollectionType::Mutable],
+        collection_type in &[
+            CollectionType::CopyOnWrite,
+            CollectionType::CopyOnWriteRebuild,
+            CollectionType::CopyOnWriteSmallBatch,
+            CollectionType::InsertOptimized,
+            CollectionType::Mutable,
+            CollectionType::Ordered,
+        ],
     }
     bench_apply,
 }
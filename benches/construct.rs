@@ -0,0 +1,91 @@
+use guacamole::combinators::*;
+use guacamole::Guacamole;
+use statslicer::{benchmark, black_box, statslicer_main, Bencher, Parameter, Parameters};
+
+use tnaps::{ComponentCollection, CopyOnWriteComponentCollection, ThreadPool};
+
+const ELEMENTS: &[usize] = &[1024, 16384, 262144, 1048576, 4194304];
+
+// NOTE(rescrv):  Zero threads is the sequential baseline:  a stable sort on the calling thread and
+// no thread pool.  The crossover is the smallest size at which some thread count beats it.
+const THREADS: &[usize] = &[0, 2, 4, 8];
+
+//////////////////////////////////////// ConstructParameters ///////////////////////////////////////
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct ConstructParameters {
+    elements: usize,
+    threads: usize,
+}
+
+impl Parameters for ConstructParameters {
+    fn params(&self) -> Vec<(&'static str, Parameter)> {
+        vec![
+            ("elements", Parameter::Integer(self.elements as u64)),
+            ("threads", Parameter::Integer(self.threads as u64)),
+        ]
+    }
+}
+
+/////////////////////////////////////////////// utils //////////////////////////////////////////////
+
+fn construct_sequential(mut pairs: Vec<(u128, u64)>) -> CopyOnWriteComponentCollection<u128, u64> {
+    pairs.sort_by_key(|(e, _)| *e);
+    let mut deduped: Vec<(u128, u64)> = Vec::with_capacity(pairs.len());
+    for (e, t) in pairs.into_iter() {
+        match deduped.last_mut() {
+            Some(last) if last.0 == e => {
+                *last = (e, t);
+            }
+            _ => {
+                deduped.push((e, t));
+            }
+        }
+    }
+    CopyOnWriteComponentCollection::from_iter(deduped)
+}
+
+///////////////////////////////////////////// construct ////////////////////////////////////////////
+
+fn bench_construct(params: &ConstructParameters, b: &mut Bencher) {
+    let mut guac = Guacamole::new(b.seed());
+    let mut inputs = vec![];
+    for _ in 0..b.size() {
+        let entities = to_vec(constant(params.elements), any::<u128>)(&mut guac);
+        let values = to_vec(constant(params.elements), any::<u64>)(&mut guac);
+        inputs.push(std::iter::zip(entities, values).collect::<Vec<_>>());
+    }
+    if params.threads == 0 {
+        b.run(|| {
+            for pairs in inputs.into_iter() {
+                black_box(construct_sequential(black_box(pairs)));
+            }
+        });
+    } else {
+        let thread_pool = ThreadPool::new("construct", params.threads);
+        b.run(|| {
+            for pairs in inputs.into_iter() {
+                black_box(CopyOnWriteComponentCollection::from_unsorted_parallel(
+                    black_box(pairs),
+                    &thread_pool,
+                ));
+            }
+        });
+        thread_pool.shutdown();
+    }
+}
+
+benchmark! {
+    name = construct;
+    ConstructParameters {
+        elements in ELEMENTS,
+        threads in THREADS,
+    }
+    bench_construct
+}
+
+/////////////////////////////////////////////// main ///////////////////////////////////////////////
+
+statslicer_main! {
+    construct,
+}
@@ -10,6 +10,13 @@ const CONSTRUCT_LENS: &[usize] = &[
 
 const MAP_TYPES: &[MapType] = &[MapType::Vec, MapType::Fast];
 
+// NOTE(rescrv):  VecEntityMap::offset_of compares u64 and u32 entities with AVX2 when the target
+// has it.  Build with RUSTFLAGS="-C target-feature=+avx2" to compare it against the scalar
+// binary search it replaces.
+const OFFSET_OF_MAP_TYPES: &[MapType] = &[MapType::Vec, MapType::VecScalar, MapType::Fast];
+
+const CONSTRUCT_MAP_TYPES: &[MapType] = &[MapType::Vec, MapType::Fast, MapType::FastSlice];
+
 const ENTITY_TYPES: &[EntityType] = &[EntityType::U128, EntityType::U64, EntityType::U32];
 
 ////////////////////////////////////////////// MapType /////////////////////////////////////////////
@@ -18,7 +25,9 @@ const ENTITY_TYPES: &[EntityType] = &[EntityType::U128, EntityType::U64, EntityT
 enum MapType {
     #[default]
     Vec,
+    VecScalar,
     Fast,
+    FastSlice,
 }
 
 //////////////////////////////////////////// EntityType ////////////////////////////////////////////
@@ -44,7 +53,9 @@ impl Parameters for EntityMapParameters {
     fn params(&self) -> Vec<(&'static str, Parameter)> {
         let map_type = match self.map_type {
             MapType::Fast => "fast",
+            MapType::FastSlice => "fast_slice",
             MapType::Vec => "vec",
+            MapType::VecScalar => "vec_scalar",
         };
         let entity_type = match self.entity_type {
             EntityType::U128 => "u128",
@@ -76,12 +87,16 @@ fn bench_construct_entity<E: Entity + FromGuacamole<()>>(
     fn construct_fast<E: Entity>(ents: Vec<E>) {
         black_box(FastEntityMap::<E>::from_iter(ents.into_iter()));
     }
+    fn construct_fast_slice<E: Entity>(ents: Vec<E>) {
+        black_box(FastEntityMap::<E>::from_sorted_slice(&ents));
+    }
     fn construct_vec<E: Entity>(ents: Vec<E>) {
         black_box(VecEntityMap::<E>::from_iter(ents.into_iter()));
     }
     let construct = match params.map_type {
         MapType::Fast => construct_fast,
-        MapType::Vec => construct_vec,
+        MapType::FastSlice => construct_fast_slice,
+        MapType::Vec | MapType::VecScalar => construct_vec,
     };
     b.run(|| {
         for ents in entities.into_iter() {
@@ -102,7 +117,7 @@ benchmark! {
     name = entity_map_construct;
     EntityMapParameters {
         elements in CONSTRUCT_LENS,
-        map_type in MAP_TYPES,
+        map_type in CONSTRUCT_MAP_TYPES,
         entity_type in ENTITY_TYPES,
     }
     bench_construct
@@ -120,7 +135,7 @@ fn bench_lower_bound_entity<E: Entity + FromGuacamole<()>>(
     entities.dedup();
     let queries = to_vec(constant(b.size()), any::<E>)(&mut guac);
     match &params.map_type {
-        MapType::Vec => {
+        MapType::Vec | MapType::VecScalar => {
             let entities = VecEntityMap::<E>::from_iter(entities);
             b.run(|| {
                 for query in queries.into_iter() {
@@ -128,7 +143,7 @@ fn bench_lower_bound_entity<E: Entity + FromGuacamole<()>>(
                 }
             });
         }
-        MapType::Fast => {
+        MapType::Fast | MapType::FastSlice => {
             let entities = FastEntityMap::<E>::from_iter(entities);
             b.run(|| {
                 for query in queries.into_iter() {
@@ -177,7 +192,16 @@ fn bench_offset_of_entity<E: Entity + FromGuacamole<()>>(
                 }
             });
         }
-        MapType::Fast => {
+        MapType::VecScalar => {
+            let entities = VecEntityMap::<E>::from_iter(entities);
+            let entities = entities.as_slice();
+            b.run(|| {
+                for query in queries.into_iter() {
+                    black_box(entities.partition_point(|e| *e < query));
+                }
+            });
+        }
+        MapType::Fast | MapType::FastSlice => {
             let entities = FastEntityMap::<E>::from_iter(entities);
             b.run(|| {
                 for query in queries.into_iter() {
@@ -200,7 +224,7 @@ benchmark! {
     name = entity_map_offset_of;
     EntityMapParameters {
         elements in CONSTRUCT_LENS,
-        map_type in MAP_TYPES,
+        map_type in OFFSET_OF_MAP_TYPES,
         entity_type in ENTITY_TYPES,
     }
     bench_offset_of
@@ -0,0 +1,171 @@
+use guacamole::combinators::*;
+use guacamole::{FromGuacamole, Guacamole};
+use statslicer::{benchmark, black_box, statslicer_main, Bencher, Parameter, Parameters};
+
+use tnaps::{
+    ComponentCollection, CopyOnWriteComponentCollection, Entity,
+    InsertOptimizedComponentCollection, MutableComponentCollection, OrderedComponentCollection,
+};
+
+const ELEMENTS: &[usize] = &[1024, 65536];
+
+const THREADS: &[usize] = &[1, 2, 4, 8];
+
+const COLLECTION_TYPES: &[CollectionType] = &[
+    CollectionType::CopyOnWrite,
+    CollectionType::InsertOptimized,
+    CollectionType::Mutable,
+    CollectionType::Ordered,
+];
+
+const METHODS: &[Method] = &[Method::GetRef, Method::GetCloned];
+
+////////////////////////////////////////// CollectionType //////////////////////////////////////////
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+enum CollectionType {
+    #[default]
+    CopyOnWrite,
+    InsertOptimized,
+    Mutable,
+    Ordered,
+}
+
+////////////////////////////////////////////// Method //////////////////////////////////////////////
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+enum Method {
+    #[default]
+    GetRef,
+    GetCloned,
+}
+
+/////////////////////////////////////////// GetParameters //////////////////////////////////////////
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct GetParameters {
+    elements: usize,
+    threads: usize,
+    collection_type: CollectionType,
+    method: Method,
+}
+
+impl Parameters for GetParameters {
+    fn params(&self) -> Vec<(&'static str, Parameter)> {
+        let collection_type = match self.collection_type {
+            CollectionType::CopyOnWrite => "cow",
+            CollectionType::InsertOptimized => "ins",
+            CollectionType::Mutable => "mut",
+            CollectionType::Ordered => "ord",
+        };
+        let method = match self.method {
+            Method::GetRef => "get_ref",
+            Method::GetCloned => "get_cloned",
+        };
+        vec![
+            ("elements", Parameter::Integer(self.elements as u64)),
+            ("threads", Parameter::Integer(self.threads as u64)),
+            (
+                "collection_type",
+                Parameter::Text(collection_type.to_string()),
+            ),
+            ("method", Parameter::Text(method.to_string())),
+        ]
+    }
+}
+
+/////////////////////////////////////////////// utils //////////////////////////////////////////////
+
+type Value = [u64; 8];
+
+fn entities<E: Entity + FromGuacamole<()>>(elements: usize, guac: &mut Guacamole) -> Vec<E> {
+    let mut entities = to_vec(constant(elements), any::<E>)(guac);
+    entities.sort();
+    entities.dedup();
+    entities
+}
+
+// NOTE(rescrv):  Every thread looks up its share of the queries concurrently, so the ref types
+// that hold a lock for as long as they live contend with one another.
+fn lookup<C: ComponentCollection<u128, Value> + Sync>(
+    collection: &C,
+    queries: &[u128],
+    threads: usize,
+    method: Method,
+) {
+    let chunk_size = queries.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        for chunk in queries.chunks(chunk_size) {
+            scope.spawn(move || {
+                for query in chunk.iter().copied() {
+                    match method {
+                        Method::GetRef => {
+                            black_box(collection.get_ref(query).map(|r| *r));
+                        }
+                        Method::GetCloned => {
+                            black_box(collection.get_cloned(query));
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+//////////////////////////////////////////////// get ///////////////////////////////////////////////
+
+fn bench_get_collection<C: ComponentCollection<u128, Value> + Sync>(
+    params: &GetParameters,
+    b: &mut Bencher,
+) {
+    let mut guac = Guacamole::new(b.seed());
+    let entities: Vec<u128> = entities(params.elements, &mut guac);
+    let values: Vec<Value> = to_vec(constant(entities.len()), any::<Value>)(&mut guac);
+    let collection = C::from_iter(std::iter::zip(entities.iter().copied(), values));
+    let queries: Vec<u128> = to_vec(constant(b.size()), range_to(entities.len()))(&mut guac)
+        .into_iter()
+        .map(|idx| entities[idx])
+        .collect();
+    b.run(|| {
+        lookup(
+            &collection,
+            black_box(&queries),
+            params.threads,
+            params.method,
+        );
+    });
+}
+
+fn bench_get(params: &GetParameters, b: &mut Bencher) {
+    match &params.collection_type {
+        CollectionType::CopyOnWrite => {
+            bench_get_collection::<CopyOnWriteComponentCollection<u128, Value>>(params, b)
+        }
+        CollectionType::InsertOptimized => {
+            bench_get_collection::<InsertOptimizedComponentCollection<u128, Value>>(params, b)
+        }
+        CollectionType::Mutable => {
+            bench_get_collection::<MutableComponentCollection<u128, Value>>(params, b)
+        }
+        CollectionType::Ordered => {
+            bench_get_collection::<OrderedComponentCollection<u128, Value>>(params, b)
+        }
+    }
+}
+
+benchmark! {
+    name = get;
+    GetParameters {
+        elements in ELEMENTS,
+        threads in THREADS,
+        collection_type in COLLECTION_TYPES,
+        method in METHODS,
+    }
+    bench_get
+}
+
+/////////////////////////////////////////////// main ///////////////////////////////////////////////
+
+statslicer_main! {
+    get,
+}
@@ -0,0 +1,125 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use guacamole::combinators::*;
+use guacamole::{FromGuacamole, Guacamole};
+use statslicer::{benchmark, black_box, statslicer_main, Bencher, Parameter, Parameters};
+
+use tnaps::{BuildIdentityHasher, Entity};
+
+const ELEMENTS: &[usize] = &[1024, 65536, 1048576];
+
+const HASHER_TYPES: &[HasherType] = &[HasherType::Sip, HasherType::Identity];
+
+const ENTITY_TYPES: &[EntityType] = &[EntityType::U128, EntityType::U64, EntityType::U32];
+
+//////////////////////////////////////////// HasherType ////////////////////////////////////////////
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+enum HasherType {
+    #[default]
+    Sip,
+    Identity,
+}
+
+//////////////////////////////////////////// EntityType ////////////////////////////////////////////
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+enum EntityType {
+    #[default]
+    U128,
+    U64,
+    U32,
+}
+
+////////////////////////////////////// IdentityHashParameters //////////////////////////////////////
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct IdentityHashParameters {
+    elements: usize,
+    hasher_type: HasherType,
+    entity_type: EntityType,
+}
+
+impl Parameters for IdentityHashParameters {
+    fn params(&self) -> Vec<(&'static str, Parameter)> {
+        let hasher_type = match self.hasher_type {
+            HasherType::Sip => "sip",
+            HasherType::Identity => "identity",
+        };
+        let entity_type = match self.entity_type {
+            EntityType::U128 => "u128",
+            EntityType::U64 => "u64",
+            EntityType::U32 => "u32",
+        };
+        vec![
+            ("elements", Parameter::Integer(self.elements as u64)),
+            ("hasher_type", Parameter::Text(hasher_type.to_string())),
+            ("entity_type", Parameter::Text(entity_type.to_string())),
+        ]
+    }
+}
+
+////////////////////////////////////////////// lookup //////////////////////////////////////////////
+
+fn bench_lookup_hasher<E: Entity + FromGuacamole<()>, S: BuildHasher + Default>(
+    params: &IdentityHashParameters,
+    b: &mut Bencher,
+) {
+    let mut guac = Guacamole::new(b.seed());
+    let entities = to_vec(constant(params.elements), any::<E>)(&mut guac);
+    let mut map: HashMap<E, u64, S> =
+        HashMap::with_capacity_and_hasher(entities.len(), S::default());
+    for (idx, entity) in entities.iter().enumerate() {
+        map.insert(*entity, idx as u64);
+    }
+    // NOTE(rescrv):  Half the queries hit and half miss, as lookups of entities without the
+    // component would.
+    let queries: Vec<E> = to_vec(constant(b.size()), |guac: &mut Guacamole| {
+        if prob(0.5)(guac) {
+            entities[range_to(entities.len())(guac)]
+        } else {
+            any::<E>(guac)
+        }
+    })(&mut guac);
+    b.run(|| {
+        for query in queries.into_iter() {
+            black_box(map.get(&query));
+        }
+    });
+}
+
+fn bench_lookup_entity<E: Entity + FromGuacamole<()>>(
+    params: &IdentityHashParameters,
+    b: &mut Bencher,
+) {
+    match &params.hasher_type {
+        HasherType::Sip => bench_lookup_hasher::<E, RandomState>(params, b),
+        HasherType::Identity => bench_lookup_hasher::<E, BuildIdentityHasher>(params, b),
+    }
+}
+
+fn bench_lookup(params: &IdentityHashParameters, b: &mut Bencher) {
+    match &params.entity_type {
+        EntityType::U128 => bench_lookup_entity::<u128>(params, b),
+        EntityType::U64 => bench_lookup_entity::<u64>(params, b),
+        EntityType::U32 => bench_lookup_entity::<u32>(params, b),
+    }
+}
+
+benchmark! {
+    name = identity_hash_lookup;
+    IdentityHashParameters {
+        elements in ELEMENTS,
+        hasher_type in HASHER_TYPES,
+        entity_type in ENTITY_TYPES,
+    }
+    bench_lookup
+}
+
+/////////////////////////////////////////////// main ///////////////////////////////////////////////
+
+statslicer_main! {
+    identity_hash_lookup,
+}
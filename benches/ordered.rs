@@ -0,0 +1,189 @@
+use guacamole::combinators::*;
+use guacamole::{FromGuacamole, Guacamole};
+use statslicer::{benchmark, black_box, statslicer_main, Bencher, Parameter, Parameters};
+
+use tnaps::{
+    ComponentCollection, Entity, InsertOptimizedComponentCollection, OrderedComponentCollection,
+};
+
+const ELEMENTS: &[usize] = &[1024, 16384, 65536, 262144];
+
+const SCAN_LENS: &[usize] = &[1, 16, 256];
+
+const COLLECTION_TYPES: &[CollectionType] =
+    &[CollectionType::InsertOptimized, CollectionType::Ordered];
+
+const ENTITY_TYPES: &[EntityType] = &[EntityType::U128, EntityType::U64, EntityType::U32];
+
+////////////////////////////////////////// CollectionType //////////////////////////////////////////
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+enum CollectionType {
+    #[default]
+    InsertOptimized,
+    Ordered,
+}
+
+//////////////////////////////////////////// EntityType ////////////////////////////////////////////
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+enum EntityType {
+    #[default]
+    U128,
+    U64,
+    U32,
+}
+
+///////////////////////////////////////// OrderedParameters ////////////////////////////////////////
+
+#[derive(Debug, Default, Eq, PartialEq)]
+struct OrderedParameters {
+    elements: usize,
+    scan_len: usize,
+    collection_type: CollectionType,
+    entity_type: EntityType,
+}
+
+impl Parameters for OrderedParameters {
+    fn params(&self) -> Vec<(&'static str, Parameter)> {
+        let collection_type = match self.collection_type {
+            CollectionType::InsertOptimized => "ins",
+            CollectionType::Ordered => "ord",
+        };
+        let entity_type = match self.entity_type {
+            EntityType::U128 => "u128",
+            EntityType::U64 => "u64",
+            EntityType::U32 => "u32",
+        };
+        vec![
+            ("elements", Parameter::Integer(self.elements as u64)),
+            ("scan_len", Parameter::Integer(self.scan_len as u64)),
+            (
+                "collection_type",
+                Parameter::Text(collection_type.to_string()),
+            ),
+            ("entity_type", Parameter::Text(entity_type.to_string())),
+        ]
+    }
+}
+
+/////////////////////////////////////////////// utils //////////////////////////////////////////////
+
+fn entities<E: Entity + FromGuacamole<()>>(elements: usize, guac: &mut Guacamole) -> Vec<E> {
+    let mut entities = to_vec(constant(elements), any::<E>)(guac);
+    entities.sort();
+    entities.dedup();
+    entities
+}
+
+////////////////////////////////////////////// insert //////////////////////////////////////////////
+
+fn bench_insert_entity<E: Entity + FromGuacamole<()>>(params: &OrderedParameters, b: &mut Bencher) {
+    let mut guac = Guacamole::new(b.seed());
+    let entities: Vec<E> = entities(params.elements, &mut guac);
+    let components = entities.iter().map(|e| (*e, 0u64));
+    let inserts = to_vec(constant(b.size()), any::<E>)(&mut guac);
+    match &params.collection_type {
+        CollectionType::InsertOptimized => {
+            let collection = InsertOptimizedComponentCollection::from_iter(components);
+            b.run(|| {
+                for entity in inserts.into_iter() {
+                    black_box(collection.insert(entity, 1));
+                }
+            });
+        }
+        CollectionType::Ordered => {
+            let collection = OrderedComponentCollection::from_iter(components);
+            b.run(|| {
+                for entity in inserts.into_iter() {
+                    black_box(collection.insert(entity, 1));
+                }
+            });
+        }
+    }
+}
+
+fn bench_insert(params: &OrderedParameters, b: &mut Bencher) {
+    match &params.entity_type {
+        EntityType::U128 => bench_insert_entity::<u128>(params, b),
+        EntityType::U64 => bench_insert_entity::<u64>(params, b),
+        EntityType::U32 => bench_insert_entity::<u32>(params, b),
+    }
+}
+
+benchmark! {
+    name = ordered_insert;
+    OrderedParameters {
+        elements in ELEMENTS,
+        scan_len in &[0],
+        collection_type in COLLECTION_TYPES,
+        entity_type in ENTITY_TYPES,
+    }
+    bench_insert
+}
+
+/////////////////////////////////////////////// scan ///////////////////////////////////////////////
+
+fn scan<E: Entity, C: ComponentCollection<E, u64>>(collection: &C, lo: E, hi: E) -> u64 {
+    collection
+        .iter_range(lo, hi)
+        .map(|(_, component)| component)
+        .fold(0, u64::wrapping_add)
+}
+
+fn bench_scan_entity<E: Entity + FromGuacamole<()>>(params: &OrderedParameters, b: &mut Bencher) {
+    let mut guac = Guacamole::new(b.seed());
+    let entities: Vec<E> = entities(params.elements, &mut guac);
+    let components = entities.iter().map(|e| (*e, 1u64));
+    let ranges: Vec<(E, E)> = to_vec(constant(b.size()), range_to(entities.len()))(&mut guac)
+        .into_iter()
+        .map(|idx| {
+            let hi = (idx + params.scan_len).min(entities.len() - 1);
+            (entities[idx], entities[hi])
+        })
+        .collect();
+    match &params.collection_type {
+        CollectionType::InsertOptimized => {
+            let collection = InsertOptimizedComponentCollection::from_iter(components);
+            b.run(|| {
+                for (lo, hi) in ranges.into_iter() {
+                    black_box(scan(&collection, lo, hi));
+                }
+            });
+        }
+        CollectionType::Ordered => {
+            let collection = OrderedComponentCollection::from_iter(components);
+            b.run(|| {
+                for (lo, hi) in ranges.into_iter() {
+                    black_box(scan(&collection, lo, hi));
+                }
+            });
+        }
+    }
+}
+
+fn bench_scan(params: &OrderedParameters, b: &mut Bencher) {
+    match &params.entity_type {
+        EntityType::U128 => bench_scan_entity::<u128>(params, b),
+        EntityType::U64 => bench_scan_entity::<u64>(params, b),
+        EntityType::U32 => bench_scan_entity::<u32>(params, b),
+    }
+}
+
+benchmark! {
+    name = ordered_scan;
+    OrderedParameters {
+        elements in ELEMENTS,
+        scan_len in SCAN_LENS,
+        collection_type in COLLECTION_TYPES,
+        entity_type in ENTITY_TYPES,
+    }
+    bench_scan
+}
+
+/////////////////////////////////////////////// main ///////////////////////////////////////////////
+
+statslicer_main! {
+    ordered_insert,
+    ordered_scan,
+}
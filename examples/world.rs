@@ -0,0 +1,84 @@
+//! An example ECS world using tnaps.
+
+use std::sync::Arc;
+
+use tnaps::{
+    system, system_parallel, world, ComponentChange, ComponentCollection, ComponentRef,
+    CopyOnWriteComponentCollection, CopyOnWriteComponentRef, Entity as EntityTrait,
+    MutableComponentCollection, MutableComponentRef, PartitioningScheme, ThreadPool,
+    VecPartitioningScheme,
+};
+
+type Entity = u128;
+
+world! {
+    Universe<Entity> partitioned PartitionedUniverse {
+        position: CopyOnWriteComponentCollection<f64>,
+        velocity: MutableComponentCollection<f64>,
+    }
+}
+
+struct Move;
+
+system! {
+    Move<Entity> {
+        position: CopyOnWriteComponentCollection<f64>,
+        velocity: MutableComponentCollection<f64>,
+    }
+}
+
+impl Move {
+    fn process(
+        &self,
+        _: Entity,
+        position: &mut CopyOnWriteComponentRef<f64>,
+        velocity: &mut MutableComponentRef<f64>,
+    ) {
+        position.update(|p| *p += **velocity);
+        velocity.update(|v| *v *= 0.5);
+    }
+}
+
+struct MoveParallel;
+
+system_parallel! {
+    MoveParallel<Entity> {
+        position: CopyOnWriteComponentCollection<f64>,
+        velocity: MutableComponentCollection<f64>,
+    }
+}
+
+impl MoveParallel {
+    fn process(
+        &self,
+        entity: Entity,
+        position: &mut CopyOnWriteComponentRef<f64>,
+        velocity: &mut MutableComponentRef<f64>,
+    ) {
+        Move.process(entity, position, velocity);
+    }
+}
+
+fn main() {
+    let mut universe = Universe {
+        position: CopyOnWriteComponentCollection::from_iter((1..=8u128).map(|e| (e, 0.0))),
+        velocity: MutableComponentCollection::from_iter((1..=8u128).map(|e| (e, e as f64))),
+    };
+    println!("----");
+    let changes = Move.run(&mut universe.position, &mut universe.velocity);
+    universe.apply_all(changes);
+    println!("universe: {:?}", universe);
+    let changes = Move.run_subset(&[1], &mut universe.position, &mut universe.velocity);
+    assert_eq!(1, changes.0.len());
+    universe.apply_all(changes);
+    println!("universe: {:?}", universe);
+    println!("----");
+    let partitioning: Arc<dyn PartitioningScheme<Entity>> =
+        Arc::new(VecPartitioningScheme::from(vec![3, 6]));
+    let mut universe = universe.partition_all(&partitioning);
+    let thread_pool = ThreadPool::new("world", 2);
+    let changes =
+        Arc::new(MoveParallel).run(&thread_pool, &universe.position, &universe.velocity)();
+    universe.apply_all(changes);
+    println!("universe: {:?}", universe);
+}
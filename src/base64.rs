@@ -2,9 +2,21 @@ const BASE64: &[char] = &[
     'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S',
     'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l',
     'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '0', '1', '2', '3', '4',
-    '5', '6', '7', '8', '9', ' ', ' ',
+    '5', '6', '7', '8', '9', '+', '/',
 ];
 
+const INVALID: u8 = 0xff;
+
+const DECODE: [u8; 256] = {
+    let mut table = [INVALID; 256];
+    let mut idx = 0;
+    while idx < BASE64.len() {
+        table[BASE64[idx] as usize] = idx as u8;
+        idx += 1;
+    }
+    table
+};
+
 fn encode_one(input: [u8; 3]) -> [char; 4] {
     let mut index = [0u8; 4];
     index[0] = input[0] >> 2;
@@ -45,3 +57,74 @@ pub fn encode(mut bytes: &[u8]) -> String {
     }
     encoded
 }
+
+fn decode_one(input: [u8; 4]) -> Option<[u8; 3]> {
+    let mut index = [0u8; 4];
+    for (idx, c) in input.into_iter().enumerate() {
+        index[idx] = DECODE[c as usize];
+        if index[idx] == INVALID {
+            return None;
+        }
+    }
+    let mut output = [0u8; 3];
+    output[0] = (index[0] << 2) + (index[1] >> 4);
+    output[1] = ((index[1] & 0x0f) << 4) + (index[2] >> 2);
+    output[2] = ((index[2] & 0x03) << 6) + index[3];
+    Some(output)
+}
+
+/// Decode the output of [encode].  Returns None if the string contains characters outside the
+/// alphabet or is not a length that [encode] could have produced.
+pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut chars = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(chars.len() * 6 / 8);
+    while chars.len() >= 4 {
+        let input = [chars[0], chars[1], chars[2], chars[3]];
+        let output = decode_one(input)?;
+        chars = &chars[4..];
+        decoded.extend_from_slice(&output);
+    }
+    let (input, sz) = match chars.len() {
+        3 => ([chars[0], chars[1], chars[2], BASE64[0] as u8], 2),
+        2 => ([chars[0], chars[1], BASE64[0] as u8, BASE64[0] as u8], 1),
+        0 => {
+            return Some(decoded);
+        }
+        _ => {
+            return None;
+        }
+    };
+    let output = decode_one(input)?;
+    // Reject encodings whose trailing bits are not zero; encode never produces them.
+    if output[sz..].iter().any(|b| *b != 0) {
+        return None;
+    }
+    decoded.extend_from_slice(&output[..sz]);
+    Some(decoded)
+}
+
+/////////////////////////////////////////////// tests //////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest::proptest! {
+        #[test]
+        fn round_trip(bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..=32)) {
+            assert_eq!(Some(bytes.clone()), decode(&encode(&bytes)));
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(Some(vec![]), decode(""));
+        assert_eq!(None, decode("A"));
+        assert_eq!(None, decode("AAAAA"));
+        assert_eq!(None, decode("AA A"));
+        assert_eq!(None, decode("AB"));
+        assert_eq!(Some(vec![0]), decode("AA"));
+        assert_eq!(None, decode("AAB"));
+        assert_eq!(Some(vec![0, 0]), decode("AAA"));
+    }
+}
@@ -1,7 +1,10 @@
 use std::fmt::Debug;
 use std::ops::Deref;
 
-use super::{ComponentChange, ComponentCollection, ComponentRef};
+use super::{
+    apply_component_changes, apply_component_changes_to_vecs, ComponentChange, ComponentCollection,
+    ComponentRef, SupportsDirectMutation,
+};
 use crate::{Entity, EntityMap, VecEntityMap};
 
 ////////////////////////////////////// CopyOnWriteComponentRef /////////////////////////////////////
@@ -19,6 +22,30 @@ impl<'a, T: Debug> CopyOnWriteComponentRef<'a, T> {
         let out = None;
         Self { unbound, this, out }
     }
+
+    /// Update the value by moving it through `f`, without requiring `T: Clone`.  The first update
+    /// has no owned value to move because the collection's copy is borrowed, so `rebuild`
+    /// constructs one from it; subsequent updates move the staged value out and back in.
+    pub fn update_taken<R: FnOnce(&T) -> T, F: FnOnce(T) -> T>(&mut self, rebuild: R, f: F) {
+        self.unbound = false;
+        let taken = match self.out.take() {
+            Some(value) => value,
+            None => rebuild(self.this),
+        };
+        self.out = Some(f(taken));
+    }
+
+    /// Consume this reference and make a [ComponentChange].  This is [ComponentRef::change] for
+    /// when `T` is not `Clone`.
+    pub fn into_change(self) -> ComponentChange<T> {
+        if self.unbound {
+            ComponentChange::Unbind
+        } else if let Some(value) = self.out {
+            ComponentChange::Value(value)
+        } else {
+            ComponentChange::NoChange
+        }
+    }
 }
 
 impl<'a, T: Debug> Debug for CopyOnWriteComponentRef<'a, T> {
@@ -42,9 +69,11 @@ impl<'a, T: Debug> Deref for CopyOnWriteComponentRef<'a, T> {
 impl<'a, T: Debug + Clone> ComponentRef<T> for CopyOnWriteComponentRef<'a, T> {
     fn unbind(&mut self) {
         self.unbound = true;
+        self.out = None;
     }
 
     fn update<F: FnOnce(&mut T) -> U, U>(&mut self, f: F) -> U {
+        self.unbound = false;
         if self.out.is_none() {
             self.out = Some(self.this.clone());
         }
@@ -52,13 +81,15 @@ impl<'a, T: Debug + Clone> ComponentRef<T> for CopyOnWriteComponentRef<'a, T> {
     }
 
     fn change(self) -> ComponentChange<T> {
-        if self.unbound {
-            ComponentChange::Unbind
-        } else if let Some(value) = self.out {
-            ComponentChange::Value(value)
-        } else {
-            ComponentChange::NoChange
-        }
+        self.into_change()
+    }
+
+    fn is_unbound(&self) -> bool {
+        self.unbound
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.out.is_some()
     }
 }
 
@@ -84,6 +115,101 @@ impl<E: Entity, T: Debug> Default for CopyOnWriteComponentCollection<E, T> {
     }
 }
 
+impl<E: Entity, T: Debug> CopyOnWriteComponentCollection<E, T> {
+    /// The number of components the collection can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.entities.capacity().min(self.components.capacity())
+    }
+
+    /// Borrow the component bound to `entity` directly, without constructing a
+    /// [CopyOnWriteComponentRef].  Useful for read-only systems.
+    pub fn peek(&self, entity: E) -> Option<&T> {
+        self.entities
+            .exact_offset_of(entity)
+            .map(|offset| &self.components[offset])
+    }
+
+    /// Get a reference to the component held for entity, if it exists.  This is
+    /// [ComponentCollection::get_ref] for when `T` is not `Clone`; use
+    /// [CopyOnWriteComponentRef::update_taken] and [CopyOnWriteComponentRef::into_change] to
+    /// change the component.
+    pub fn get_cow_ref(&self, entity: E) -> Option<CopyOnWriteComponentRef<'_, T>> {
+        self.entities
+            .exact_offset_of(entity)
+            .map(|offset| CopyOnWriteComponentRef::new(&self.components[offset]))
+    }
+
+    /// Borrow every binding, ordered by `compare` applied to the components rather than by
+    /// entity.  The sort is stable, so entities with equal components stay in entity order.  The
+    /// collection itself keeps its entity order, so lookups by entity are unaffected.
+    pub fn sorted_by_component<F: Fn(&T, &T) -> std::cmp::Ordering>(
+        &self,
+        compare: F,
+    ) -> SortedByComponent<'_, E, T> {
+        let mut bindings: Vec<(E, &T)> = (0..self.entities.len())
+            .map(|offset| (self.entities.get(offset), &self.components[offset]))
+            .collect();
+        bindings.sort_by(|lhs, rhs| compare(lhs.1, rhs.1));
+        SortedByComponent { bindings }
+    }
+}
+
+impl<E: Entity, T: Debug + Clone> CopyOnWriteComponentCollection<E, T> {
+    /// Apply changes by editing components in place, splicing only the tail of the collection
+    /// from the first change that binds a new entity or unbinds one.  Every change costs a binary
+    /// search, so this beats [Self::apply_rebuild] when there are few changes relative to the
+    /// size of the collection.  [ComponentCollection::apply] chooses between the two.
+    ///
+    /// It is undefined behavior to pass changes not sorted by entity value.
+    pub fn apply_small_batch<I: IntoIterator<Item = (E, ComponentChange<T>)>>(
+        &mut self,
+        changes: I,
+    ) {
+        let mut changes = changes.into_iter().peekable();
+        while let Some((entity, change)) = changes.peek() {
+            let offset = self.entities.offset_of(*entity);
+            let bound = offset < self.entities.len() && self.entities.get(offset) == *entity;
+            let in_place = match change {
+                ComponentChange::NoChange => true,
+                ComponentChange::Unbind => !bound,
+                ComponentChange::Value(_) => bound,
+            };
+            if !in_place {
+                // NOTE(rescrv):  Changes are sorted, so none of the rest sort before offset.
+                let entities = self.entities.split_off(offset);
+                let components = self.components.split_off(offset);
+                let tail = Self {
+                    entities,
+                    components,
+                };
+                let mut tail = apply_component_changes(tail, changes);
+                self.entities.append(tail.entities);
+                self.components.append(&mut tail.components);
+                return;
+            }
+            if let Some((_, ComponentChange::Value(component))) = changes.next() {
+                self.components[offset] = component;
+            }
+        }
+    }
+
+    /// Apply changes by merging them with the whole collection into a new one.  Linear in the
+    /// size of the collection no matter how few changes there are.  The new collection keeps the
+    /// capacity of the old, including any set aside with [ComponentCollection::reserve].
+    ///
+    /// It is undefined behavior to pass changes not sorted by entity value.
+    pub fn apply_rebuild<I: IntoIterator<Item = (E, ComponentChange<T>)>>(&mut self, changes: I) {
+        let this = std::mem::take(self);
+        let (entities, components) = apply_component_changes_to_vecs(
+            this.entities.into_vec(),
+            this.components,
+            changes.into_iter(),
+        );
+        self.entities = VecEntityMap::from_sorted_vec(entities);
+        self.components = components;
+    }
+}
+
 impl<E: Entity, T: Debug + Clone> ComponentCollection<E, T>
     for CopyOnWriteComponentCollection<E, T>
 {
@@ -102,10 +228,89 @@ impl<E: Entity, T: Debug + Clone> ComponentCollection<E, T>
         self.entities.lower_bound(lower_bound)
     }
 
+    fn first(&self) -> Option<E> {
+        if self.entities.is_empty() {
+            None
+        } else {
+            Some(self.entities.get(0))
+        }
+    }
+
+    fn last(&self) -> Option<E> {
+        if self.entities.is_empty() {
+            None
+        } else {
+            Some(self.entities.get(self.entities.len() - 1))
+        }
+    }
+
+    fn contains_entity(&self, entity: E) -> bool {
+        self.entities.exact_offset_of(entity).is_some()
+    }
+
     fn get_ref(&self, entity: E) -> Option<Self::Ref<'_>> {
+        self.get_cow_ref(entity)
+    }
+
+    /// Apply with [Self::apply_small_batch] when there are fewer than `len / log2(len)` changes,
+    /// going by the upper bound of their size hint, and with [Self::apply_rebuild] otherwise.
+    fn apply<I: IntoIterator<Item = (E, ComponentChange<T>)>>(&mut self, changes: I) {
+        let changes = changes.into_iter();
+        let len = self.entities.len();
+        let threshold = len / (len.checked_ilog2().unwrap_or(0) as usize + 1);
+        if changes.size_hint().1.is_some_and(|upper| upper < threshold) {
+            self.apply_small_batch(changes);
+        } else {
+            self.apply_rebuild(changes);
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.entities.reserve(additional);
+        self.components.reserve(additional);
+    }
+
+    fn next_free(&self, after: E) -> Option<E> {
+        self.entities.next_free(after)
+    }
+
+    fn truncate_to(&mut self, n: usize) {
+        if n < self.entities.len() {
+            self.entities.split_off(n);
+            self.components.truncate(n);
+        }
+    }
+
+    fn skip_first(&mut self, n: usize) {
+        let n = n.min(self.entities.len());
+        self.entities = self.entities.split_off(n);
+        self.components.drain(..n);
+    }
+
+    fn split_at(mut self, pivot: E) -> (Self, Self) {
+        let at = self.entities.offset_of(pivot);
+        let entities = self.entities.split_off(at);
+        let components = self.components.split_off(at);
+        let upper = Self {
+            entities,
+            components,
+        };
+        (self, upper)
+    }
+
+    fn iter_range<'a>(&'a self, lo: E, hi: E) -> impl Iterator<Item = (E, T)>
+    where
+        T: Clone + 'a,
+    {
+        let start = self.entities.offset_of(lo);
+        let limit = self.entities.offset_of(hi);
+        (start..limit).map(move |idx| (self.entities.get(idx), self.components[idx].clone()))
+    }
+
+    fn get_cloned(&self, entity: E) -> Option<T> {
         self.entities
             .exact_offset_of(entity)
-            .map(|offset| CopyOnWriteComponentRef::new(&self.components[offset]))
+            .map(|offset| self.components[offset].clone())
     }
 
     fn consume(self) -> Self::Consumed {
@@ -113,6 +318,27 @@ impl<E: Entity, T: Debug + Clone> ComponentCollection<E, T>
     }
 }
 
+impl<E: Entity, T: Debug + Clone> SupportsDirectMutation<E, T>
+    for CopyOnWriteComponentCollection<E, T>
+{
+    fn insert_one(&mut self, entity: E, component: T) -> Option<T> {
+        let offset = self.entities.offset_of(entity);
+        if offset < self.entities.len() && self.entities.get(offset) == entity {
+            Some(std::mem::replace(&mut self.components[offset], component))
+        } else {
+            self.entities.insert(offset, entity);
+            self.components.insert(offset, component);
+            None
+        }
+    }
+
+    fn remove_one(&mut self, entity: E) -> Option<T> {
+        let offset = self.entities.exact_offset_of(entity)?;
+        self.entities.remove(offset);
+        Some(self.components.remove(offset))
+    }
+}
+
 impl<E: Entity, T: Debug> FromIterator<(E, T)> for CopyOnWriteComponentCollection<E, T> {
     fn from_iter<I: IntoIterator<Item = (E, T)>>(iter: I) -> Self {
         let mut entities = vec![];
@@ -149,6 +375,57 @@ impl<E: Entity, T: Debug> FromIterator<(E, ComponentChange<T>)>
     }
 }
 
+/// Collect in parallel into a vector that then gets sorted by entity before building the
+/// collection.  The sort is stable, so entities must still be unique.
+#[cfg(feature = "rayon")]
+impl<E: Entity + Send, T: Debug + Send> rayon::iter::FromParallelIterator<(E, T)>
+    for CopyOnWriteComponentCollection<E, T>
+{
+    fn from_par_iter<I: rayon::iter::IntoParallelIterator<Item = (E, T)>>(iter: I) -> Self {
+        use rayon::iter::ParallelIterator;
+        use rayon::slice::ParallelSliceMut;
+        let mut pairs: Vec<(E, T)> = iter.into_par_iter().collect();
+        pairs.par_sort_by_key(|(e, _)| *e);
+        Self::from_iter(pairs)
+    }
+}
+
+///////////////////////////////////////// SortedByComponent ////////////////////////////////////////
+
+/// The bindings of a [CopyOnWriteComponentCollection] ordered by component rather than entity.
+/// Returned by [CopyOnWriteComponentCollection::sorted_by_component].  There is no lookup by
+/// entity; iterate instead.
+#[derive(Debug)]
+pub struct SortedByComponent<'a, E: Entity, T: Debug> {
+    bindings: Vec<(E, &'a T)>,
+}
+
+impl<'a, E: Entity, T: Debug> SortedByComponent<'a, E, T> {
+    /// True if there are no bindings.
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    /// The number of bindings.
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    /// Iterate the bindings in component order.
+    pub fn iter(&self) -> impl Iterator<Item = (E, &'a T)> + '_ {
+        self.bindings.iter().copied()
+    }
+}
+
+impl<'a, E: Entity, T: Debug> IntoIterator for SortedByComponent<'a, E, T> {
+    type Item = (E, &'a T);
+    type IntoIter = std::vec::IntoIter<(E, &'a T)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.bindings.into_iter()
+    }
+}
+
 /////////////////////////////////////////////// tests //////////////////////////////////////////////
 
 #[cfg(test)]
@@ -156,11 +433,149 @@ mod tests {
     use super::super::tests::{arb_entities, collection_properties};
 
     use super::CopyOnWriteComponentCollection;
+    use crate::{ComponentChange, ComponentCollection, ComponentRef};
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct CloneBomb(u64);
+
+    impl Clone for CloneBomb {
+        fn clone(&self) -> Self {
+            panic!("cloned {:?}", self);
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct NotClone(Vec<u64>);
+
+    #[test]
+    fn last_operation_wins() {
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(1u64, 10u64)]);
+        let mut r = collection.get_cow_ref(1).unwrap();
+        r.unbind();
+        r.update(|x| *x += 1);
+        assert!(matches!(r.into_change(), ComponentChange::Value(11)));
+        let mut r = collection.get_cow_ref(1).unwrap();
+        r.update(|x| *x += 1);
+        r.unbind();
+        assert!(matches!(r.into_change(), ComponentChange::Unbind));
+    }
+
+    #[test]
+    fn update_taken() {
+        let collection =
+            CopyOnWriteComponentCollection::from_iter(vec![(1u64, NotClone(vec![1, 2, 3]))]);
+        let mut rebuilds = 0;
+        let mut r = collection.get_cow_ref(1).unwrap();
+        for x in [4, 5] {
+            r.update_taken(
+                |this| {
+                    rebuilds += 1;
+                    NotClone(this.0.clone())
+                },
+                |mut taken| {
+                    taken.0.push(x);
+                    taken
+                },
+            );
+        }
+        assert_eq!(1, rebuilds);
+        assert_eq!(NotClone(vec![1, 2, 3, 4, 5]), *r);
+        assert_eq!(Some(&NotClone(vec![1, 2, 3])), collection.peek(1));
+        let ComponentChange::Value(value) = r.into_change() else {
+            panic!("expected a value");
+        };
+        assert_eq!(NotClone(vec![1, 2, 3, 4, 5]), value);
+        let untouched = collection.get_cow_ref(1).unwrap();
+        assert!(untouched.into_change().is_no_change());
+    }
+
+    #[test]
+    fn sorted_by_component() {
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![
+            (1u128, 30u64),
+            (2, 10),
+            (3, 20),
+            (4, 10),
+        ]);
+        let sorted = collection.sorted_by_component(|lhs, rhs| lhs.cmp(rhs));
+        assert_eq!(4, sorted.len());
+        assert_eq!(
+            vec![(2, &10), (4, &10), (3, &20), (1, &30)],
+            sorted.iter().collect::<Vec<_>>()
+        );
+        let sorted = collection.sorted_by_component(|lhs, rhs| rhs.cmp(lhs));
+        assert_eq!(
+            vec![(1, &30), (3, &20), (2, &10), (4, &10)],
+            sorted.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(3), collection.lower_bound(3));
+        assert_eq!(Some(&20), collection.peek(3));
+    }
+
+    proptest::prop_compose! {
+        fn arb_small_batch()(
+            initial in proptest::collection::btree_set(0u64..4096, 0..2048),
+            changes in proptest::collection::btree_map(0u64..4096, 0usize..3, 0..128),
+        ) -> (Vec<u64>, Vec<(u64, usize)>) {
+            (initial.into_iter().collect(), changes.into_iter().collect())
+        }
+    }
 
     proptest::proptest! {
+        #[test]
+        fn apply_small_batch(input in arb_small_batch()) {
+            let (initial, changes) = input;
+            // Build the changes afresh for each use because ComponentChange is not Clone.
+            let changes = || {
+                changes.iter().map(|(e, kind)| match kind {
+                    0 => (*e, ComponentChange::NoChange),
+                    1 => (*e, ComponentChange::Unbind),
+                    _ => (*e, ComponentChange::Value(*e * 100)),
+                })
+            };
+            let collection = || CopyOnWriteComponentCollection::from_iter(initial.iter().map(|e| (*e, *e)));
+            let mut small_batch = collection();
+            small_batch.apply_small_batch(changes());
+            let mut rebuild = collection();
+            rebuild.apply_rebuild(changes());
+            let mut applied = collection();
+            applied.apply(changes().collect::<Vec<_>>());
+            let expected = rebuild.consume().collect::<Vec<_>>();
+            assert_eq!(expected, small_batch.consume().collect::<Vec<_>>());
+            assert_eq!(expected, applied.consume().collect::<Vec<_>>());
+        }
+
         #[test]
         fn cow_collection_properties(entities in arb_entities()) {
             collection_properties::<u128, usize, CopyOnWriteComponentCollection<u128, usize>>(entities);
         }
+
+        #[test]
+        fn peek(entities in arb_entities()) {
+            let collection = CopyOnWriteComponentCollection::from_iter(
+                entities.iter().map(|(e, v)| (*e, CloneBomb(*v as u64))),
+            );
+            for (e, v) in entities.iter() {
+                assert_eq!(Some(&CloneBomb(*v as u64)), collection.peek(*e));
+                assert_eq!(collection.peek(*e).unwrap(), &*collection.get_ref(*e).unwrap());
+                let next = e.wrapping_add(1);
+                assert_eq!(collection.contains_entity(next), collection.peek(next).is_some());
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn from_par_iter() {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let collection: CopyOnWriteComponentCollection<u128, usize> = (0..65536usize)
+            .into_par_iter()
+            .map(|x| ((65536 - x) as u128, x))
+            .collect();
+        let consumed: Vec<(u128, usize)> = collection.consume().collect();
+        let expected: Vec<(u128, usize)> =
+            (1..=65536usize).map(|x| (x as u128, 65536 - x)).collect();
+        assert_eq!(expected, consumed);
     }
 }
@@ -4,7 +4,7 @@ use std::fmt::Debug;
 use std::ops::{Bound, Deref};
 use std::sync::{Mutex, MutexGuard};
 
-use super::{ComponentChange, ComponentCollection, ComponentRef};
+use super::{ComponentChange, ComponentCollection, ComponentRef, SupportsDirectMutation};
 use crate::Entity;
 
 //////////////////////////////////////////// Components ////////////////////////////////////////////
@@ -25,57 +25,93 @@ impl<T: Debug> Default for Components<T> {
 
 //////////////////////////////////// InsertOptimizedComponentRef ///////////////////////////////////
 
-/// The [ComponentRef] type for [InsertOptimizedComponentCollection].
-pub struct InsertOptimizedComponentRef<'a, T: Debug> {
+/// The [ComponentRef] type for [InsertOptimizedComponentCollection].  Unbinding takes effect
+/// when the ref is dropped, so that a subsequent update may cancel it.  The ref holds both of the
+/// collection's locks so that dropping it unbound can unmap the entity along with its slot.
+pub struct InsertOptimizedComponentRef<'a, E: Entity, T: Debug> {
+    entities: MutexGuard<'a, BTreeMap<E, usize>>,
     this: MutexGuard<'a, Components<T>>,
+    entity: E,
     idx: usize,
+    unbound: bool,
+    dirty: bool,
 }
 
-impl<'a, T: Debug> InsertOptimizedComponentRef<'a, T> {
-    fn new(this: MutexGuard<'a, Components<T>>, idx: usize) -> Self {
-        assert!(idx < this.components.len());
-        Self { this, idx }
+impl<'a, E: Entity, T: Debug> InsertOptimizedComponentRef<'a, E, T> {
+    fn new(
+        entities: MutexGuard<'a, BTreeMap<E, usize>>,
+        this: MutexGuard<'a, Components<T>>,
+        entity: E,
+    ) -> Self {
+        let idx = entities[&entity];
+        assert!(this.components[idx].is_some());
+        let unbound = false;
+        let dirty = false;
+        Self {
+            entities,
+            this,
+            entity,
+            idx,
+            unbound,
+            dirty,
+        }
     }
 }
 
-impl<'a, T: Debug> Debug for InsertOptimizedComponentRef<'a, T> {
+impl<'a, E: Entity, T: Debug> Debug for InsertOptimizedComponentRef<'a, E, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.debug_struct("InsertOptimizedComponentRef<T>")
             .field("this", &self.this.components[self.idx])
+            .field("unbound", &self.unbound)
+            .field("dirty", &self.dirty)
             .finish()
     }
 }
 
-impl<'a, T: Debug> Deref for InsertOptimizedComponentRef<'a, T> {
+impl<'a, E: Entity, T: Debug> Deref for InsertOptimizedComponentRef<'a, E, T> {
     type Target = T;
 
-    /// # Panics:
-    ///
-    /// This function panics if there was a previous call to unbind.
     fn deref(&self) -> &Self::Target {
-        // SAFETY(rescrv):  Ensured by the caller.
+        // SAFETY(rescrv):  Every mapped entity's slot holds a component, and the locks are held.
         self.this.components[self.idx].as_ref().unwrap()
     }
 }
 
-impl<'a, T: Debug> ComponentRef<T> for InsertOptimizedComponentRef<'a, T> {
+impl<'a, E: Entity, T: Debug> ComponentRef<T> for InsertOptimizedComponentRef<'a, E, T> {
     fn unbind(&mut self) {
-        if self.this.components[self.idx].is_some() {
-            self.this.components[self.idx] = None;
-            self.this.free.push(self.idx);
-        }
+        self.unbound = true;
     }
 
-    /// # Panics:
-    ///
-    /// This function panics if there was a previous call to unbind.
     fn update<F: FnOnce(&mut T) -> U, U>(&mut self, f: F) -> U {
+        self.unbound = false;
+        self.dirty = true;
+        // SAFETY(rescrv):  Every mapped entity's slot holds a component, and the locks are held.
         f(self.this.components[self.idx].as_mut().unwrap())
     }
 
     fn change(self) -> ComponentChange<T> {
         ComponentChange::NoChange
     }
+
+    fn is_unbound(&self) -> bool {
+        self.unbound
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+impl<'a, E: Entity, T: Debug> Drop for InsertOptimizedComponentRef<'a, E, T> {
+    fn drop(&mut self) {
+        if self.unbound {
+            InsertOptimizedComponentCollection::unbind_locked(
+                &mut self.entities,
+                &mut self.this,
+                self.entity,
+            );
+        }
+    }
 }
 
 //////////////////////////////// InsertOptimizedComponentCollection ////////////////////////////////
@@ -85,6 +121,15 @@ impl<'a, T: Debug> ComponentRef<T> for InsertOptimizedComponentRef<'a, T> {
 /// efficient than individual insertions or deletions like in other collections, but only for small
 /// update sizes.  For changes that touch more than a small number of components,
 /// CopyOnWriteComponentCollection and MutableComponentCollection are preferred.
+///
+/// # Locking
+///
+/// Methods that take `&self` lock the entity map before the components and never the other way
+/// around.  A [InsertOptimizedComponentRef] holds both locks for as long as it lives, so calling
+/// any other `&self` method on the same collection while holding a ref will deadlock, as
+/// will calling one from the closures passed to `update_or_insert`, which run under both locks.
+/// Methods that take `&mut self`, including `apply` and `unbind_all`, take no locks at all:
+/// exclusive access already rules out every other caller.
 #[derive(Debug)]
 pub struct InsertOptimizedComponentCollection<E: Entity, T: Debug> {
     entities: Mutex<BTreeMap<E, usize>>,
@@ -96,6 +141,46 @@ impl<E: Entity, T: Debug> InsertOptimizedComponentCollection<E, T> {
     pub fn insert(&self, entity: E, component: T) -> Option<T> {
         let mut entities = self.entities.lock().unwrap();
         let mut components = self.components.lock().unwrap();
+        Self::insert_locked(&mut entities, &mut components, entity, component)
+    }
+
+    /// Call `update_fn` on the component bound to `entity` or, if there is none, bind the
+    /// component returned by `insert_fn`.  Both locks are held throughout, so concurrent callers
+    /// for the same entity will insert exactly once.
+    pub fn update_or_insert<F: FnOnce(&mut T), G: FnOnce() -> T>(
+        &self,
+        entity: E,
+        update_fn: F,
+        insert_fn: G,
+    ) {
+        let mut entities = self.entities.lock().unwrap();
+        let mut components = self.components.lock().unwrap();
+        if let Some(component) = entities
+            .get(&entity)
+            .and_then(|index| components.components[*index].as_mut())
+        {
+            update_fn(component);
+        } else {
+            Self::insert_locked(&mut entities, &mut components, entity, insert_fn());
+        }
+    }
+
+    fn unbind_locked(
+        entities: &mut BTreeMap<E, usize>,
+        components: &mut Components<T>,
+        entity: E,
+    ) -> Option<T> {
+        let index = entities.remove(&entity)?;
+        components.free.push(index);
+        components.components[index].take()
+    }
+
+    fn insert_locked(
+        entities: &mut BTreeMap<E, usize>,
+        components: &mut Components<T>,
+        entity: E,
+        component: T,
+    ) -> Option<T> {
         match entities.entry(entity) {
             Entry::Occupied(entry) => {
                 let mut component = Some(component);
@@ -116,6 +201,52 @@ impl<E: Entity, T: Debug> InsertOptimizedComponentCollection<E, T> {
             }
         }
     }
+
+    /// The number of components the collection can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.components.lock().unwrap().components.capacity()
+    }
+
+    /// The fraction of component slots that sit on the free list.  This is 0.0 when the
+    /// collection is dense and approaches 1.0 as more components get unbound.  Use it to decide
+    /// when to call [Self::compact].
+    pub fn fragmentation(&self) -> f32 {
+        let components = self.components.lock().unwrap();
+        if components.components.is_empty() {
+            0.0
+        } else {
+            components.free.len() as f32 / components.components.len() as f32
+        }
+    }
+
+    /// Compact the collection so that no component slots are free.
+    pub fn compact(&self) {
+        let mut entities = self.entities.lock().unwrap();
+        let mut components = self.components.lock().unwrap();
+        let mut compacted = Vec::with_capacity(components.components.len() - components.free.len());
+        for index in entities.values_mut() {
+            // SAFETY(rescrv):  Every mapped entity's slot holds a component.
+            let component = components.components[*index].take().unwrap();
+            *index = compacted.len();
+            compacted.push(Some(component));
+        }
+        components.components = compacted;
+        components.free.clear();
+    }
+
+    /// Move every entity and its component out of the collection, in order.  The collection is
+    /// empty once this returns, but keeps its allocated capacity for refilling.  Entries not
+    /// consumed from the iterator are dropped with it.
+    pub fn drain(&mut self) -> InsertOptimizedComponentDrainIter<'_, E, T> {
+        let entities = std::mem::take(self.entities.get_mut().unwrap()).into_iter();
+        let components = self.components.get_mut().unwrap();
+        components.free.clear();
+        let components = &mut components.components;
+        InsertOptimizedComponentDrainIter {
+            entities,
+            components,
+        }
+    }
 }
 
 impl<E: Entity, T: Debug> Default for InsertOptimizedComponentCollection<E, T> {
@@ -130,7 +261,7 @@ impl<E: Entity, T: Debug> Default for InsertOptimizedComponentCollection<E, T> {
 }
 
 impl<E: Entity, T: Debug> ComponentCollection<E, T> for InsertOptimizedComponentCollection<E, T> {
-    type Ref<'a> = InsertOptimizedComponentRef<'a, T> where Self: 'a, T: 'a;
+    type Ref<'a> = InsertOptimizedComponentRef<'a, E, T> where Self: 'a, T: 'a;
     type Consumed = InsertOptimizedComponentCollectionIterator<E, T>;
 
     fn is_empty(&self) -> bool {
@@ -149,15 +280,22 @@ impl<E: Entity, T: Debug> ComponentCollection<E, T> for InsertOptimizedComponent
             .map(|x| *x.0)
     }
 
+    fn last(&self) -> Option<E> {
+        let entities = self.entities.lock().unwrap();
+        entities.last_key_value().map(|x| *x.0)
+    }
+
+    fn contains_entity(&self, entity: E) -> bool {
+        self.entities.lock().unwrap().contains_key(&entity)
+    }
+
     fn get_ref(&self, entity: E) -> Option<Self::Ref<'_>> {
         let entities = self.entities.lock().unwrap();
-        let components = self.components.lock().unwrap();
-        if let Some(index) = entities.get(&entity) {
-            if *index < components.components.len() {
-                Some(InsertOptimizedComponentRef::new(components, *index))
-            } else {
-                None
-            }
+        if entities.contains_key(&entity) {
+            let components = self.components.lock().unwrap();
+            Some(InsertOptimizedComponentRef::new(
+                entities, components, entity,
+            ))
         } else {
             None
         }
@@ -172,30 +310,115 @@ impl<E: Entity, T: Debug> ComponentCollection<E, T> for InsertOptimizedComponent
         }
     }
 
-    fn apply(&mut self, changes: Vec<(E, ComponentChange<T>)>) {
-        for (e, change) in changes.into_iter() {
-            if let Some(mut existing) = self.get_ref(e) {
-                match change {
-                    ComponentChange::NoChange => {}
-                    ComponentChange::Unbind => {
-                        existing.unbind();
-                    }
-                    ComponentChange::Value(t) => {
-                        let t: T = t;
-                        existing.update(|x| *x = t);
-                    }
-                };
-            } else {
-                match change {
-                    ComponentChange::NoChange => {}
-                    ComponentChange::Unbind => {}
-                    ComponentChange::Value(t) => {
-                        self.insert(e, t);
-                    }
-                };
-            }
+    /// Hold the locks only long enough to clone the component.  The component lock is taken
+    /// before the entity lock is released so that a concurrent compaction cannot move the slot.
+    fn get_cloned(&self, entity: E) -> Option<T>
+    where
+        T: Clone,
+    {
+        let entities = self.entities.lock().unwrap();
+        let index = *entities.get(&entity)?;
+        let components = self.components.lock().unwrap();
+        drop(entities);
+        components.components[index].clone()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let components = self.components.get_mut().unwrap();
+        components.components.reserve(additional);
+    }
+
+    fn truncate_to(&mut self, n: usize) {
+        let map = self.entities.get_mut().unwrap();
+        let components = self.components.get_mut().unwrap();
+        while map.len() > n {
+            // SAFETY(rescrv):  The map holds more than n >= 0 entities.
+            let (entity, _) = map.last_key_value().unwrap();
+            let entity = *entity;
+            Self::unbind_locked(map, components, entity);
+        }
+    }
+
+    fn skip_first(&mut self, n: usize) {
+        let map = self.entities.get_mut().unwrap();
+        let components = self.components.get_mut().unwrap();
+        for _ in 0..n.min(map.len()) {
+            // SAFETY(rescrv):  We remove at most as many entities as the map holds.
+            let (entity, _) = map.first_key_value().unwrap();
+            let entity = *entity;
+            Self::unbind_locked(map, components, entity);
+        }
+    }
+
+    fn split_at(mut self, pivot: E) -> (Self, Self) {
+        let upper = self.entities.get_mut().unwrap().split_off(&pivot);
+        let components = self.components.get_mut().unwrap();
+        let mut moved = Vec::with_capacity(upper.len());
+        for (entity, index) in upper {
+            // SAFETY(rescrv):  Every mapped entity's slot holds a component.
+            let component = components.components[index].take().unwrap();
+            components.free.push(index);
+            moved.push((entity, component));
+        }
+        (self, Self::from_iter(moved))
+    }
+
+    fn iter_range<'a>(&'a self, lo: E, hi: E) -> impl Iterator<Item = (E, T)>
+    where
+        T: Clone + 'a,
+    {
+        // NOTE(rescrv):  Snapshot the range rather than hold the entity lock across iteration,
+        // so that the caller may insert while iterating.  Entities unbound since are skipped.
+        let entities: Vec<E> = if lo < hi {
+            let entities = self.entities.lock().unwrap();
+            entities.range(lo..hi).map(|(e, _)| *e).collect()
+        } else {
+            vec![]
+        };
+        entities
+            .into_iter()
+            .filter_map(move |e| self.get_cloned(e).map(|t| (e, t)))
+    }
+
+    fn apply<I: IntoIterator<Item = (E, ComponentChange<T>)>>(&mut self, changes: I) {
+        let map = self.entities.get_mut().unwrap();
+        let components = self.components.get_mut().unwrap();
+        for (e, change) in changes {
+            match change {
+                ComponentChange::NoChange => {}
+                ComponentChange::Unbind => {
+                    Self::unbind_locked(map, components, e);
+                }
+                ComponentChange::Value(t) => {
+                    Self::insert_locked(map, components, e, t);
+                }
+            };
         }
     }
+
+    fn unbind_all<I: IntoIterator<Item = E>>(&mut self, entities: I) {
+        let map = self.entities.get_mut().unwrap();
+        let components = self.components.get_mut().unwrap();
+        for entity in entities {
+            Self::unbind_locked(map, components, entity);
+        }
+    }
+}
+
+impl<E: Entity, T: Debug> SupportsDirectMutation<E, T>
+    for InsertOptimizedComponentCollection<E, T>
+{
+    fn insert_one(&mut self, entity: E, component: T) -> Option<T> {
+        let map = self.entities.get_mut().unwrap();
+        let components = self.components.get_mut().unwrap();
+        Self::insert_locked(map, components, entity, component)
+    }
+
+    fn remove_one(&mut self, entity: E) -> Option<T> {
+        let map = self.entities.get_mut().unwrap();
+        let components = self.components.get_mut().unwrap();
+        Self::unbind_locked(map, components, entity)
+    }
 }
 
 impl<E: Entity, T: Debug> FromIterator<(E, T)> for InsertOptimizedComponentCollection<E, T> {
@@ -262,6 +485,33 @@ impl<E: Entity, T: Debug> Iterator for InsertOptimizedComponentCollectionIterato
     }
 }
 
+///////////////////////////////// InsertOptimizedComponentDrainIter ////////////////////////////////
+
+/// An iterator returned by [InsertOptimizedComponentCollection::drain].
+pub struct InsertOptimizedComponentDrainIter<'a, E: Entity, T: Debug> {
+    entities: std::collections::btree_map::IntoIter<E, usize>,
+    components: &'a mut Vec<Option<T>>,
+}
+
+impl<'a, E: Entity, T: Debug> Iterator for InsertOptimizedComponentDrainIter<'a, E, T> {
+    type Item = (E, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (e, idx) in self.entities.by_ref() {
+            if let Some(t) = self.components[idx].take() {
+                return Some((e, t));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, E: Entity, T: Debug> Drop for InsertOptimizedComponentDrainIter<'a, E, T> {
+    fn drop(&mut self) {
+        self.components.clear();
+    }
+}
+
 /////////////////////////////////////////////// tests //////////////////////////////////////////////
 
 #[cfg(test)]
@@ -269,6 +519,7 @@ mod tests {
     use super::super::tests::{arb_entities, collection_properties};
 
     use super::InsertOptimizedComponentCollection;
+    use crate::{ComponentChange, ComponentCollection, ComponentRef};
 
     proptest::proptest! {
         #[test]
@@ -276,4 +527,231 @@ mod tests {
             collection_properties::<u128, usize, InsertOptimizedComponentCollection<u128, usize>>(entities);
         }
     }
+
+    #[test]
+    fn apply_unbind_then_insert() {
+        let mut collection =
+            InsertOptimizedComponentCollection::from_iter(vec![(15u128, 15usize), (16, 16)]);
+        collection.apply(vec![(15, ComponentChange::Unbind)]);
+        collection.apply(vec![(22, ComponentChange::Value(22))]);
+        assert!(!collection.contains_entity(15));
+        assert_eq!(Some(16), collection.get_cloned(16));
+        assert_eq!(Some(22), collection.get_cloned(22));
+        collection.apply(vec![(15, ComponentChange::Value(150))]);
+        assert_eq!(
+            vec![(15, 150), (16, 16), (22, 22)],
+            collection.consume().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn ref_unbind_then_insert() {
+        let collection =
+            InsertOptimizedComponentCollection::from_iter(vec![(1u128, 100usize), (2, 200)]);
+        collection.get_ref(1).unwrap().unbind();
+        assert!(!collection.contains_entity(1));
+        assert_eq!(1, collection.len());
+        collection.insert(3, 300);
+        assert_eq!(None, collection.get_cloned(1));
+        assert_eq!(None, collection.insert(1, 101));
+        assert_eq!(Some(300), collection.get_cloned(3));
+        assert_eq!(0.0, collection.fragmentation());
+        assert_eq!(
+            vec![(1, 101), (2, 200), (3, 300)],
+            collection.consume().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn get_cloned() {
+        let collection =
+            InsertOptimizedComponentCollection::from_iter((1..=100u128).map(|e| (e, vec![e; 4])));
+        for e in 1..=100u128 {
+            let cloned = collection.get_cloned(e).unwrap();
+            assert_eq!(*collection.get_ref(e).unwrap(), cloned);
+        }
+        assert_eq!(None, collection.get_cloned(101));
+        collection.get_ref(50).unwrap().unbind();
+        assert_eq!(None, collection.get_cloned(50));
+        // The clone holds no lock, so a ref may be taken and changed while it is alive.
+        let cloned = collection.get_cloned(7).unwrap();
+        collection.get_ref(7).unwrap().update(|v| v.push(0));
+        assert_eq!(vec![7; 4], cloned);
+        assert_eq!(Some(vec![7, 7, 7, 7, 0]), collection.get_cloned(7));
+    }
+
+    #[test]
+    fn get_cloned_concurrent() {
+        let collection =
+            InsertOptimizedComponentCollection::from_iter((1..=100u128).map(|e| (e, e as usize)));
+        std::thread::scope(|scope| {
+            let readers: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let values: Vec<usize> = (1..=100u128)
+                            .map(|e| collection.get_cloned(e).unwrap())
+                            .collect();
+                        // NOTE(rescrv):  Every reader holds its clones at once; none holds a lock.
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                        values
+                    })
+                })
+                .collect();
+            for reader in readers {
+                assert_eq!((1..=100).collect::<Vec<usize>>(), reader.join().unwrap());
+            }
+        });
+        assert!(collection.get_ref(1).is_some());
+    }
+
+    #[test]
+    fn get_cloned_concurrent_compact() {
+        let collection =
+            InsertOptimizedComponentCollection::from_iter((1..=100u128).map(|e| (e, e as usize)));
+        std::thread::scope(|scope| {
+            let readers: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        for _ in 0..1000 {
+                            for e in 1..=100u128 {
+                                let value = collection.get_cloned(e);
+                                assert!(value.is_none() || value == Some(e as usize));
+                            }
+                        }
+                    })
+                })
+                .collect();
+            // NOTE(rescrv):  Unbinding and compacting renumbers every slot after the unbound one.
+            for e in (1..=100u128).cycle() {
+                if readers.iter().all(|reader| reader.is_finished()) {
+                    break;
+                }
+                collection.get_ref(e).unwrap().unbind();
+                collection.compact();
+                collection.insert(e, e as usize);
+            }
+        });
+        for e in 1..=100u128 {
+            assert_eq!(Some(e as usize), collection.get_cloned(e));
+        }
+    }
+
+    #[test]
+    fn apply_concurrent_get_ref() {
+        let collection = std::sync::RwLock::new(InsertOptimizedComponentCollection::from_iter(
+            (0..100u128).map(|e| (e, 0usize)),
+        ));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for e in (0..100u128).cycle().take(10_000) {
+                        let guard = collection.read().unwrap();
+                        let value = guard.get_ref(e).map(|c| *c);
+                        assert!(value.unwrap_or(0) <= 100);
+                    }
+                });
+            }
+            for round in 1..=100usize {
+                let changes = (0..100u128).map(|e| {
+                    if e == round as u128 {
+                        (e, ComponentChange::Unbind)
+                    } else {
+                        (e, ComponentChange::Value(round))
+                    }
+                });
+                collection.write().unwrap().apply(changes);
+            }
+        });
+        let collection = collection.into_inner().unwrap();
+        assert_eq!(100, collection.len());
+        for e in 0..100u128 {
+            assert_eq!(Some(100), collection.get_cloned(e));
+        }
+    }
+
+    #[test]
+    fn update_or_insert() {
+        let collection =
+            InsertOptimizedComponentCollection::from_iter(vec![(1u128, 1usize), (2, 2)]);
+        collection.update_or_insert(1, |c| *c += 10, || panic!("1 is bound"));
+        collection.update_or_insert(3, |_| panic!("3 is unbound"), || 3);
+        collection.get_ref(2).unwrap().unbind();
+        collection.update_or_insert(2, |_| panic!("2 was unbound"), || 20);
+        assert_eq!(0.0, collection.fragmentation());
+        assert_eq!(
+            vec![(1, 11), (2, 20), (3, 3)],
+            collection.consume().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn update_or_insert_concurrent() {
+        const THREADS: usize = 8;
+        const ENTITIES: u128 = 64;
+        let collection = InsertOptimizedComponentCollection::<u128, usize>::default();
+        std::thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for e in 0..ENTITIES {
+                        collection.update_or_insert(e, |c| *c += 1, || 1);
+                    }
+                });
+            }
+        });
+        let expected: Vec<(u128, usize)> = (0..ENTITIES).map(|e| (e, THREADS)).collect();
+        assert_eq!(expected, collection.consume().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drain() {
+        let mut collection = InsertOptimizedComponentCollection::<u128, usize>::default();
+        for e in (1..=100u128).rev() {
+            collection.insert(e, e as usize);
+        }
+        collection.get_ref(50).unwrap().unbind();
+        let capacity = collection.capacity();
+        let drained: Vec<(u128, usize)> = collection.drain().collect();
+        let expected: Vec<(u128, usize)> = (1..=100u128)
+            .filter(|e| *e != 50)
+            .map(|e| (e, e as usize))
+            .collect();
+        assert_eq!(expected, drained);
+        assert!(collection.is_empty());
+        assert_eq!(0.0, collection.fragmentation());
+        assert_eq!(capacity, collection.capacity());
+        for e in 1..=100u128 {
+            collection.insert(e, e as usize * 2);
+        }
+        assert_eq!(capacity, collection.capacity());
+        collection.drain().take(3).for_each(drop);
+        assert!(collection.is_empty());
+        assert!(collection.get_ref(99).is_none());
+        collection.drain().for_each(drop);
+        assert_eq!(capacity, collection.capacity());
+    }
+
+    #[test]
+    fn fragmentation() {
+        let collection = InsertOptimizedComponentCollection::<u128, usize>::default();
+        assert_eq!(0.0, collection.fragmentation());
+        for e in 1..=100u128 {
+            collection.insert(e, e as usize);
+        }
+        assert_eq!(0.0, collection.fragmentation());
+        assert!(collection.capacity() >= 100);
+        for e in (2..=100u128).step_by(2) {
+            collection.get_ref(e).unwrap().unbind();
+        }
+        assert!((collection.fragmentation() - 0.5).abs() < 0.01);
+        collection.compact();
+        assert_eq!(0.0, collection.fragmentation());
+        assert_eq!(50, collection.len());
+        for e in 1..=100u128 {
+            if e % 2 == 0 {
+                assert!(collection.get_ref(e).is_none());
+            } else {
+                assert_eq!(e as usize, *collection.get_ref(e).unwrap());
+            }
+        }
+    }
 }
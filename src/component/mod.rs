@@ -1,16 +1,23 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::fmt::Debug;
 use std::ops::Deref;
 
 mod cow;
 mod insert;
 mod r#mut;
+mod ordered;
 
-pub use cow::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
-pub use insert::{InsertOptimizedComponentCollection, InsertOptimizedComponentRef};
+pub use cow::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef, SortedByComponent};
+pub use insert::{
+    InsertOptimizedComponentCollection, InsertOptimizedComponentDrainIter,
+    InsertOptimizedComponentRef,
+};
+pub use ordered::{OrderedComponentCollection, OrderedComponentRef};
 pub use r#mut::{MutableComponentCollection, MutableComponentRef};
 
 use crate::partitioning::PartitioningScheme;
-use crate::Entity;
+use crate::{Entity, ThreadPool};
 
 //////////////////////////////////////// ComponentCollection ///////////////////////////////////////
 
@@ -34,12 +41,82 @@ pub trait ComponentCollection<E: Entity, T: Debug>:
 
     /// What's the first entity greater-or-equal to the provided entity?
     fn lower_bound(&self, lower_bound: E) -> Option<E>;
+    /// What's the smallest entity in the collection?
+    fn first(&self) -> Option<E> {
+        self.lower_bound(E::default())
+    }
+    /// What's the largest entity in the collection?  The default walks every entity from
+    /// [Self::first] with [Self::lower_bound]; collections that can find it directly should.
+    fn last(&self) -> Option<E> {
+        let mut last = self.first()?;
+        while let Some(next) = lower_bound_after(self, last) {
+            last = next;
+        }
+        Some(last)
+    }
     /// Get a reference to the component held for entity, if it exists.
     fn get_ref(&self, entity: E) -> Option<Self::Ref<'_>>;
+    /// Does the collection hold a component for entity?  Implementations should answer without
+    /// taking a lock on the components themselves.
+    fn contains_entity(&self, entity: E) -> bool {
+        self.get_ref(entity).is_some()
+    }
+    /// Get a clone of the component held for entity, if it exists.  Unlike [Self::get_ref], no
+    /// reference to the collection is held once this returns.
+    fn get_cloned(&self, entity: E) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.get_ref(entity).map(|r| r.deref().clone())
+    }
+    /// Iterate the entities in `[lo, hi)` in order, each with a clone of its component.  As with
+    /// [Self::get_cloned], no reference to the collection is held between items, so the iterator
+    /// may be collected even when refs hold a lock.  Empty if `lo >= hi`.
+    fn iter_range<'a>(&'a self, lo: E, hi: E) -> impl Iterator<Item = (E, T)>
+    where
+        T: Clone + 'a,
+    {
+        let mut next = if lo < hi { self.lower_bound(lo) } else { None };
+        std::iter::from_fn(move || {
+            let entity = next.filter(|e| *e < hi)?;
+            // NOTE(rescrv):  entity < hi, so the increment cannot wrap.
+            next = self.lower_bound(entity.increment());
+            Some((entity, self.get_cloned(entity)?))
+        })
+    }
 
     /// Consume the component collection.
     fn consume(self) -> Self::Consumed;
 
+    /// Split the collection in two:  the entities less than pivot, and the rest.
+    fn split_at(self, pivot: E) -> (Self, Self) {
+        let mut lower = vec![];
+        let mut upper = vec![];
+        for (e, t) in self.consume() {
+            if e < pivot {
+                lower.push((e, t));
+            } else {
+                upper.push((e, t));
+            }
+        }
+        (Self::from_iter(lower), Self::from_iter(upper))
+    }
+
+    /// Split the collection in two by component rather than by entity:  the entities for which
+    /// `predicate` holds, and the rest.
+    fn split_by_predicate<F: FnMut(E, &T) -> bool>(self, mut predicate: F) -> (Self, Self) {
+        let mut matching = vec![];
+        let mut non_matching = vec![];
+        for (e, t) in self.consume() {
+            if predicate(e, &t) {
+                matching.push((e, t));
+            } else {
+                non_matching.push((e, t));
+            }
+        }
+        (Self::from_iter(matching), Self::from_iter(non_matching))
+    }
+
     /// Partition the collection according to the provided partitioning scheme.
     ///
     /// This function makes an arbitrary, but sorted, collection suitable for application to a
@@ -81,32 +158,325 @@ pub trait ComponentCollection<E: Entity, T: Debug>:
         partitions
     }
 
-    /// Apply the changes to this collection.
+    /// Build a collection from pairs in arbitrary order, sorting them with a parallel merge sort
+    /// over the thread pool.  The sort is stable, so where an entity appears more than once, the
+    /// last pair for that entity in iteration order wins.
+    fn from_unsorted_parallel<I: IntoIterator<Item = (E, T)>>(
+        iter: I,
+        thread_pool: &ThreadPool,
+    ) -> Self
+    where
+        E: Send,
+        T: Send,
+    {
+        let sorted = parallel_sort_by_entity(iter.into_iter().collect(), thread_pool);
+        let mut deduped: Vec<(E, T)> = Vec::with_capacity(sorted.len());
+        for (e, t) in sorted.into_iter() {
+            match deduped.last_mut() {
+                Some(last) if last.0 == e => {
+                    *last = (e, t);
+                }
+                _ => {
+                    deduped.push((e, t));
+                }
+            }
+        }
+        Self::from_iter(deduped)
+    }
+
+    /// Apply the changes to this collection.  Any iterator will do, so changes need not be
+    /// collected into a vector first.
     ///
-    /// It is undefined behavior to pass a changes vector not sorted by entity value.
-    fn apply(&mut self, changes: Vec<(E, ComponentChange<T>)>) {
+    /// It is undefined behavior to pass changes not sorted by entity value.
+    fn apply<I: IntoIterator<Item = (E, ComponentChange<T>)>>(&mut self, changes: I) {
         let this = std::mem::take(self);
         *self = apply_component_changes(this, changes.into_iter());
     }
+
+    /// Apply several vectors of changes, such as those returned by several systems, as if
+    /// [Self::apply] were called on each in turn.  The vectors are merged in a single pass, and
+    /// where more than one vector changes the same entity, later vectors take precedence per
+    /// [ComponentChange::sequence].
+    ///
+    /// It is undefined behavior to pass a changes vector not sorted by entity value.
+    fn apply_batch(&mut self, changes: Vec<Vec<(E, ComponentChange<T>)>>) {
+        self.apply(merge_component_changes(changes));
+    }
+
+    /// Reserve room for at least `additional` more components.  This is a hint: collections that
+    /// have nowhere to put the room, like the default implementation, ignore it.
+    fn reserve(&mut self, additional: usize) {
+        let _ = additional;
+    }
+
+    /// Unbind the components held for entities.  Entities without a component are ignored, and
+    /// entities need not be sorted.
+    fn unbind_all<I: IntoIterator<Item = E>>(&mut self, entities: I) {
+        let mut entities: Vec<E> = entities.into_iter().collect();
+        entities.sort();
+        entities.dedup();
+        self.apply(entities.into_iter().map(|e| (e, ComponentChange::Unbind)));
+    }
+
+    /// The smallest entity greater than `after` that has no component bound, or None if every
+    /// such entity up to [Entity::max_value] is bound.  The default implementation looks up
+    /// each bound entity in turn with [Self::lower_bound].
+    fn next_free(&self, after: E) -> Option<E> {
+        if after == E::max_value() {
+            return None;
+        }
+        let mut candidate = after.increment();
+        while self.lower_bound(candidate) == Some(candidate) {
+            if candidate == E::max_value() {
+                return None;
+            }
+            candidate = candidate.increment();
+        }
+        Some(candidate)
+    }
+
+    /// Keep the `n` smallest entities and unbind the rest.  Does nothing if the collection holds
+    /// `n` or fewer entities.  The default implementation rebuilds the collection; the
+    /// collections of this crate remove only what they unbind.
+    fn truncate_to(&mut self, n: usize) {
+        if n < self.len() {
+            let this = std::mem::take(self);
+            *self = Self::from_iter(this.consume().take(n));
+        }
+    }
+
+    /// Unbind the `n` smallest entities and keep the rest.  Empties the collection if it holds `n`
+    /// or fewer entities.  The default implementation rebuilds the collection.
+    fn skip_first(&mut self, n: usize) {
+        if n > 0 {
+            let this = std::mem::take(self);
+            *self = Self::from_iter(this.consume().skip(n));
+        }
+    }
+
+    /// The sorted changes that, applied to this collection, make it equal to other:  `Value` for
+    /// entities bound only or differently in other, `Unbind` for entities bound only in this
+    /// collection.  Entities bound to equal components in both are skipped.
+    fn diff(&self, other: &Self) -> Vec<(E, ComponentChange<T>)>
+    where
+        T: Clone + PartialEq,
+    {
+        let mut diff = vec![];
+        let mut ours = self.first();
+        let mut theirs = other.first();
+        loop {
+            match (ours, theirs) {
+                (None, None) => break,
+                (Some(a), b) if b.map(|b| a < b).unwrap_or(true) => {
+                    diff.push((a, ComponentChange::Unbind));
+                    ours = lower_bound_after(self, a);
+                }
+                (a, Some(b)) if a.map(|a| b < a).unwrap_or(true) => {
+                    // SAFETY(rescrv):  b came from other's lower_bound.
+                    let component = other.get_cloned(b).unwrap();
+                    diff.push((b, ComponentChange::Value(component)));
+                    theirs = lower_bound_after(other, b);
+                }
+                (Some(e), Some(_)) => {
+                    // NOTE(rescrv):  Clone rather than hold both refs, so that diffing a
+                    // collection against itself cannot deadlock on its lock.
+                    // SAFETY(rescrv):  e came from each collection's lower_bound.
+                    let component = self.get_cloned(e).unwrap();
+                    let other_component = other.get_cloned(e).unwrap();
+                    if component != other_component {
+                        diff.push((e, ComponentChange::Value(other_component)));
+                    }
+                    ours = lower_bound_after(self, e);
+                    theirs = lower_bound_after(other, e);
+                }
+                (None, Some(_)) | (Some(_), None) => unreachable!(),
+            }
+        }
+        diff
+    }
+
+    /// Move every component into a new collection of another type.
+    fn into_collection<C2: ComponentCollection<E, T>>(self) -> C2 {
+        C2::from_iter(self.consume())
+    }
+
+    /// Clone every component into a new collection of another type, leaving this one intact.
+    fn clone_into_collection<C2: ComponentCollection<E, T>>(&self) -> C2
+    where
+        T: Clone,
+    {
+        let mut next = self.first();
+        C2::from_iter(std::iter::from_fn(|| {
+            let entity = next?;
+            next = lower_bound_after(self, entity);
+            // SAFETY(rescrv):  entity came from lower_bound.
+            Some((entity, self.get_cloned(entity).unwrap()))
+        }))
+    }
+}
+
+/// The first entity greater than entity, without wrapping around past [Entity::max_value].
+pub(crate) fn lower_bound_after<E: Entity, T: Debug, C: ComponentCollection<E, T>>(
+    collection: &C,
+    entity: E,
+) -> Option<E> {
+    if entity == E::max_value() {
+        None
+    } else {
+        collection.lower_bound(entity.increment())
+    }
+}
+
+////////////////////////////////////// SupportsDirectMutation //////////////////////////////////////
+
+/// A [ComponentCollection] that can bind or unbind a single component in place, at the cost of a
+/// search and a shift rather than a rebuild of the whole collection.
+pub trait SupportsDirectMutation<E: Entity, T: Debug>: ComponentCollection<E, T> {
+    /// Bind `component` to `entity`, returning the component previously bound to it.
+    fn insert_one(&mut self, entity: E, component: T) -> Option<T>;
+    /// Unbind the component bound to `entity` and return it.
+    fn remove_one(&mut self, entity: E) -> Option<T>;
+}
+
+/// Apply changes one at a time when there are fewer of them than `log2(collection.len())`, and
+/// with `rebuild` otherwise.  The number of changes is taken from the upper bound of their size
+/// hint; iterators without one are always applied with a rebuild.
+pub(crate) fn apply_direct_or_merge<
+    E: Entity,
+    T: Debug,
+    C: SupportsDirectMutation<E, T>,
+    I: Iterator<Item = (E, ComponentChange<T>)>,
+    R: FnOnce(&mut C, I),
+>(
+    collection: &mut C,
+    changes: I,
+    rebuild: R,
+) {
+    let threshold = collection.len().checked_ilog2().unwrap_or(0) as usize;
+    if changes.size_hint().1.is_some_and(|upper| upper < threshold) {
+        for (entity, change) in changes {
+            match change {
+                ComponentChange::NoChange => {}
+                ComponentChange::Unbind => {
+                    collection.remove_one(entity);
+                }
+                ComponentChange::Value(component) => {
+                    collection.insert_one(entity, component);
+                }
+            }
+        }
+    } else {
+        rebuild(collection, changes);
+    }
+}
+
+/////////////////////////////////////////////// sort ///////////////////////////////////////////////
+
+pub(crate) fn parallel_sort_by_entity<E: Entity + Send, T: Send>(
+    mut pairs: Vec<(E, T)>,
+    thread_pool: &ThreadPool,
+) -> Vec<(E, T)> {
+    let chunk_size = pairs
+        .len()
+        .div_ceil(thread_pool.num_threads().max(1))
+        .max(1);
+    let mut runs = vec![];
+    while pairs.len() > chunk_size {
+        runs.push(pairs.split_off(pairs.len() - chunk_size));
+    }
+    runs.push(pairs);
+    runs.reverse();
+    thread_pool.scope(|scope| {
+        for run in runs.iter_mut() {
+            scope.enqueue(move || run.sort_by_key(|(e, _)| *e));
+        }
+    });
+    while runs.len() > 1 {
+        let mut merged: Vec<Vec<(E, T)>> = Vec::with_capacity(runs.len().div_ceil(2));
+        merged.resize_with(runs.len().div_ceil(2), Vec::new);
+        let mut runs_iter = runs.into_iter();
+        thread_pool.scope(|scope| {
+            for out in merged.iter_mut() {
+                // SAFETY(rescrv):  There are ceil(runs / 2) outputs, so every output has a left.
+                let left = runs_iter.next().unwrap();
+                let right = runs_iter.next().unwrap_or_default();
+                scope.enqueue(move || *out = merge_by_entity(left, right));
+            }
+        });
+        runs = merged;
+    }
+    // SAFETY(rescrv):  We push at least one run and merging never leaves zero runs.
+    runs.pop().unwrap()
+}
+
+fn merge_by_entity<E: Entity, T>(left: Vec<(E, T)>, right: Vec<(E, T)>) -> Vec<(E, T)> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        // NOTE(rescrv):  Ties go to the left run, which came first, to keep the sort stable.
+        if l.0 <= r.0 {
+            merged.extend(left.next());
+        } else {
+            merged.extend(right.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    merged
 }
 
 /////////////////////////////////////////////// apply //////////////////////////////////////////////
 
-pub(crate) fn apply_component_changes<
+pub(crate) fn merge_component_changes<E: Entity, T: Debug>(
+    changes: Vec<Vec<(E, ComponentChange<T>)>>,
+) -> Vec<(E, ComponentChange<T>)> {
+    let mut merged: Vec<(E, ComponentChange<T>)> =
+        Vec::with_capacity(changes.iter().map(Vec::len).max().unwrap_or(0));
+    let mut iters: Vec<_> = changes
+        .into_iter()
+        .map(|c| c.into_iter().peekable())
+        .collect();
+    let mut heap = BinaryHeap::with_capacity(iters.len());
+    for (idx, iter) in iters.iter_mut().enumerate() {
+        if let Some((e, _)) = iter.peek() {
+            heap.push(Reverse((*e, idx)));
+        }
+    }
+    // NOTE(rescrv):  The heap pops by entity and then by index, so the changes to one entity are
+    // sequenced in the order of the vectors they came from.
+    while let Some(Reverse((entity, idx))) = heap.pop() {
+        // SAFETY(rescrv):  Only iterators with a peeked element get pushed onto the heap.
+        let (_, change) = iters[idx].next().unwrap();
+        if let Some((e, _)) = iters[idx].peek() {
+            heap.push(Reverse((*e, idx)));
+        }
+        match merged.last_mut() {
+            Some((last, prev)) if *last == entity => {
+                let first = std::mem::replace(prev, ComponentChange::NoChange);
+                *prev = ComponentChange::sequence(first, change);
+            }
+            _ => {
+                merged.push((entity, change));
+            }
+        }
+    }
+    merged
+}
+
+/// Merge sorted changes into sorted pairs, handing each pair that survives to `push` in order.
+fn merge_component_changes_into<
     E: Entity,
     T: Debug,
-    C: ComponentCollection<E, T>,
+    P: Iterator<Item = (E, T)>,
     I: Iterator<Item = (E, ComponentChange<T>)>,
+    F: FnMut((E, T)),
 >(
-    collection: C,
+    mut collection: P,
     mut changes: I,
-) -> C {
+    mut push: F,
+) {
     let mut changes_next = changes.next();
-    if changes_next.is_none() {
-        return collection;
-    }
-    let mut collected = Vec::with_capacity(collection.len());
-    let mut collection = collection.consume();
     let mut collection_next = collection.next();
     while let (Some(c), Some(i)) = (collection_next.as_ref(), changes_next.as_ref()) {
         #[allow(clippy::comparison_chain)]
@@ -114,7 +484,7 @@ pub(crate) fn apply_component_changes<
             match &i.1 {
                 ComponentChange::NoChange => {
                     // SAFETY(rescrv):  We see Some(c) above and haven't changed collection_next.
-                    collected.push(collection_next.unwrap());
+                    push(collection_next.unwrap());
                 }
                 ComponentChange::Unbind => {
                     // pass
@@ -124,14 +494,14 @@ pub(crate) fn apply_component_changes<
                     let (e, ComponentChange::Value(v)) = changes_next.unwrap() else {
                         unreachable!();
                     };
-                    collected.push((e, v));
+                    push((e, v));
                 }
             }
             collection_next = collection.next();
             changes_next = changes.next();
         } else if c.0 < i.0 {
             // SAFETY(rescrv):  We see Some(c) above and haven't changed collection_next.
-            collected.push(collection_next.unwrap());
+            push(collection_next.unwrap());
             collection_next = collection.next();
         } else {
             match &i.1 {
@@ -146,14 +516,14 @@ pub(crate) fn apply_component_changes<
                     let (e, ComponentChange::Value(v)) = changes_next.unwrap() else {
                         unreachable!();
                     };
-                    collected.push((e, v));
+                    push((e, v));
                 }
             }
             changes_next = changes.next();
         }
     }
     while collection_next.as_ref().is_some() {
-        collected.push(collection_next.unwrap());
+        push(collection_next.unwrap());
         collection_next = collection.next();
     }
     while let Some(i) = changes_next.as_ref() {
@@ -169,14 +539,62 @@ pub(crate) fn apply_component_changes<
                 let (e, ComponentChange::Value(v)) = changes_next.unwrap() else {
                     unreachable!();
                 };
-                collected.push((e, v));
+                push((e, v));
             }
         }
         changes_next = changes.next();
     }
+}
+
+// NOTE(rescrv):  There is no SIMD variant of this merge.  Each step moves a component into the
+// output, so the loop is bound by moves rather than entity compares, and the rebuild consumes the
+// collection, so a run of unchanged components cannot be skipped without moving it.  Small
+// batches apply in place instead, finding each change with [Entity::partition_point] and its AVX2
+// implementations.
+pub(crate) fn apply_component_changes<
+    E: Entity,
+    T: Debug,
+    C: ComponentCollection<E, T>,
+    I: Iterator<Item = (E, ComponentChange<T>)>,
+>(
+    collection: C,
+    changes: I,
+) -> C {
+    let mut changes = changes.peekable();
+    if changes.peek().is_none() {
+        return collection;
+    }
+    let mut collected = Vec::with_capacity(collection.len());
+    merge_component_changes_into(collection.consume(), changes, |pair| collected.push(pair));
     C::from_iter(collected)
 }
 
+/// Apply changes like [apply_component_changes] to a collection kept as parallel vectors of
+/// entities and components.  The merged vectors are allocated with the capacity of those they
+/// replace, so that room set aside with [ComponentCollection::reserve] survives the rebuild.
+pub(crate) fn apply_component_changes_to_vecs<
+    E: Entity,
+    T: Debug,
+    I: Iterator<Item = (E, ComponentChange<T>)>,
+>(
+    entities: Vec<E>,
+    components: Vec<T>,
+    changes: I,
+) -> (Vec<E>, Vec<T>) {
+    let mut changes = changes.peekable();
+    if changes.peek().is_none() {
+        return (entities, components);
+    }
+    let mut merged_entities = Vec::with_capacity(entities.capacity().max(entities.len()));
+    let mut merged_components = Vec::with_capacity(components.capacity().max(components.len()));
+    let pairs = std::iter::zip(entities, components);
+    merge_component_changes_into(pairs, changes, |(e, t)| {
+        merged_entities.push(e);
+        merged_components.push(t);
+    });
+    (merged_entities, merged_components)
+}
+
 ////////////////////////////////////////// ComponentChange /////////////////////////////////////////
 
 /// A change in the component.  This type is constructed by the ComponentRef, and should be passed
@@ -191,25 +609,90 @@ pub enum ComponentChange<T: Debug> {
 }
 
 impl<T: Debug> ComponentChange<T> {
+    /// A change that assigns `value` to the component.
+    pub fn value(value: T) -> Self {
+        Self::Value(value)
+    }
+
+    /// A change that unbinds the component.
+    pub fn unbind() -> Self {
+        Self::Unbind
+    }
+
     /// True if and only if this is a NoChange ComponentChange.
     pub fn is_no_change(&self) -> bool {
         matches!(self, Self::NoChange)
     }
+
+    /// Compose two changes to the same component into the one change that has the same effect as
+    /// applying first and then second.  NoChange is the identity; otherwise second wins.
+    pub fn sequence(first: Self, second: Self) -> Self {
+        match second {
+            Self::NoChange => first,
+            second => second,
+        }
+    }
+
+    /// Compose a series of changes to the same component, in order, into a single change.
+    /// Returns NoChange for an empty series.
+    pub fn fold_sequence(changes: impl Iterator<Item = Self>) -> Self {
+        changes.fold(Self::NoChange, Self::sequence)
+    }
+}
+
+impl<T: Debug> From<T> for ComponentChange<T> {
+    fn from(value: T) -> Self {
+        Self::Value(value)
+    }
+}
+
+/// Sort changes by entity, composing the changes to each entity in the order given.  This is what
+/// [crate::changes] expands to.
+#[doc(hidden)]
+pub fn sort_changes<E: Entity, T: Debug>(
+    mut changes: Vec<(E, ComponentChange<T>)>,
+) -> Vec<(E, ComponentChange<T>)> {
+    changes.sort_by_key(|(e, _)| *e);
+    let mut sorted: Vec<(E, ComponentChange<T>)> = Vec::with_capacity(changes.len());
+    for (e, change) in changes {
+        match sorted.last_mut() {
+            Some((last, composed)) if *last == e => {
+                let first = std::mem::replace(composed, ComponentChange::NoChange);
+                *composed = ComponentChange::sequence(first, change);
+            }
+            _ => sorted.push((e, change)),
+        }
+    }
+    sorted
 }
 
 /////////////////////////////////////////// ComponentRef ///////////////////////////////////////////
 
 /// Reference a component.
+///
+/// Of [ComponentRef::unbind] and [ComponentRef::update], the last call wins:  An update after an
+/// unbind rebinds the component with the updated value, and an unbind after an update discards
+/// the update.
 pub trait ComponentRef<T: Debug>: Deref<Target = T> + Debug {
     /// Unbind the component, assuming the change generated by change is passed to the collection.
     /// In practice, this is done by taking the return value of running a system and passing the
     /// batch to apply.
     fn unbind(&mut self);
-    /// Upudate the value and optionally return some state.
+    /// Upudate the value and optionally return some state.  This cancels a prior unbind.
     fn update<F: FnOnce(&mut T) -> U, U>(&mut self, f: F) -> U;
     /// Consume this reference and make a [ComponentChange].  This is useful for saving a clone of
     /// T.
     fn change(self) -> ComponentChange<T>;
+    /// True if [ComponentRef::unbind] has been called on this reference.  The default always
+    /// returns false, for refs that don't track it.
+    fn is_unbound(&self) -> bool {
+        false
+    }
+    /// True if [ComponentRef::update] has been called on this reference.  The default always
+    /// returns false, for refs that don't track it.
+    fn is_dirty(&self) -> bool {
+        false
+    }
 }
 
 /////////////////////////////////////////////// tests //////////////////////////////////////////////
@@ -218,40 +701,659 @@ pub trait ComponentRef<T: Debug>: Deref<Target = T> + Debug {
 pub mod tests {
     extern crate proptest;
 
+    use std::collections::BTreeMap;
     use std::fmt::Debug;
 
     use proptest::strategy::Strategy;
 
-    use super::ComponentCollection;
+    use super::{ComponentChange, ComponentCollection, ComponentRef};
 
-    use crate::tests::{arb_entity, is_free_of_duplicates};
-    use crate::Entity;
+    use crate::tests::{arb_entity, is_free_of_duplicates, with_extremes};
+    use crate::{Entity, PartitioningScheme, VecPartitioningScheme};
 
     proptest::prop_compose! {
-        pub fn arb_entities()(mut entities in proptest::collection::vec(arb_entity(), 0..=65536).prop_filter("dedupe", is_free_of_duplicates)) -> Vec<(u128, usize)> {
+        pub fn arb_entities()(mut entities in proptest::collection::vec(arb_entity(), 0..=65536).prop_filter("dedupe", is_free_of_duplicates), default in proptest::bool::ANY, max in proptest::bool::ANY) -> Vec<(u128, usize)> {
             entities.sort();
             entities.dedup();
-            entities.into_iter().enumerate().map(|(i, x)| (x, i)).collect()
+            with_extremes(entities, default, max).into_iter().enumerate().map(|(i, x)| (x, i)).collect()
         }
     }
 
-    pub fn collection_properties<E: Entity, T: Debug + Clone + Eq, C: ComponentCollection<E, T>>(
+    /// Pick dividers from the collection itself so that partitions split runs of entities, fall
+    /// on entities, and fall just past them.
+    pub fn partitioning_for<E: Entity, T>(collection: &[(E, T)]) -> VecPartitioningScheme<E> {
+        let mut dividers: Vec<E> = collection
+            .iter()
+            .step_by(collection.len() / 4 + 1)
+            .enumerate()
+            .map(|(idx, (e, _))| if idx & 1 == 1 { e.increment() } else { *e })
+            .collect();
+        dividers.sort();
+        dividers.dedup();
+        VecPartitioningScheme::from(dividers)
+    }
+
+    /// Generate a sorted change set that leaves, unbinds, and overwrites existing components, and
+    /// binds and unbinds entities in the gaps between them.
+    pub fn changes_for<E: Entity, T: Debug + Clone>(
+        collection: &[(E, T)],
+    ) -> Vec<(E, ComponentChange<T>)> {
+        let mut changes = vec![];
+        for (idx, (e, t)) in collection.iter().enumerate() {
+            let gap = if idx > 0 {
+                collection[idx - 1].0.increment() != *e
+            } else {
+                *e != E::default()
+            };
+            match idx % 5 {
+                0 => changes.push((*e, ComponentChange::NoChange)),
+                1 => changes.push((*e, ComponentChange::Unbind)),
+                2 => {
+                    let t = collection[idx * 7 % collection.len()].1.clone();
+                    changes.push((*e, ComponentChange::Value(t)));
+                }
+                3 if gap => changes.push((e.decrement(), ComponentChange::Value(t.clone()))),
+                4 if gap => changes.push((e.decrement(), ComponentChange::Unbind)),
+                _ => {}
+            }
+        }
+        changes
+    }
+
+    /// Apply the changes to the collection one at a time, without any cleverness.
+    pub fn reference_apply<E: Entity, T: Debug>(
+        collection: Vec<(E, T)>,
+        changes: Vec<(E, ComponentChange<T>)>,
+    ) -> Vec<(E, T)> {
+        let mut reference: BTreeMap<E, T> = collection.into_iter().collect();
+        for (e, change) in changes {
+            match change {
+                ComponentChange::NoChange => {}
+                ComponentChange::Unbind => {
+                    reference.remove(&e);
+                }
+                ComponentChange::Value(t) => {
+                    reference.insert(e, t);
+                }
+            }
+        }
+        reference.into_iter().collect()
+    }
+
+    pub fn collection_properties<
+        E: Entity + Send + Sync,
+        T: Debug + Clone + Eq,
+        C: ComponentCollection<E, T>,
+    >(
         collection: Vec<(E, T)>,
     ) {
         let components = C::from_iter(collection.clone());
         assert_eq!(collection.is_empty(), components.is_empty());
         assert_eq!(collection.len(), components.len());
+        assert_eq!(collection.first().map(|x| x.0), components.first());
+        assert_eq!(collection.last().map(|x| x.0), components.last());
         for (idx, (e, t)) in collection.iter().enumerate() {
             assert_eq!(Some(*e), components.lower_bound(*e));
             assert_eq!(*t, *components.get_ref(*e).unwrap());
+            assert_eq!(Some(t.clone()), components.get_cloned(*e));
+            assert!(components.contains_entity(*e));
             if idx > 0 && collection[idx - 1].0.increment() != collection[idx].0 {
                 assert_eq!(Some(*e), components.lower_bound(e.decrement()));
                 assert!(components.get_ref(e.decrement()).is_none());
+                assert!(components.get_cloned(e.decrement()).is_none());
+                assert!(!components.contains_entity(e.decrement()));
+            }
+        }
+        if !collection.is_empty() {
+            let lo = collection[collection.len() / 4].0;
+            let hi = collection[collection.len() * 3 / 4].0;
+            for (lo, hi) in [
+                (lo, hi),
+                (lo.decrement(), hi.increment()),
+                (hi, lo),
+                (lo, lo),
+            ] {
+                let expected: Vec<(E, T)> = collection
+                    .iter()
+                    .filter(|(e, _)| lo <= *e && *e < hi)
+                    .cloned()
+                    .collect();
+                let ranged: Vec<(E, T)> = components.iter_range(lo, hi).collect();
+                assert_eq!(expected, ranged);
             }
         }
-        // TODO(partition);
-        // TODO(apply);
+        if !collection.is_empty() {
+            let pivot = collection[collection.len() / 2].0;
+            let (lower, upper) = C::from_iter(collection.clone()).split_at(pivot);
+            assert_eq!(collection.len(), lower.len() + upper.len());
+            assert!(lower.last().map(|e| e < pivot).unwrap_or(true));
+            assert_eq!(Some(pivot), upper.first());
+            let recombined: Vec<(E, T)> = lower.consume().chain(upper.consume()).collect();
+            assert_eq!(collection, recombined);
+        }
+        let partitioning = partitioning_for(&collection);
+        let partitions = C::from_iter(collection.clone()).partition(&partitioning);
+        assert_eq!(partitioning.len() + 1, partitions.len());
+        let mut repartitioned = vec![];
+        for (idx, partition) in partitions.into_iter().enumerate() {
+            let (lower, upper) = partitioning.entities_in_partition(idx).unwrap();
+            for (e, t) in partition.into_iter().flat_map(|p| p.consume()) {
+                assert_eq!(idx, partitioning.partition_of(e));
+                assert!(lower.map(|lower| lower <= e).unwrap_or(true));
+                assert!(upper.map(|upper| e < upper).unwrap_or(true));
+                repartitioned.push((e, t));
+            }
+        }
+        assert_eq!(collection, repartitioned);
+        let mut applied = C::from_iter(collection.clone());
+        applied.apply(changes_for(&collection));
+        let expected = reference_apply(collection.clone(), changes_for(&collection));
+        assert_eq!(expected, applied.consume().collect::<Vec<_>>());
         let consumed: Vec<(E, T)> = components.consume().collect();
         assert_eq!(collection, consumed);
     }
+
+    #[test]
+    fn first_last() {
+        fn check<C: ComponentCollection<u64, u64>>() {
+            let components = C::default();
+            assert_eq!(None, components.first());
+            assert_eq!(None, components.last());
+            let components = C::from_iter(vec![(7u64, 0u64)]);
+            assert_eq!(Some(7), components.first());
+            assert_eq!(Some(7), components.last());
+            let components = C::from_iter(vec![(3u64, 0u64), (5, 1), (8, 2)]);
+            assert_eq!(Some(3), components.first());
+            assert_eq!(Some(8), components.last());
+            let components = C::from_iter((1..=100_000u64).map(|e| (e * 3, e)));
+            assert_eq!(Some(3), components.first());
+            assert_eq!(Some(300_000), components.last());
+        }
+        check::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check::<super::OrderedComponentCollection<u64, u64>>();
+        check::<super::MutableComponentCollection<u64, u64>>();
+        check::<crate::Partitioned<u64, u64, super::MutableComponentCollection<u64, u64>>>();
+    }
+
+    #[test]
+    fn iter_range() {
+        fn check<C: ComponentCollection<u64, u64>>() {
+            let components = C::from_iter((1..=100u64).map(|e| (e * 2, e)));
+            let ranged: Vec<(u64, u64)> = components.iter_range(10, 21).collect();
+            assert_eq!(
+                vec![(10, 5), (12, 6), (14, 7), (16, 8), (18, 9), (20, 10)],
+                ranged
+            );
+            assert_eq!(0, components.iter_range(11, 12).count());
+            assert_eq!(0, components.iter_range(21, 10).count());
+            assert_eq!(100, components.iter_range(0, u64::MAX).count());
+            assert_eq!(1, components.iter_range(200, u64::MAX).count());
+            assert_eq!(0, C::default().iter_range(0, u64::MAX).count());
+            // Nothing is held between items, so two ranges may be walked at once.
+            let zipped = components
+                .iter_range(0, 10)
+                .zip(components.iter_range(10, 20));
+            assert_eq!(4, zipped.count());
+        }
+        check::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check::<super::OrderedComponentCollection<u64, u64>>();
+        check::<super::MutableComponentCollection<u64, u64>>();
+        check::<crate::Partitioned<u64, u64, super::MutableComponentCollection<u64, u64>>>();
+    }
+
+    #[test]
+    fn split_at() {
+        use super::ComponentChange;
+
+        fn check<C: ComponentCollection<u64, u64>>() {
+            let components = C::from_iter((1..=100u64).map(|e| (e * 2, e)));
+            let (lower, upper) = components.split_at(101);
+            assert_eq!((Some(2), Some(100)), (lower.first(), lower.last()));
+            assert_eq!((Some(102), Some(200)), (upper.first(), upper.last()));
+            assert_eq!(50, *lower.get_ref(100).unwrap());
+            assert!(lower.get_ref(102).is_none());
+            assert_eq!(51, *upper.get_ref(102).unwrap());
+            assert!(upper.get_ref(100).is_none());
+            // Both halves remain valid collections.
+            let (mut lower, mut upper) = (lower, upper);
+            lower.apply(vec![
+                (1, ComponentChange::Value(0)),
+                (2, ComponentChange::Unbind),
+            ]);
+            upper.apply(vec![(201, ComponentChange::Value(0))]);
+            let recombined: Vec<(u64, u64)> = lower.consume().chain(upper.consume()).collect();
+            let mut expected: Vec<(u64, u64)> = (2..=100u64).map(|e| (e * 2, e)).collect();
+            expected.insert(0, (1, 0));
+            expected.push((201, 0));
+            assert_eq!(expected, recombined);
+            // A pivot past either end leaves one half empty.
+            let components = C::from_iter((1..=100u64).map(|e| (e * 2, e)));
+            let (lower, upper) = components.split_at(0);
+            assert!(lower.is_empty());
+            assert_eq!(100, upper.len());
+            let (lower, upper) = upper.split_at(u64::MAX);
+            assert_eq!(100, lower.len());
+            assert!(upper.is_empty());
+        }
+        check::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check::<super::OrderedComponentCollection<u64, u64>>();
+        check::<super::MutableComponentCollection<u64, u64>>();
+        check::<crate::Partitioned<u64, u64, super::MutableComponentCollection<u64, u64>>>();
+    }
+
+    #[test]
+    fn reserve() {
+        use super::{
+            ComponentChange, CopyOnWriteComponentCollection, InsertOptimizedComponentCollection,
+            MutableComponentCollection,
+        };
+
+        fn check<C: ComponentCollection<u64, u64>>(capacity: impl Fn(&C) -> usize) {
+            let mut components = C::from_iter((1..=10u64).map(|e| (e, e)));
+            components.reserve(1000);
+            let reserved = capacity(&components);
+            assert!(reserved >= components.len() + 1000);
+            // Enough changes to rebuild rather than edit in place; the rebuild keeps the room.
+            components.apply((5..=20u64).map(|e| (e, ComponentChange::Value(e * 2))));
+            assert_eq!(reserved, capacity(&components));
+            let expected: Vec<(u64, u64)> = (1..=20u64)
+                .map(|e| (e, if e < 5 { e } else { e * 2 }))
+                .collect();
+            assert_eq!(expected, components.consume().collect::<Vec<_>>());
+        }
+
+        check(CopyOnWriteComponentCollection::capacity);
+        check(InsertOptimizedComponentCollection::capacity);
+        check(MutableComponentCollection::capacity);
+    }
+
+    #[test]
+    fn sequence() {
+        use super::{ComponentChange, CopyOnWriteComponentCollection};
+
+        // NoChange, Unbind, and Value(value), by index, because ComponentChange is not Clone.
+        fn change(kind: usize, value: u64) -> ComponentChange<u64> {
+            match kind {
+                0 => ComponentChange::NoChange,
+                1 => ComponentChange::Unbind,
+                _ => ComponentChange::Value(value),
+            }
+        }
+
+        fn duplicate(change: &ComponentChange<u64>) -> ComponentChange<u64> {
+            match change {
+                ComponentChange::NoChange => ComponentChange::NoChange,
+                ComponentChange::Unbind => ComponentChange::Unbind,
+                ComponentChange::Value(v) => ComponentChange::Value(*v),
+            }
+        }
+
+        fn apply(initial: Option<u64>, changes: Vec<ComponentChange<u64>>) -> Vec<(u64, u64)> {
+            let mut collection =
+                CopyOnWriteComponentCollection::from_iter(initial.map(|v| (1u64, v)));
+            for change in changes {
+                collection.apply(vec![(1, change)]);
+            }
+            collection.consume().collect()
+        }
+
+        for first in 0..3 {
+            for second in 0..3 {
+                let sequenced = ComponentChange::sequence(change(first, 1), change(second, 2));
+                let folded = ComponentChange::fold_sequence(
+                    vec![change(first, 1), change(second, 2)].into_iter(),
+                );
+                for initial in [None, Some(100)] {
+                    let expected = apply(initial, vec![change(first, 1), change(second, 2)]);
+                    assert_eq!(expected, apply(initial, vec![duplicate(&sequenced)]));
+                    assert_eq!(expected, apply(initial, vec![duplicate(&folded)]));
+                }
+            }
+        }
+        assert!(ComponentChange::<u64>::fold_sequence(std::iter::empty()).is_no_change());
+    }
+
+    fn apply_batch_properties<C: ComponentCollection<u64, u64>>(
+        initial: &[u64],
+        batch: &[Vec<(u64, usize)>],
+    ) {
+        use super::ComponentChange;
+
+        // Build the changes afresh for each use because ComponentChange is not Clone.
+        let changes = || -> Vec<Vec<(u64, ComponentChange<u64>)>> {
+            batch
+                .iter()
+                .enumerate()
+                .map(|(k, changes)| {
+                    changes
+                        .iter()
+                        .map(|(e, kind)| match kind {
+                            0 => (*e, ComponentChange::NoChange),
+                            1 => (*e, ComponentChange::Unbind),
+                            _ => (*e, ComponentChange::Value(*e * 100 + k as u64)),
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+        let mut sequential = C::from_iter(initial.iter().map(|e| (*e, *e)));
+        for changes in changes() {
+            sequential.apply(changes);
+        }
+        let mut batched = C::from_iter(initial.iter().map(|e| (*e, *e)));
+        batched.apply_batch(changes());
+        assert_eq!(
+            sequential.consume().collect::<Vec<_>>(),
+            batched.consume().collect::<Vec<_>>()
+        );
+    }
+
+    proptest::prop_compose! {
+        fn arb_batch()(
+            initial in proptest::collection::btree_set(1u64..32, 0..16),
+            batch in proptest::collection::vec(proptest::collection::btree_map(1u64..32, 0usize..3, 0..16), 0..5),
+        ) -> (Vec<u64>, Vec<Vec<(u64, usize)>>) {
+            let initial = initial.into_iter().collect();
+            let batch = batch.into_iter().map(|c| c.into_iter().collect()).collect();
+            (initial, batch)
+        }
+    }
+
+    fn truncate_properties<C: ComponentCollection<u64, u64>>(initial: &[u64], n: usize) {
+        let pairs: Vec<(u64, u64)> = initial.iter().map(|e| (*e, !*e)).collect();
+        let mut truncated = C::from_iter(pairs.clone());
+        truncated.truncate_to(n);
+        assert_eq!(n.min(pairs.len()), truncated.len());
+        let expected: Vec<(u64, u64)> = pairs.iter().take(n).copied().collect();
+        assert_eq!(expected, truncated.consume().collect::<Vec<_>>());
+        let mut skipped = C::from_iter(pairs.clone());
+        skipped.skip_first(n);
+        assert_eq!(pairs.len().saturating_sub(n), skipped.len());
+        assert_eq!(pairs.get(n).map(|(e, _)| *e), skipped.first());
+        let expected: Vec<(u64, u64)> = pairs.iter().skip(n).copied().collect();
+        assert_eq!(expected, skipped.consume().collect::<Vec<_>>());
+    }
+
+    fn diff_properties<C: ComponentCollection<u64, u64>>(a: &[(u64, u64)], b: &[(u64, u64)]) {
+        let mut ours = C::from_iter(a.to_vec());
+        let theirs = C::from_iter(b.to_vec());
+        assert!(ours.diff(&ours).is_empty());
+        let diff = ours.diff(&theirs);
+        assert!(diff.windows(2).all(|w| w[0].0 < w[1].0));
+        for (e, change) in diff.iter() {
+            let expected = b.iter().find(|(x, _)| x == e).map(|(_, t)| *t);
+            match change {
+                super::ComponentChange::Unbind => assert!(expected.is_none()),
+                super::ComponentChange::Value(t) => assert_eq!(Some(*t), expected),
+                super::ComponentChange::NoChange => panic!("diff should skip unchanged entities"),
+            }
+        }
+        ours.apply(diff);
+        assert_eq!(b.to_vec(), ours.consume().collect::<Vec<_>>());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn truncate_to_and_skip_first(
+            initial in proptest::collection::btree_set(proptest::num::u64::ANY, 0..64),
+            n in 0usize..80,
+        ) {
+            let initial: Vec<u64> = initial.into_iter().collect();
+            truncate_properties::<super::CopyOnWriteComponentCollection<u64, u64>>(&initial, n);
+            truncate_properties::<super::InsertOptimizedComponentCollection<u64, u64>>(&initial, n);
+            truncate_properties::<super::OrderedComponentCollection<u64, u64>>(&initial, n);
+            truncate_properties::<super::MutableComponentCollection<u64, u64>>(&initial, n);
+            truncate_properties::<crate::Partitioned<u64, u64, super::MutableComponentCollection<u64, u64>>>(&initial, n);
+        }
+
+        #[test]
+        fn diff_round_trip(
+            a in proptest::collection::btree_map((0u64..64).prop_map(|e| if e < 60 { e } else { !(e - 60) }), 0u64..4, 0..48),
+            b in proptest::collection::btree_map((0u64..64).prop_map(|e| if e < 60 { e } else { !(e - 60) }), 0u64..4, 0..48),
+        ) {
+            let a: Vec<(u64, u64)> = a.into_iter().collect();
+            let b: Vec<(u64, u64)> = b.into_iter().collect();
+            diff_properties::<super::CopyOnWriteComponentCollection<u64, u64>>(&a, &b);
+            diff_properties::<super::InsertOptimizedComponentCollection<u64, u64>>(&a, &b);
+            diff_properties::<super::OrderedComponentCollection<u64, u64>>(&a, &b);
+            diff_properties::<super::MutableComponentCollection<u64, u64>>(&a, &b);
+            diff_properties::<crate::Partitioned<u64, u64, super::MutableComponentCollection<u64, u64>>>(&a, &b);
+        }
+
+        #[test]
+        fn apply_batch(input in arb_batch()) {
+            let (initial, batch) = input;
+            apply_batch_properties::<super::CopyOnWriteComponentCollection<u64, u64>>(&initial, &batch);
+            apply_batch_properties::<super::InsertOptimizedComponentCollection<u64, u64>>(&initial, &batch);
+            apply_batch_properties::<super::MutableComponentCollection<u64, u64>>(&initial, &batch);
+        }
+    }
+
+    fn from_unsorted_parallel_properties<C: ComponentCollection<u64, usize>>(
+        pairs: &[(u64, usize)],
+        thread_pool: &crate::ThreadPool,
+    ) {
+        let mut sorted = pairs.to_vec();
+        sorted.sort_by_key(|(e, _)| *e);
+        let mut expected: Vec<(u64, usize)> = vec![];
+        for (e, t) in sorted.into_iter() {
+            match expected.last_mut() {
+                Some(last) if last.0 == e => *last = (e, t),
+                _ => expected.push((e, t)),
+            }
+        }
+        let collection = C::from_unsorted_parallel(pairs.iter().copied(), thread_pool);
+        assert_eq!(expected, collection.consume().collect::<Vec<_>>());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn parallel_sort_by_entity(entities in proptest::collection::vec(0u64..256, 0..4096), threads in 1usize..6) {
+            let thread_pool = crate::ThreadPool::new("parallel_sort", threads);
+            let pairs: Vec<(u64, usize)> = entities.into_iter().enumerate().map(|(i, e)| (e, i)).collect();
+            let mut expected = pairs.clone();
+            expected.sort_by_key(|(e, _)| *e);
+            assert_eq!(expected, super::parallel_sort_by_entity(pairs.clone(), &thread_pool));
+            from_unsorted_parallel_properties::<super::CopyOnWriteComponentCollection<u64, usize>>(&pairs, &thread_pool);
+            from_unsorted_parallel_properties::<super::InsertOptimizedComponentCollection<u64, usize>>(&pairs, &thread_pool);
+            from_unsorted_parallel_properties::<super::MutableComponentCollection<u64, usize>>(&pairs, &thread_pool);
+            thread_pool.shutdown();
+        }
+    }
+
+    #[test]
+    fn unbind_all() {
+        fn check<C: ComponentCollection<u64, u64>>() {
+            let mut components = C::from_iter((1..=10u64).map(|e| (e, e * 10)));
+            components.unbind_all(vec![7u64, 2, 42, 4, 2]);
+            for e in 1..=10u64 {
+                if [2, 4, 7].contains(&e) {
+                    assert!(!components.contains_entity(e));
+                    assert!(components.get_ref(e).is_none());
+                } else {
+                    assert_eq!(Some(e * 10), components.get_cloned(e));
+                }
+            }
+            assert_eq!(7, components.len());
+        }
+        check::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check::<super::OrderedComponentCollection<u64, u64>>();
+        check::<super::MutableComponentCollection<u64, u64>>();
+    }
+
+    #[test]
+    fn split_by_predicate() {
+        fn check<C: ComponentCollection<u64, u64>>() {
+            let pairs: Vec<(u64, u64)> = (1..=100u64).map(|e| (e, e * 7 % 13)).collect();
+            let even = |_: u64, t: &u64| *t & 1 == 0;
+            let (matching, non_matching) = C::from_iter(pairs.clone()).split_by_predicate(even);
+            assert_eq!(pairs.len(), matching.len() + non_matching.len());
+            let matching: Vec<(u64, u64)> = matching.consume().collect();
+            let non_matching: Vec<(u64, u64)> = non_matching.consume().collect();
+            assert!(matching.iter().all(|(e, t)| even(*e, t)));
+            assert!(!non_matching.iter().any(|(e, t)| even(*e, t)));
+            let mut recombined = matching;
+            recombined.extend(non_matching);
+            recombined.sort();
+            assert_eq!(pairs, recombined);
+            let (all, none) = C::default().split_by_predicate(|_, _| true);
+            assert!(all.is_empty() && none.is_empty());
+        }
+        check::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check::<super::OrderedComponentCollection<u64, u64>>();
+        check::<super::MutableComponentCollection<u64, u64>>();
+        check::<crate::Partitioned<u64, u64, super::MutableComponentCollection<u64, u64>>>();
+    }
+
+    #[test]
+    fn next_free() {
+        fn check<C: ComponentCollection<u64, u64>>() {
+            let mut components = C::from_iter([1u64, 2, 4, 7].map(|e| (e, e)));
+            assert_eq!(Some(3), components.next_free(1));
+            assert_eq!(Some(5), components.next_free(3));
+            assert_eq!(Some(8), components.next_free(7));
+            assert_eq!(Some(9), components.next_free(8));
+            let mut allocated = vec![];
+            for _ in 0..5 {
+                let entity = components.next_free(0).unwrap();
+                components.apply(vec![(entity, super::ComponentChange::Value(entity))]);
+                allocated.push(entity);
+            }
+            assert_eq!(vec![3, 5, 6, 8, 9], allocated);
+            assert_eq!(None, components.next_free(u64::MAX));
+            let components = C::from_iter([u64::MAX - 1, u64::MAX].map(|e| (e, e)));
+            assert_eq!(None, components.next_free(u64::MAX - 2));
+            assert_eq!(Some(u64::MAX - 2), components.next_free(u64::MAX - 3));
+            assert_eq!(Some(1), C::default().next_free(0));
+        }
+        check::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check::<super::OrderedComponentCollection<u64, u64>>();
+        check::<super::MutableComponentCollection<u64, u64>>();
+        check::<crate::Partitioned<u64, u64, super::MutableComponentCollection<u64, u64>>>();
+    }
+
+    #[test]
+    fn into_collection() {
+        fn check<C1: ComponentCollection<u64, u64>, C2: ComponentCollection<u64, u64>>() {
+            let pairs: Vec<(u64, u64)> = [0, 3, 4, 9, u64::MAX].iter().map(|e| (*e, !*e)).collect();
+            let collection = C1::from_iter(pairs.clone());
+            let cloned: C2 = collection.clone_into_collection();
+            assert_eq!(pairs, cloned.consume().collect::<Vec<_>>());
+            let moved: C2 = collection.into_collection();
+            assert_eq!(pairs, moved.consume().collect::<Vec<_>>());
+        }
+        fn check_all<C1: ComponentCollection<u64, u64>>() {
+            check::<C1, super::CopyOnWriteComponentCollection<u64, u64>>();
+            check::<C1, super::InsertOptimizedComponentCollection<u64, u64>>();
+            check::<C1, super::OrderedComponentCollection<u64, u64>>();
+            check::<C1, super::MutableComponentCollection<u64, u64>>();
+        }
+        check_all::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check_all::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check_all::<super::OrderedComponentCollection<u64, u64>>();
+        check_all::<super::MutableComponentCollection<u64, u64>>();
+    }
+
+    #[test]
+    fn ref_state() {
+        fn check<C: ComponentCollection<u64, u64>>() {
+            let components = C::from_iter((1..=3u64).map(|e| (e, e * 10)));
+            {
+                let mut r = components.get_ref(1).unwrap();
+                assert!(!r.is_unbound());
+                assert!(!r.is_dirty());
+                r.update(|x| *x += 1);
+                assert!(!r.is_unbound());
+                assert!(r.is_dirty());
+                r.unbind();
+                assert!(r.is_unbound());
+            }
+            {
+                let mut r = components.get_ref(2).unwrap();
+                r.unbind();
+                assert!(r.is_unbound());
+                assert!(!r.is_dirty());
+            }
+            {
+                let r = components.get_ref(3).unwrap();
+                assert!(!r.is_unbound());
+                assert!(!r.is_dirty());
+            }
+        }
+        check::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check::<super::OrderedComponentCollection<u64, u64>>();
+        check::<super::MutableComponentCollection<u64, u64>>();
+    }
+
+    #[test]
+    fn apply_direct() {
+        fn check<C: super::SupportsDirectMutation<u64, u64>>(changes: usize) {
+            let mut components = C::from_iter((0..1024u64).map(|e| (e * 2, e)));
+            let mut expected: std::collections::BTreeMap<u64, u64> =
+                (0..1024u64).map(|e| (e * 2, e)).collect();
+            let mut batch = vec![];
+            for e in (0..2048u64).step_by(2048 / changes).take(changes) {
+                let change = match e % 3 {
+                    0 => {
+                        expected.remove(&e);
+                        super::ComponentChange::Unbind
+                    }
+                    1 => {
+                        expected.insert(e, e + 1);
+                        super::ComponentChange::Value(e + 1)
+                    }
+                    _ => super::ComponentChange::NoChange,
+                };
+                batch.push((e, change));
+            }
+            components.apply(batch);
+            assert_eq!(expected.len(), components.len());
+            for (e, t) in expected {
+                assert_eq!(Some(t), components.get_cloned(e));
+            }
+            assert_eq!(None, components.remove_one(1));
+            assert_eq!(None, components.insert_one(1, 1));
+            assert_eq!(Some(1), components.insert_one(1, 2));
+            assert_eq!(Some(2), components.remove_one(1));
+            assert!(!components.contains_entity(1));
+        }
+        for changes in [1, 9, 10, 100] {
+            check::<super::CopyOnWriteComponentCollection<u64, u64>>(changes);
+            check::<super::InsertOptimizedComponentCollection<u64, u64>>(changes);
+            check::<super::OrderedComponentCollection<u64, u64>>(changes);
+            check::<super::MutableComponentCollection<u64, u64>>(changes);
+        }
+    }
+
+    #[test]
+    fn last_operation_wins() {
+        fn check<C: ComponentCollection<u64, u64>>() {
+            let mut components = C::from_iter((1..=2u64).map(|e| (e, e * 10)));
+            let mut r = components.get_ref(1).unwrap();
+            r.unbind();
+            r.update(|x| *x += 1);
+            assert!(!r.is_unbound());
+            assert_eq!(11, *r);
+            let rebound = r.change();
+            let mut r = components.get_ref(2).unwrap();
+            r.update(|x| *x += 1);
+            r.unbind();
+            assert!(r.is_unbound());
+            let unbound = r.change();
+            components.apply(vec![(1, rebound), (2, unbound)]);
+            assert_eq!(Some(11), components.get_cloned(1));
+            assert_eq!(None, components.get_cloned(2));
+        }
+        check::<super::CopyOnWriteComponentCollection<u64, u64>>();
+        check::<super::InsertOptimizedComponentCollection<u64, u64>>();
+        check::<super::OrderedComponentCollection<u64, u64>>();
+        check::<super::MutableComponentCollection<u64, u64>>();
+    }
 }
@@ -2,7 +2,10 @@ use std::fmt::Debug;
 use std::ops::Deref;
 use std::sync::{Mutex, MutexGuard};
 
-use super::{ComponentChange, ComponentCollection, ComponentRef};
+use super::{
+    apply_component_changes_to_vecs, apply_direct_or_merge, ComponentChange, ComponentCollection,
+    ComponentRef, SupportsDirectMutation,
+};
 use crate::{Entity, EntityMap, VecEntityMap};
 
 //////////////////////////////////////// MutableComponentRef ///////////////////////////////////////
@@ -10,6 +13,7 @@ use crate::{Entity, EntityMap, VecEntityMap};
 /// The ComponentRef for MutableComponentCollection.
 pub struct MutableComponentRef<'a, T: Debug> {
     unbound: bool,
+    dirty: bool,
     this: MutexGuard<'a, Vec<T>>,
     idx: usize,
 }
@@ -17,7 +21,13 @@ pub struct MutableComponentRef<'a, T: Debug> {
 impl<'a, T: Debug> MutableComponentRef<'a, T> {
     fn new(this: MutexGuard<'a, Vec<T>>, idx: usize) -> Self {
         let unbound = false;
-        Self { unbound, this, idx }
+        let dirty = false;
+        Self {
+            unbound,
+            dirty,
+            this,
+            idx,
+        }
     }
 }
 
@@ -25,6 +35,7 @@ impl<'a, T: Debug> Debug for MutableComponentRef<'a, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         f.debug_struct("MutableComponentRef<T>")
             .field("unbound", &self.unbound)
+            .field("dirty", &self.dirty)
             .field("this", &self.this[self.idx])
             .finish()
     }
@@ -44,6 +55,8 @@ impl<'a, T: Debug> ComponentRef<T> for MutableComponentRef<'a, T> {
     }
 
     fn update<F: FnOnce(&mut T) -> U, U>(&mut self, f: F) -> U {
+        self.unbound = false;
+        self.dirty = true;
         f(&mut self.this[self.idx])
     }
 
@@ -54,6 +67,14 @@ impl<'a, T: Debug> ComponentRef<T> for MutableComponentRef<'a, T> {
             ComponentChange::NoChange
         }
     }
+
+    fn is_unbound(&self) -> bool {
+        self.unbound
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
 }
 
 //////////////////////////////////// MutableComponentCollection ////////////////////////////////////
@@ -82,6 +103,33 @@ impl<E: Entity, T: Debug> Default for MutableComponentCollection<E, T> {
     }
 }
 
+impl<E: Entity, T: Debug> MutableComponentCollection<E, T> {
+    /// The number of components the collection can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        let components = self.components.lock().unwrap();
+        self.entities.capacity().min(components.capacity())
+    }
+
+    /// Iterate the entities of the collection in order, alongside a mutable reference to each
+    /// entity's component.  The iterator borrows the collection exclusively for its lifetime,
+    /// which is equivalent to holding the collection's lock; no system may run against the
+    /// collection until the iterator is dropped.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (E, &mut T)> + '_ {
+        let components = self.components.get_mut().unwrap();
+        std::iter::zip(self.entities.iter(), components.iter_mut())
+    }
+
+    /// Call `f` on every entity of the collection in order, alongside a mutable reference to the
+    /// entity's component.  Mutations are visible as soon as this returns, without going through
+    /// [ComponentCollection::apply].  Like [Self::iter_mut], this holds the collection
+    /// exclusively for the duration of the call.
+    pub fn for_each_mut<F: FnMut(E, &mut T)>(&mut self, mut f: F) {
+        for (entity, component) in self.iter_mut() {
+            f(entity, component);
+        }
+    }
+}
+
 impl<E: Entity, T: Debug> ComponentCollection<E, T> for MutableComponentCollection<E, T> {
     type Ref<'a> = MutableComponentRef<'a, T> where Self: 'a, T: 'a;
     type Consumed = std::iter::Zip<std::vec::IntoIter<E>, std::vec::IntoIter<T>>;
@@ -98,6 +146,26 @@ impl<E: Entity, T: Debug> ComponentCollection<E, T> for MutableComponentCollecti
         self.entities.lower_bound(lower_bound)
     }
 
+    fn first(&self) -> Option<E> {
+        if self.entities.is_empty() {
+            None
+        } else {
+            Some(self.entities.get(0))
+        }
+    }
+
+    fn last(&self) -> Option<E> {
+        if self.entities.is_empty() {
+            None
+        } else {
+            Some(self.entities.get(self.entities.len() - 1))
+        }
+    }
+
+    fn contains_entity(&self, entity: E) -> bool {
+        self.entities.exact_offset_of(entity).is_some()
+    }
+
     fn get_ref(&self, entity: E) -> Option<Self::Ref<'_>> {
         if let Some(offset) = self.entities.exact_offset_of(entity) {
             let components = self.components.lock().unwrap();
@@ -107,6 +175,64 @@ impl<E: Entity, T: Debug> ComponentCollection<E, T> for MutableComponentCollecti
         }
     }
 
+    /// Small change sets are applied in place; see [SupportsDirectMutation].
+    fn apply<I: IntoIterator<Item = (E, ComponentChange<T>)>>(&mut self, changes: I) {
+        apply_direct_or_merge(self, changes.into_iter(), |this, changes| {
+            // NOTE(rescrv):  Rebuild into vectors with the old capacity so reserve is honored.
+            let entities = std::mem::replace(&mut this.entities, VecEntityMap::from_iter(vec![]));
+            let components = std::mem::take(this.components.get_mut().unwrap());
+            let (entities, components) =
+                apply_component_changes_to_vecs(entities.into_vec(), components, changes);
+            this.entities = VecEntityMap::from_sorted_vec(entities);
+            *this.components.get_mut().unwrap() = components;
+        });
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.entities.reserve(additional);
+        self.components.get_mut().unwrap().reserve(additional);
+    }
+
+    fn next_free(&self, after: E) -> Option<E> {
+        self.entities.next_free(after)
+    }
+
+    fn truncate_to(&mut self, n: usize) {
+        if n < self.entities.len() {
+            self.entities.split_off(n);
+            self.components.get_mut().unwrap().truncate(n);
+        }
+    }
+
+    fn skip_first(&mut self, n: usize) {
+        let n = n.min(self.entities.len());
+        self.entities = self.entities.split_off(n);
+        self.components.get_mut().unwrap().drain(..n);
+    }
+
+    fn split_at(mut self, pivot: E) -> (Self, Self) {
+        let at = self.entities.offset_of(pivot);
+        let entities = self.entities.split_off(at);
+        let components = Mutex::new(self.components.get_mut().unwrap().split_off(at));
+        let upper = Self {
+            entities,
+            components,
+        };
+        (self, upper)
+    }
+
+    fn iter_range<'a>(&'a self, lo: E, hi: E) -> impl Iterator<Item = (E, T)>
+    where
+        T: Clone + 'a,
+    {
+        let start = self.entities.offset_of(lo);
+        let limit = self.entities.offset_of(hi);
+        (start..limit).map(move |idx| {
+            let component = self.components.lock().unwrap()[idx].clone();
+            (self.entities.get(idx), component)
+        })
+    }
+
     fn consume(self) -> Self::Consumed {
         let e = self.entities.into_iter();
         let t = self.components.into_inner().unwrap().into_iter();
@@ -114,6 +240,26 @@ impl<E: Entity, T: Debug> ComponentCollection<E, T> for MutableComponentCollecti
     }
 }
 
+impl<E: Entity, T: Debug> SupportsDirectMutation<E, T> for MutableComponentCollection<E, T> {
+    fn insert_one(&mut self, entity: E, component: T) -> Option<T> {
+        let offset = self.entities.offset_of(entity);
+        let components = self.components.get_mut().unwrap();
+        if offset < self.entities.len() && self.entities.get(offset) == entity {
+            Some(std::mem::replace(&mut components[offset], component))
+        } else {
+            self.entities.insert(offset, entity);
+            components.insert(offset, component);
+            None
+        }
+    }
+
+    fn remove_one(&mut self, entity: E) -> Option<T> {
+        let offset = self.entities.exact_offset_of(entity)?;
+        self.entities.remove(offset);
+        Some(self.components.get_mut().unwrap().remove(offset))
+    }
+}
+
 impl<E: Entity, T: Debug> FromIterator<(E, T)> for MutableComponentCollection<E, T> {
     fn from_iter<I: IntoIterator<Item = (E, T)>>(iter: I) -> Self {
         let mut entities = vec![];
@@ -152,18 +298,74 @@ impl<E: Entity, T: Debug> FromIterator<(E, ComponentChange<T>)>
     }
 }
 
+/// Collect in parallel into a vector that then gets sorted by entity before building the
+/// collection.  The sort is stable, so entities must still be unique.
+#[cfg(feature = "rayon")]
+impl<E: Entity + Send, T: Debug + Send> rayon::iter::FromParallelIterator<(E, T)>
+    for MutableComponentCollection<E, T>
+{
+    fn from_par_iter<I: rayon::iter::IntoParallelIterator<Item = (E, T)>>(iter: I) -> Self {
+        use rayon::iter::ParallelIterator;
+        use rayon::slice::ParallelSliceMut;
+        let mut pairs: Vec<(E, T)> = iter.into_par_iter().collect();
+        pairs.par_sort_by_key(|(e, _)| *e);
+        Self::from_iter(pairs)
+    }
+}
+
 /////////////////////////////////////////////// tests //////////////////////////////////////////////
 
 #[cfg(test)]
 mod tests {
     use super::super::tests::{arb_entities, collection_properties};
 
+    use crate::{ComponentChange, ComponentCollection};
+
     use super::MutableComponentCollection;
 
+    #[test]
+    fn iter_mut() {
+        let mut collection =
+            MutableComponentCollection::from_iter(vec![(1u64, 10u64), (2, 20), (5, 50)]);
+        for (entity, value) in collection.iter_mut() {
+            *value += entity;
+        }
+        let pairs: Vec<(u64, u64)> = collection.iter_mut().map(|(e, v)| (e, *v)).collect();
+        assert_eq!(vec![(1, 11), (2, 22), (5, 55)], pairs);
+        assert_eq!(22, *collection.get_ref(2).unwrap());
+    }
+
+    #[test]
+    fn for_each_mut() {
+        let mut collection =
+            MutableComponentCollection::from_iter(vec![(1u64, 10u64), (2, 20), (5, 50)]);
+        collection.for_each_mut(|entity, value| *value *= entity);
+        assert_eq!(Some(10), collection.get_cloned(1));
+        assert_eq!(Some(40), collection.get_cloned(2));
+        assert_eq!(Some(250), collection.get_cloned(5));
+    }
+
     proptest::proptest! {
         #[test]
         fn mut_collection_properties(entities in arb_entities()) {
             collection_properties::<u128, usize, MutableComponentCollection<u128, usize>>(entities);
         }
+
+        #[test]
+        fn for_each_mut_matches_apply(entities in arb_entities()) {
+            let transform = |entity: u128, value: usize| value.wrapping_mul(3) ^ entity as usize;
+            let mut in_place = MutableComponentCollection::from_iter(entities.clone());
+            in_place.for_each_mut(|entity, value| *value = transform(entity, *value));
+            let mut applied = MutableComponentCollection::from_iter(entities.clone());
+            applied.apply(
+                entities
+                    .iter()
+                    .map(|(e, v)| (*e, ComponentChange::Value(transform(*e, *v)))),
+            );
+            assert_eq!(
+                applied.consume().collect::<Vec<_>>(),
+                in_place.consume().collect::<Vec<_>>()
+            );
+        }
     }
 }
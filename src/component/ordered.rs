@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::ops::{Bound, Deref};
+use std::sync::{Mutex, MutexGuard};
+
+use super::{ComponentChange, ComponentCollection, ComponentRef, SupportsDirectMutation};
+use crate::Entity;
+
+//////////////////////////////////////// OrderedComponentRef ///////////////////////////////////////
+
+/// The [ComponentRef] type for [OrderedComponentCollection].
+pub struct OrderedComponentRef<'a, E: Entity, T: Debug> {
+    unbound: bool,
+    dirty: bool,
+    this: MutexGuard<'a, BTreeMap<E, T>>,
+    entity: E,
+}
+
+impl<'a, E: Entity, T: Debug> OrderedComponentRef<'a, E, T> {
+    fn new(this: MutexGuard<'a, BTreeMap<E, T>>, entity: E) -> Self {
+        assert!(this.contains_key(&entity));
+        let unbound = false;
+        let dirty = false;
+        Self {
+            unbound,
+            dirty,
+            this,
+            entity,
+        }
+    }
+}
+
+impl<'a, E: Entity, T: Debug> Debug for OrderedComponentRef<'a, E, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        f.debug_struct("OrderedComponentRef<T>")
+            .field("unbound", &self.unbound)
+            .field("dirty", &self.dirty)
+            .field("this", &self.this[&self.entity])
+            .finish()
+    }
+}
+
+impl<'a, E: Entity, T: Debug> Deref for OrderedComponentRef<'a, E, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.this[&self.entity]
+    }
+}
+
+impl<'a, E: Entity, T: Debug> ComponentRef<T> for OrderedComponentRef<'a, E, T> {
+    fn unbind(&mut self) {
+        self.unbound = true;
+    }
+
+    fn update<F: FnOnce(&mut T) -> U, U>(&mut self, f: F) -> U {
+        self.unbound = false;
+        self.dirty = true;
+        // SAFETY(rescrv):  The entity is present on construction and the guard is held since.
+        f(self.this.get_mut(&self.entity).unwrap())
+    }
+
+    fn change(self) -> ComponentChange<T> {
+        if self.unbound {
+            ComponentChange::Unbind
+        } else {
+            ComponentChange::NoChange
+        }
+    }
+
+    fn is_unbound(&self) -> bool {
+        self.unbound
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+//////////////////////////////////// OrderedComponentCollection ////////////////////////////////////
+
+/// A ComponentCollection that keeps its components in a single `BTreeMap` keyed by entity.
+/// Components are mutated in place like MutableComponentCollection, and single inserts and
+/// removals cost a tree descent rather than a shift or rebuild of the collection.  Unlike
+/// InsertOptimizedComponentCollection, a lookup is one descent with no indirection through a
+/// slab of components.
+///
+/// The locking is at the granularity of the collection:  an [OrderedComponentRef] holds the lock
+/// for as long as it lives, so calling any other `&self` method on the same collection while
+/// holding a ref will deadlock.
+#[derive(Debug)]
+pub struct OrderedComponentCollection<E: Entity, T: Debug> {
+    components: Mutex<BTreeMap<E, T>>,
+}
+
+impl<E: Entity, T: Debug> OrderedComponentCollection<E, T> {
+    /// Bind the provided component to the specified entity, returning the component previously
+    /// bound to it.
+    pub fn insert(&self, entity: E, component: T) -> Option<T> {
+        self.components.lock().unwrap().insert(entity, component)
+    }
+
+    /// Unbind the component bound to the specified entity and return it.
+    pub fn remove(&self, entity: E) -> Option<T> {
+        self.components.lock().unwrap().remove(&entity)
+    }
+}
+
+impl<E: Entity, T: Debug> Default for OrderedComponentCollection<E, T> {
+    fn default() -> Self {
+        let components = Mutex::new(BTreeMap::new());
+        Self { components }
+    }
+}
+
+impl<E: Entity, T: Debug> ComponentCollection<E, T> for OrderedComponentCollection<E, T> {
+    type Ref<'a> = OrderedComponentRef<'a, E, T> where Self: 'a, T: 'a;
+    type Consumed = std::collections::btree_map::IntoIter<E, T>;
+
+    fn is_empty(&self) -> bool {
+        self.components.lock().unwrap().is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.components.lock().unwrap().len()
+    }
+
+    fn lower_bound(&self, lower_bound: E) -> Option<E> {
+        let components = self.components.lock().unwrap();
+        components
+            .range((Bound::Included(lower_bound), Bound::Unbounded))
+            .next()
+            .map(|x| *x.0)
+    }
+
+    fn first(&self) -> Option<E> {
+        let components = self.components.lock().unwrap();
+        components.first_key_value().map(|x| *x.0)
+    }
+
+    fn last(&self) -> Option<E> {
+        let components = self.components.lock().unwrap();
+        components.last_key_value().map(|x| *x.0)
+    }
+
+    fn contains_entity(&self, entity: E) -> bool {
+        self.components.lock().unwrap().contains_key(&entity)
+    }
+
+    fn get_ref(&self, entity: E) -> Option<Self::Ref<'_>> {
+        let components = self.components.lock().unwrap();
+        if components.contains_key(&entity) {
+            Some(OrderedComponentRef::new(components, entity))
+        } else {
+            None
+        }
+    }
+
+    fn consume(self) -> Self::Consumed {
+        self.components.into_inner().unwrap().into_iter()
+    }
+
+    fn truncate_to(&mut self, n: usize) {
+        let components = self.components.get_mut().unwrap();
+        while components.len() > n {
+            components.pop_last();
+        }
+    }
+
+    fn skip_first(&mut self, n: usize) {
+        let components = self.components.get_mut().unwrap();
+        for _ in 0..n.min(components.len()) {
+            components.pop_first();
+        }
+    }
+
+    fn split_at(mut self, pivot: E) -> (Self, Self) {
+        let upper = self.components.get_mut().unwrap().split_off(&pivot);
+        let upper = Self {
+            components: Mutex::new(upper),
+        };
+        (self, upper)
+    }
+
+    /// Every change is applied in place; a `BTreeMap` never needs a rebuild.
+    fn apply<I: IntoIterator<Item = (E, ComponentChange<T>)>>(&mut self, changes: I) {
+        for (entity, change) in changes {
+            match change {
+                ComponentChange::NoChange => {}
+                ComponentChange::Unbind => {
+                    self.remove_one(entity);
+                }
+                ComponentChange::Value(component) => {
+                    self.insert_one(entity, component);
+                }
+            }
+        }
+    }
+
+    fn unbind_all<I: IntoIterator<Item = E>>(&mut self, entities: I) {
+        let components = self.components.get_mut().unwrap();
+        for entity in entities {
+            components.remove(&entity);
+        }
+    }
+}
+
+impl<E: Entity, T: Debug> SupportsDirectMutation<E, T> for OrderedComponentCollection<E, T> {
+    fn insert_one(&mut self, entity: E, component: T) -> Option<T> {
+        self.components.get_mut().unwrap().insert(entity, component)
+    }
+
+    fn remove_one(&mut self, entity: E) -> Option<T> {
+        self.components.get_mut().unwrap().remove(&entity)
+    }
+}
+
+impl<E: Entity, T: Debug> FromIterator<(E, T)> for OrderedComponentCollection<E, T> {
+    fn from_iter<I: IntoIterator<Item = (E, T)>>(iter: I) -> Self {
+        let components = Mutex::new(BTreeMap::from_iter(iter));
+        Self { components }
+    }
+}
+
+impl<E: Entity, T: Debug> FromIterator<(E, ComponentChange<T>)>
+    for OrderedComponentCollection<E, T>
+{
+    fn from_iter<I: IntoIterator<Item = (E, ComponentChange<T>)>>(iter: I) -> Self {
+        let components = iter
+            .into_iter()
+            .filter_map(|(e, t)| match t {
+                ComponentChange::Value(t) => Some((e, t)),
+                _ => None,
+            })
+            .collect();
+        let components = Mutex::new(components);
+        Self { components }
+    }
+}
+
+/////////////////////////////////////////////// tests //////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{arb_entities, collection_properties};
+
+    use super::OrderedComponentCollection;
+    use crate::{ComponentChange, ComponentCollection, ComponentRef};
+
+    proptest::proptest! {
+        #[test]
+        fn ordered_collection_properties(entities in arb_entities()) {
+            collection_properties::<u128, usize, OrderedComponentCollection<u128, usize>>(entities);
+        }
+    }
+
+    #[test]
+    fn insert_remove() {
+        let collection = OrderedComponentCollection::from_iter(vec![(5u64, 50u64), (9, 90)]);
+        assert_eq!(None, collection.insert(7, 70));
+        assert_eq!(Some(90), collection.insert(9, 99));
+        assert_eq!(Some(7), collection.lower_bound(6));
+        assert_eq!(Some(50), collection.remove(5));
+        assert_eq!(None, collection.remove(5));
+        assert_eq!(
+            vec![(7, 70), (9, 99)],
+            collection.consume().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn update_in_place() {
+        let mut collection = OrderedComponentCollection::from_iter((1..=10u64).map(|e| (e, e)));
+        let mut changes = vec![];
+        for e in 1..=10u64 {
+            let mut component = collection.get_ref(e).unwrap();
+            if e <= 3 {
+                component.unbind();
+            } else {
+                component.update(|c| *c *= 10);
+            }
+            changes.push((e, component.change()));
+        }
+        assert!(changes[3..]
+            .iter()
+            .all(|(_, c)| matches!(c, ComponentChange::NoChange)));
+        collection.apply(changes);
+        assert_eq!(
+            (4..=10u64).map(|e| (e, e * 10)).collect::<Vec<_>>(),
+            collection.consume().collect::<Vec<_>>()
+        );
+    }
+}
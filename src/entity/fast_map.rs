@@ -6,10 +6,12 @@ const FLAG_MASK: u64 = 31;
 
 /////////////////////////////////////////////// Node ///////////////////////////////////////////////
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 #[repr(C, align(64))]
 struct Node<E: Entity> {
     flags: u64,
+    // NOTE(rescrv):  Narrowing offset to u32 saves nothing:  Nodes are padded out to a multiple of
+    // the 64B cache line, so they are 192, 320, and 512 bytes for u32, u64, and u128 either way.
     offset: usize,
     entities: [E; FANOUT],
 }
@@ -48,12 +50,17 @@ impl<E: Entity> Node<E> {
 
 impl<E: Entity> From<Vec<E>> for Node<E> {
     fn from(ents: Vec<E>) -> Self {
+        Self::from(ents.as_slice())
+    }
+}
+
+impl<E: Entity> From<&[E]> for Node<E> {
+    fn from(ents: &[E]) -> Self {
         assert!(ents.len() <= FANOUT);
-        assert!(!ents.iter().any(|e| *e == E::default()));
         let mut flags = IS_LEAF;
         flags += ents.len() as u64;
         let mut entities = [E::default(); FANOUT];
-        entities[..ents.len()].copy_from_slice(&ents);
+        entities[..ents.len()].copy_from_slice(ents);
         Self {
             flags,
             offset: 0,
@@ -75,7 +82,12 @@ impl<'a, E: Entity> Iterator for FastEntityMapIterator<'a, E> {
     type Item = E;
 
     fn next(&mut self) -> Option<E> {
-        if self.index1 >= self.nodes.len() || self.nodes[self.index1].flags & IS_LEAF == 0 {
+        // NOTE(rescrv):  Only the last leaf may be partially full, so the first slot past a
+        // leaf's length marks the end.  E::default() is a valid entity and not a sentinel.
+        if self.index1 >= self.nodes.len()
+            || self.nodes[self.index1].flags & IS_LEAF == 0
+            || self.index2 >= self.nodes[self.index1].len()
+        {
             None
         } else {
             let entity = self.nodes[self.index1].entities[self.index2];
@@ -84,11 +96,7 @@ impl<'a, E: Entity> Iterator for FastEntityMapIterator<'a, E> {
                 self.index2 = 0;
                 self.index1 += 1;
             }
-            if entity != E::default() {
-                Some(entity)
-            } else {
-                None
-            }
+            Some(entity)
         }
     }
 }
@@ -106,7 +114,12 @@ impl<E: Entity> Iterator for FastEntityMapIntoIterator<E> {
     type Item = E;
 
     fn next(&mut self) -> Option<E> {
-        if self.index1 >= self.nodes.len() || self.nodes[self.index1].flags & IS_LEAF == 0 {
+        // NOTE(rescrv):  Only the last leaf may be partially full, so the first slot past a
+        // leaf's length marks the end.  E::default() is a valid entity and not a sentinel.
+        if self.index1 >= self.nodes.len()
+            || self.nodes[self.index1].flags & IS_LEAF == 0
+            || self.index2 >= self.nodes[self.index1].len()
+        {
             None
         } else {
             let entity = self.nodes[self.index1].entities[self.index2];
@@ -115,11 +128,7 @@ impl<E: Entity> Iterator for FastEntityMapIntoIterator<E> {
                 self.index2 = 0;
                 self.index1 += 1;
             }
-            if entity != E::default() {
-                Some(entity)
-            } else {
-                None
-            }
+            Some(entity)
         }
     }
 }
@@ -129,13 +138,29 @@ impl<E: Entity> Iterator for FastEntityMapIntoIterator<E> {
 /// FastEntityMap is a cache-friendlier version of an entity map, compared to vector or other
 /// implementations.  In practice, FastEntityMap can be slower to construct, but provide faster
 /// lookup times.
-#[derive(Debug)]
+///
+/// Unlike [crate::VecEntityMap::as_slice], there is no contiguous slice of entities to hand out:
+/// entities sit in leaves padded out to a fixed fanout, followed by the internal nodes.
+#[derive(Clone, Debug)]
 pub struct FastEntityMap<E: Entity> {
     nodes: Vec<Node<E>>,
     size: usize,
 }
 
 impl<E: Entity> FastEntityMap<E> {
+    /// Construct a map from a sorted, de-duplicated slice, copying it into the leaves a chunk at a
+    /// time rather than an entity at a time as [FastEntityMap::from_iter] does.
+    pub fn from_sorted_slice(entities: &[E]) -> Self {
+        debug_assert!(entities.windows(2).all(|w| w[0] < w[1]));
+        if entities.is_empty() {
+            return Self::seal(0, vec![Node::<E>::leaf()], 0, 1);
+        }
+        let mut nodes = Vec::with_capacity((entities.len() + FANOUT - 1) / FANOUT);
+        nodes.extend(entities.chunks(FANOUT).map(Node::from));
+        let len = nodes.len();
+        Self::seal(entities.len(), nodes, 0, len)
+    }
+
     fn offset_of_recursive(&self, entity: E, index: usize) -> usize {
         if self.nodes[index].flags & IS_LEAF != 0 {
             let offset = self.nodes[index].lower_bound(entity);
@@ -165,6 +190,29 @@ impl<E: Entity> FastEntityMap<E> {
         }
     }
 
+    fn fill<I: IntoIterator<Item = E>>(nodes: &mut Vec<Node<E>>, entities: I) -> usize {
+        nodes.push(Node::<E>::leaf());
+        let mut index = 0;
+        let mut prev_entity = None;
+        let mut count = 0;
+        for entity in entities {
+            if index >= FANOUT {
+                nodes.push(Node::<E>::leaf());
+                index = 0;
+            }
+            if let Some(prev_entity) = prev_entity {
+                assert!(prev_entity < entity);
+            }
+            prev_entity = Some(entity);
+            let last = nodes.len() - 1;
+            nodes[last].entities[index] = entity;
+            nodes[last].flags += 1;
+            index += 1;
+            count += 1;
+        }
+        count
+    }
+
     fn seal(size: usize, mut nodes: Vec<Node<E>>, start: usize, limit: usize) -> Self {
         if start + 1 >= limit {
             return Self { nodes, size };
@@ -180,7 +228,7 @@ impl<E: Entity> FastEntityMap<E> {
                     internal_index = 0;
                 }
                 let last = nodes.len() - 1;
-                assert_ne!(E::default(), nodes[child_index + 1].entities[0]);
+                assert!(nodes[child_index + 1].len() > 0);
                 nodes[last].entities[internal_index] = nodes[child_index + 1].entities[0];
                 nodes[last].flags += 1;
                 internal_index += 1;
@@ -221,7 +269,7 @@ impl<E: Entity> EntityMap<E> for FastEntityMap<E> {
             None
         } else {
             let offset = self.offset_of_recursive(entity, self.nodes.len() - 1);
-            if self.get(offset) == entity {
+            if offset < self.size && self.get(offset) == entity {
                 Some(offset)
             } else {
                 None
@@ -244,8 +292,21 @@ impl<E: Entity> EntityMap<E> for FastEntityMap<E> {
             index2: 0,
         }
     }
+
+    fn from_sorted_vec(entities: Vec<E>) -> Self {
+        Self::from_sorted_slice(&entities)
+    }
+}
+
+/// Two maps are equal when they hold the same entities, regardless of how the nodes are laid out.
+impl<E: Entity> PartialEq for FastEntityMap<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size && self.iter().eq(other.iter())
+    }
 }
 
+impl<E: Entity> Eq for FastEntityMap<E> {}
+
 impl<E: Entity> IntoIterator for FastEntityMap<E> {
     type Item = E;
     type IntoIter = FastEntityMapIntoIterator<E>;
@@ -261,27 +322,57 @@ impl<E: Entity> IntoIterator for FastEntityMap<E> {
 
 impl<E: Entity> FromIterator<E> for FastEntityMap<E> {
     fn from_iter<I: IntoIterator<Item = E>>(entities: I) -> Self {
-        let mut nodes = vec![Node::<E>::leaf()];
-        let mut index = 0;
-        let prev_entity = E::default();
-        let mut count = 0;
-        for entity in entities {
-            if index >= FANOUT {
-                nodes.push(Node::<E>::leaf());
-                index = 0;
-            }
-            assert!(prev_entity < entity);
-            let last = nodes.len() - 1;
-            nodes[last].entities[index] = entity;
-            nodes[last].flags += 1;
-            index += 1;
-            count += 1;
-        }
+        let mut nodes = vec![];
+        let count = Self::fill(&mut nodes, entities);
         let len = nodes.len();
         Self::seal(count, nodes, 0, len)
     }
 }
 
+/////////////////////////////////////// FastEntityMapBuilder ///////////////////////////////////////
+
+/// FastEntityMapBuilder builds a [FastEntityMap] in place, reusing the node buffer of the previous
+/// map.  Build one per frame to amortize the allocation; the map is identical to
+/// [FastEntityMap::from_iter] over the same entities.
+#[derive(Debug)]
+pub struct FastEntityMapBuilder<E: Entity> {
+    map: FastEntityMap<E>,
+}
+
+impl<E: Entity> FastEntityMapBuilder<E> {
+    /// Create a new builder with an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Empty the most recently built map, keeping the buffer's capacity.
+    pub fn clear(&mut self) {
+        self.map.nodes.clear();
+        self.map.size = 0;
+    }
+
+    /// Build a map from sorted, de-duplicated entities, replacing the previously built map.
+    pub fn build<I: IntoIterator<Item = E>>(&mut self, entities: I) -> &FastEntityMap<E> {
+        let mut nodes = std::mem::take(&mut self.map.nodes);
+        nodes.clear();
+        let count = FastEntityMap::fill(&mut nodes, entities);
+        let len = nodes.len();
+        self.map = FastEntityMap::seal(count, nodes, 0, len);
+        &self.map
+    }
+}
+
+impl<E: Entity> Default for FastEntityMapBuilder<E> {
+    fn default() -> Self {
+        Self {
+            map: FastEntityMap {
+                nodes: vec![],
+                size: 0,
+            },
+        }
+    }
+}
+
 /////////////////////////////////////////////// tests //////////////////////////////////////////////
 
 #[cfg(test)]
@@ -290,10 +381,10 @@ mod tests {
 
     use proptest::strategy::Strategy;
 
-    use super::super::tests::check_entity_map;
+    use super::super::tests::{check_entity_map, check_merge_sorted};
     use super::*;
 
-    use crate::tests::{arb_entity, is_free_of_duplicates};
+    use crate::tests::{arb_entity, is_free_of_duplicates, with_extremes};
 
     proptest::prop_compose! {
         fn arb_entities_node()(mut entities in proptest::collection::vec(arb_entity(), 0..=FANOUT).prop_filter("dedupe", is_free_of_duplicates)) -> Vec<u128> {
@@ -304,10 +395,32 @@ mod tests {
     }
 
     proptest::prop_compose! {
-        fn arb_entities_fast_map()(mut entities in proptest::collection::vec(arb_entity(), 0..(FANOUT * FANOUT * FANOUT)).prop_filter("dedupe", is_free_of_duplicates)) -> Vec<u128> {
+        fn arb_entities_fast_map()(mut entities in proptest::collection::vec(arb_entity(), 0..(FANOUT * FANOUT * FANOUT)).prop_filter("dedupe", is_free_of_duplicates), default in proptest::bool::ANY, max in proptest::bool::ANY) -> Vec<u128> {
             entities.sort();
             entities.dedup();
-            entities
+            with_extremes(entities, default, max)
+        }
+    }
+
+    #[test]
+    fn node_size() {
+        assert_eq!(192, std::mem::size_of::<Node<u32>>());
+        assert_eq!(320, std::mem::size_of::<Node<u64>>());
+        assert_eq!(512, std::mem::size_of::<Node<u128>>());
+    }
+
+    #[test]
+    fn fast_map_default_entity() {
+        for len in [1, 2, FANOUT, FANOUT + 1, FANOUT * FANOUT + 3] {
+            let entities: Vec<u128> = (0..len as u128).map(|e| e * 2).collect();
+            assert_eq!(0, entities[0]);
+            let fast_map = FastEntityMap::from_iter(entities.clone());
+            assert_eq!(Some(0), fast_map.exact_offset_of(0));
+            assert_eq!(Some(0), fast_map.lower_bound(0));
+            assert_eq!(entities, fast_map.iter().collect::<Vec<_>>());
+            check_entity_map(entities.clone(), fast_map);
+            let fast_map = FastEntityMap::from_sorted_vec(entities.clone());
+            assert_eq!(entities, fast_map.into_iter().collect::<Vec<_>>());
         }
     }
 
@@ -330,5 +443,61 @@ mod tests {
             let fast_map = FastEntityMap::from_iter(entities.clone().into_iter());
             check_entity_map(entities, fast_map);
         }
+
+        #[test]
+        fn fast_map_merge_sorted(a in arb_entities_fast_map(), b in arb_entities_fast_map()) {
+            check_merge_sorted::<FastEntityMap<u128>>(a, b);
+        }
+
+        #[test]
+        fn fast_map_from_sorted_vec(entities in arb_entities_fast_map()) {
+            let fast_map = FastEntityMap::from_sorted_vec(entities.clone());
+            let from_iter = FastEntityMap::from_iter(entities.clone());
+            assert_eq!(from_iter.nodes.len(), fast_map.nodes.len());
+            assert!(std::iter::zip(fast_map.iter(), from_iter.iter()).all(|(lhs, rhs)| lhs == rhs));
+            assert_eq!(from_iter, fast_map);
+            check_entity_map(entities, fast_map);
+        }
+
+        #[test]
+        fn fast_map_from_sorted_slice(entities in arb_entities_fast_map()) {
+            let fast_map = FastEntityMap::from_sorted_slice(&entities);
+            let from_iter = FastEntityMap::from_iter(entities.iter().copied());
+            assert_eq!(from_iter.nodes.len(), fast_map.nodes.len());
+            assert_eq!(from_iter, fast_map);
+            check_entity_map(entities, fast_map);
+        }
+
+        #[test]
+        fn fast_map_clone_eq(entities in arb_entities_fast_map()) {
+            let fast_map = FastEntityMap::from_iter(entities.clone());
+            let cloned = fast_map.clone();
+            assert_eq!(fast_map, cloned);
+            if let Some((_, fewer)) = entities.split_last() {
+                assert_ne!(fast_map, FastEntityMap::from_iter(fewer.iter().copied()));
+            }
+            check_entity_map(entities, cloned);
+        }
+
+        #[test]
+        fn fast_map_builder(a in arb_entities_fast_map(), b in arb_entities_fast_map()) {
+            let mut builder = FastEntityMapBuilder::new();
+            let map = builder.build(a.iter().copied());
+            assert_eq!(FastEntityMap::from_iter(a.iter().copied()).nodes.len(), map.nodes.len());
+            check_entity_map(a.clone(), map.clone());
+            let ptr = builder.map.nodes.as_ptr();
+            let capacity = builder.map.nodes.capacity();
+            builder.clear();
+            assert_eq!(0, builder.map.size);
+            assert_eq!(capacity, builder.map.nodes.capacity());
+            let map = builder.build(b.iter().copied());
+            assert_eq!(FastEntityMap::from_iter(b.iter().copied()).nodes.len(), map.nodes.len());
+            assert_eq!(FastEntityMap::from_iter(b.iter().copied()), *map);
+            check_entity_map(b.clone(), map.clone());
+            if builder.map.nodes.len() <= capacity {
+                assert_eq!(ptr, builder.map.nodes.as_ptr());
+                assert_eq!(capacity, builder.map.nodes.capacity());
+            }
+        }
     }
 }
@@ -0,0 +1,121 @@
+use std::hash::{BuildHasherDefault, Hasher};
+
+////////////////////////////////////////// IdentityHasher //////////////////////////////////////////
+
+/// A [Hasher] that passes integers through unchanged.  Entities are frequently drawn uniformly at
+/// random, in which case they are already as well distributed as any hash would make them and
+/// SipHash is wasted work.  Do not use this for sequential or otherwise clustered entities.
+///
+/// A key that writes a single integer hashes to that integer (truncated to 64 bits, with the
+/// halves of a `u128` folded together).  Keys that write more than once, or write raw bytes, fold
+/// each write into the state so that they remain usable, if not as fast.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdentityHasher {
+    hash: u64,
+}
+
+impl IdentityHasher {
+    fn fold(&mut self, x: u64) {
+        self.hash = self.hash.rotate_left(29) ^ x;
+    }
+}
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut buf = [0u8; 8];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            self.fold(u64::from_le_bytes(buf));
+        }
+    }
+
+    fn write_u8(&mut self, x: u8) {
+        self.fold(x as u64);
+    }
+
+    fn write_u16(&mut self, x: u16) {
+        self.fold(x as u64);
+    }
+
+    fn write_u32(&mut self, x: u32) {
+        self.fold(x as u64);
+    }
+
+    fn write_u64(&mut self, x: u64) {
+        self.fold(x);
+    }
+
+    fn write_u128(&mut self, x: u128) {
+        self.fold((x >> 64) as u64 ^ x as u64);
+    }
+
+    fn write_usize(&mut self, x: usize) {
+        self.fold(x as u64);
+    }
+}
+
+/// A [std::hash::BuildHasher] for [IdentityHasher], e.g. `HashMap<E, V, BuildIdentityHasher>`.
+pub type BuildIdentityHasher = BuildHasherDefault<IdentityHasher>;
+
+/////////////////////////////////////////////// tests //////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::hash::{BuildHasher, Hash};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::{Entity, Ipv4Entity, Ipv6Entity};
+
+    fn check_lookups<E: Entity, S: BuildHasher + Default>(entities: &[E]) {
+        let mut map: HashMap<E, usize, S> = HashMap::default();
+        for (idx, e) in entities.iter().enumerate() {
+            map.insert(*e, idx);
+        }
+        for e in entities.iter() {
+            let idx = map[e];
+            assert_eq!(*e, entities[idx]);
+        }
+    }
+
+    fn hash_one<T: Hash>(t: T) -> u64 {
+        BuildIdentityHasher::default().hash_one(t)
+    }
+
+    #[test]
+    fn identity() {
+        assert_eq!(42, hash_one(42u32));
+        assert_eq!(u64::MAX, hash_one(u64::MAX));
+        assert_eq!(1 ^ 2, hash_one((1u128 << 64) | 2));
+        assert_ne!(
+            hash_one(Ipv4Entity(Ipv4Addr::new(10, 0, 0, 1))),
+            hash_one(Ipv4Entity(Ipv4Addr::new(10, 0, 0, 2)))
+        );
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn lookups_u64(entities in proptest::collection::vec(proptest::num::u64::ANY, 0..256)) {
+            check_lookups::<u64, std::collections::hash_map::RandomState>(&entities);
+            check_lookups::<u64, BuildIdentityHasher>(&entities);
+        }
+
+        #[test]
+        fn lookups_u128(entities in proptest::collection::vec(proptest::num::u128::ANY, 0..256)) {
+            check_lookups::<u128, std::collections::hash_map::RandomState>(&entities);
+            check_lookups::<u128, BuildIdentityHasher>(&entities);
+        }
+
+        #[test]
+        fn lookups_ipv6(entities in proptest::collection::vec(proptest::num::u128::ANY, 0..256)) {
+            let entities: Vec<Ipv6Entity> = entities.into_iter().map(|e| Ipv6Entity(Ipv6Addr::from(e))).collect();
+            check_lookups::<Ipv6Entity, std::collections::hash_map::RandomState>(&entities);
+            check_lookups::<Ipv6Entity, BuildIdentityHasher>(&entities);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::Entity;
+use crate::base64;
+
+//////////////////////////////////////////// Ipv4Entity ////////////////////////////////////////////
+
+/// An [Entity] keyed by IPv4 address.  Addresses order, increment, and decrement as their `u32`
+/// representation.  The wrapper exists because Ipv4Addr does not implement Default; the default
+/// entity is `0.0.0.0`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Ipv4Entity(pub Ipv4Addr);
+
+impl Default for Ipv4Entity {
+    fn default() -> Self {
+        Self(Ipv4Addr::UNSPECIFIED)
+    }
+}
+
+impl From<Ipv4Addr> for Ipv4Entity {
+    fn from(addr: Ipv4Addr) -> Self {
+        Self(addr)
+    }
+}
+
+impl From<Ipv4Entity> for Ipv4Addr {
+    fn from(entity: Ipv4Entity) -> Self {
+        entity.0
+    }
+}
+
+impl Entity for Ipv4Entity {
+    fn display(&self) -> String {
+        base64::encode(&self.0.octets())
+    }
+
+    fn from_display(display: &str) -> Option<Self> {
+        let bytes = base64::decode(display)?;
+        let octets: [u8; 4] = bytes.try_into().ok()?;
+        Some(Self(Ipv4Addr::from(octets)))
+    }
+
+    fn decrement(self) -> Self {
+        Self(Ipv4Addr::from(u32::from(self.0).wrapping_sub(1)))
+    }
+
+    fn increment(self) -> Self {
+        Self(Ipv4Addr::from(u32::from(self.0).wrapping_add(1)))
+    }
+
+    fn max_value() -> Self {
+        Self(Ipv4Addr::from(u32::MAX))
+    }
+}
+
+//////////////////////////////////////////// Ipv6Entity ////////////////////////////////////////////
+
+/// An [Entity] keyed by IPv6 address.  Addresses order, increment, and decrement as their `u128`
+/// representation.  The wrapper exists because Ipv6Addr does not implement Default; the default
+/// entity is `::`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Ipv6Entity(pub Ipv6Addr);
+
+impl Default for Ipv6Entity {
+    fn default() -> Self {
+        Self(Ipv6Addr::UNSPECIFIED)
+    }
+}
+
+impl From<Ipv6Addr> for Ipv6Entity {
+    fn from(addr: Ipv6Addr) -> Self {
+        Self(addr)
+    }
+}
+
+impl From<Ipv6Entity> for Ipv6Addr {
+    fn from(entity: Ipv6Entity) -> Self {
+        entity.0
+    }
+}
+
+impl Entity for Ipv6Entity {
+    fn display(&self) -> String {
+        base64::encode(&self.0.octets())
+    }
+
+    fn from_display(display: &str) -> Option<Self> {
+        let bytes = base64::decode(display)?;
+        let octets: [u8; 16] = bytes.try_into().ok()?;
+        Some(Self(Ipv6Addr::from(octets)))
+    }
+
+    fn decrement(self) -> Self {
+        Self(Ipv6Addr::from(u128::from(self.0).wrapping_sub(1)))
+    }
+
+    fn increment(self) -> Self {
+        Self(Ipv6Addr::from(u128::from(self.0).wrapping_add(1)))
+    }
+
+    fn max_value() -> Self {
+        Self(Ipv6Addr::from(u128::MAX))
+    }
+}
+
+/////////////////////////////////////////////// tests //////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn increment_across_octets() {
+        let addr = Ipv4Entity(Ipv4Addr::new(10, 0, 0, 255));
+        assert_eq!(Ipv4Entity(Ipv4Addr::new(10, 0, 1, 0)), addr.increment());
+        assert_eq!(addr, addr.increment().decrement());
+        assert_eq!(Ipv4Entity::default(), Ipv4Entity::max_value().increment());
+        let addr = Ipv6Entity("2001:db8::ffff".parse().unwrap());
+        assert_eq!(
+            Ipv6Entity("2001:db8::1:0".parse().unwrap()),
+            addr.increment()
+        );
+        assert_eq!(addr, addr.increment().decrement());
+        assert_eq!(Ipv6Entity::default(), Ipv6Entity::max_value().increment());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn ordering(x in proptest::num::u128::ANY, y in proptest::num::u128::ANY) {
+            let (x4, y4) = (Ipv4Entity(Ipv4Addr::from(x as u32)), Ipv4Entity(Ipv4Addr::from(y as u32)));
+            assert_eq!((x as u32).cmp(&(y as u32)), x4.cmp(&y4));
+            let (x6, y6) = (Ipv6Entity(Ipv6Addr::from(x)), Ipv6Entity(Ipv6Addr::from(y)));
+            assert_eq!(x.cmp(&y), x6.cmp(&y6));
+        }
+
+        #[test]
+        fn display_round_trip(x in proptest::num::u128::ANY) {
+            let x4 = Ipv4Entity(Ipv4Addr::from(x as u32));
+            assert_eq!(Some(x4), Ipv4Entity::from_display(&x4.display()));
+            let x6 = Ipv6Entity(Ipv6Addr::from(x));
+            assert_eq!(Some(x6), Ipv6Entity::from_display(&x6.display()));
+            assert_eq!(None, Ipv4Entity::from_display(&x6.display()));
+            assert_eq!(Some(x4), Ipv4Entity::from_hex(&x4.display_hex()));
+            assert_eq!(Some(x6), Ipv6Entity::from_hex(&x6.display_hex()));
+        }
+    }
+}
@@ -1,28 +1,101 @@
 use std::fmt::Debug;
 use std::hash::Hash;
 
-use crate::base64;
+use crate::{base64, hex};
 
 mod fast_map;
+mod identity_hash;
+mod ip;
+#[cfg(feature = "uuid-entity")]
+mod uuid_entity;
 mod vec_map;
 
-pub use fast_map::{FastEntityMap, FastEntityMapIntoIterator, FastEntityMapIterator};
+pub use fast_map::{
+    FastEntityMap, FastEntityMapBuilder, FastEntityMapIntoIterator, FastEntityMapIterator,
+};
+pub use identity_hash::{BuildIdentityHasher, IdentityHasher};
+pub use ip::{Ipv4Entity, Ipv6Entity};
 pub use vec_map::VecEntityMap;
 
 ////////////////////////////////////////////// Entity //////////////////////////////////////////////
 
 /// Entity is one part of the ECS triad.  It should be a Copy-able type that implements this trait.
 /// Entities are restricted because they are used as pointers in all other code.  Implementations
-/// of entity include u32, u64, and u128.
+/// of entity include u32, u64, u128, and the IP address wrappers [Ipv4Entity] and [Ipv6Entity].
 pub trait Entity: Copy + Default + Debug + Eq + Ord + Hash {
-    /// Convert the entity to a display-able value.
+    /// Convert the entity to a display-able value.  The integer entities display as unpadded
+    /// base64 of their little-endian bytes, using `+` and `/` for the last two symbols of the
+    /// alphabet.  Earlier releases rendered both of those symbols as a space, which could not be
+    /// decoded, so displays containing either differ from those of earlier releases.
     fn display(&self) -> String;
+    /// Convert the output of [Self::display] back to an entity.  Returns None if the string was
+    /// not produced by [Self::display].  The default returns None for every string, so entities
+    /// that want [Self::display] and [Self::from_hex] to round trip must override it.
+    fn from_display(display: &str) -> Option<Self> {
+        let _ = display;
+        None
+    }
+    /// Convert the entity to lowercase hex, which is easier to eyeball than [Self::display].  The
+    /// default writes the bytes that [Self::display] encodes as base64, which for the integer
+    /// entities are little-endian and fixed-width.  Where the display is not base64, the default
+    /// writes the display's own bytes instead.
+    fn display_hex(&self) -> String {
+        let display = self.display();
+        match base64::decode(&display) {
+            Some(bytes) => hex::encode(&bytes),
+            None => hex::encode(display.as_bytes()),
+        }
+    }
+    /// Convert the output of [Self::display_hex] back to an entity.  Returns None if the string
+    /// was not produced by [Self::display_hex].
+    fn from_hex(hex: &str) -> Option<Self> {
+        let bytes = hex::decode(hex)?;
+        Self::from_display(&base64::encode(&bytes))
+            .or_else(|| Self::from_display(std::str::from_utf8(&bytes).ok()?))
+    }
     /// Return the previous entity according to the total ordering of entities.
     fn decrement(self) -> Self;
     /// Return the next entity according to the total ordering of entities.
     fn increment(self) -> Self;
     /// Return the maximum entity possible.
     fn max_value() -> Self;
+
+    /// Return the number of entities in the sorted slice that are strictly less than entity.
+    /// Implementations may override this to provide a faster search than binary search.
+    fn partition_point(entities: &[Self], entity: Self) -> usize {
+        entities.partition_point(|e| *e < entity)
+    }
+
+    /// Return `count` entities, starting with `start` and each `step` increments after the last.
+    /// The sequence wraps around past [Self::max_value].  This is primarily a utility for tests
+    /// and benchmarks; it calls [Self::increment] `step` times per entity.
+    fn step_by(start: Self, step: u64, count: usize) -> Vec<Self> {
+        let mut entities = Vec::with_capacity(count);
+        let mut entity = start;
+        for idx in 0..count {
+            if idx > 0 {
+                for _ in 0..step {
+                    entity = entity.increment();
+                }
+            }
+            entities.push(entity);
+        }
+        entities
+    }
+
+    /// Iterate every entity from `lo` to `hi` inclusive, in order.  Empty if `lo > hi`.
+    fn range_inclusive(lo: Self, hi: Self) -> impl Iterator<Item = Self> {
+        let mut next = if lo <= hi { Some(lo) } else { None };
+        std::iter::from_fn(move || {
+            let entity = next?;
+            next = if entity < hi {
+                Some(entity.increment())
+            } else {
+                None
+            };
+            Some(entity)
+        })
+    }
 }
 
 impl Entity for u32 {
@@ -31,6 +104,11 @@ impl Entity for u32 {
         base64::encode(&bytes)
     }
 
+    fn from_display(display: &str) -> Option<Self> {
+        let bytes = base64::decode(display)?;
+        Some(Self::from_le_bytes(bytes.try_into().ok()?))
+    }
+
     fn decrement(self) -> Self {
         self.wrapping_sub(1)
     }
@@ -42,6 +120,10 @@ impl Entity for u32 {
     fn max_value() -> Self {
         Self::MAX
     }
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+    fn partition_point(entities: &[Self], entity: Self) -> usize {
+        vec_map::avx2::partition_point_u32(entities, entity)
+    }
 }
 
 impl Entity for u64 {
@@ -50,6 +132,11 @@ impl Entity for u64 {
         base64::encode(&bytes)
     }
 
+    fn from_display(display: &str) -> Option<Self> {
+        let bytes = base64::decode(display)?;
+        Some(Self::from_le_bytes(bytes.try_into().ok()?))
+    }
+
     fn decrement(self) -> Self {
         self.wrapping_sub(1)
     }
@@ -61,6 +148,10 @@ impl Entity for u64 {
     fn max_value() -> Self {
         Self::MAX
     }
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+    fn partition_point(entities: &[Self], entity: Self) -> usize {
+        vec_map::avx2::partition_point_u64(entities, entity)
+    }
 }
 
 impl Entity for u128 {
@@ -69,6 +160,11 @@ impl Entity for u128 {
         base64::encode(&bytes)
     }
 
+    fn from_display(display: &str) -> Option<Self> {
+        let bytes = base64::decode(display)?;
+        Some(Self::from_le_bytes(bytes.try_into().ok()?))
+    }
+
     fn decrement(self) -> Self {
         self.wrapping_sub(1)
     }
@@ -111,6 +207,68 @@ pub trait EntityMap<E: Entity>: Debug + IntoIterator<Item = E> + FromIterator<E>
     fn lower_bound(&self, entity: E) -> Option<E>;
     /// Iterate over all entities in the map.
     fn iter(&self) -> Self::Iter<'_>;
+
+    /// Construct the map from a vector that is already sorted and free of duplicates, reusing the
+    /// vector's allocation where the implementation allows.
+    fn from_sorted_vec(entities: Vec<E>) -> Self {
+        Self::from_iter(entities)
+    }
+
+    /// Merge two maps into one holding the union of their entities in a single pass over each,
+    /// rather than concatenating and sorting.
+    fn merge_sorted(a: Self, b: Self) -> Self {
+        let mut merged = Vec::with_capacity(a.len() + b.len());
+        let mut a = a.into_iter().peekable();
+        let mut b = b.into_iter().peekable();
+        loop {
+            let next = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) if x < y => a.next(),
+                (Some(x), Some(y)) if x > y => b.next(),
+                (Some(_), Some(_)) => {
+                    b.next();
+                    a.next()
+                }
+                (Some(_), None) => a.next(),
+                (None, Some(_)) => b.next(),
+                (None, None) => break,
+            };
+            // SAFETY(rescrv):  Every arm above peeked Some before taking next.
+            merged.push(next.unwrap());
+        }
+        Self::from_sorted_vec(merged)
+    }
+
+    /// Iterate the half-open ranges `[start, end)` of entities not in the map, in order, from the
+    /// default entity up to [Entity::max_value].  Because the ranges are half open, a trailing
+    /// gap is reported as ending at `max_value`; `max_value` itself is free exactly when the map
+    /// does not hold it.
+    fn gaps<'a>(&'a self) -> impl Iterator<Item = (E, E)> + 'a
+    where
+        E: 'a,
+    {
+        let mut start = Some(E::default());
+        let mut entities = self.iter();
+        std::iter::from_fn(move || loop {
+            let gap_start = start?;
+            let Some(entity) = entities.next() else {
+                start = None;
+                return if gap_start < E::max_value() {
+                    Some((gap_start, E::max_value()))
+                } else {
+                    None
+                };
+            };
+            // NOTE(rescrv):  Incrementing the maximum entity wraps around to the default entity.
+            start = if entity == E::max_value() {
+                None
+            } else {
+                Some(entity.increment())
+            };
+            if gap_start < entity {
+                return Some((gap_start, entity));
+            }
+        })
+    }
 }
 
 /////////////////////////////////////////////// tests //////////////////////////////////////////////
@@ -119,19 +277,162 @@ pub trait EntityMap<E: Entity>: Debug + IntoIterator<Item = E> + FromIterator<E>
 mod tests {
     use super::*;
 
+    proptest::proptest! {
+        #[test]
+        fn display_round_trip(x in proptest::num::u128::ANY) {
+            assert_eq!(Some(x), u128::from_display(&x.display()));
+            assert_eq!(Some(x as u64), u64::from_display(&(x as u64).display()));
+            assert_eq!(Some(x as u32), u32::from_display(&(x as u32).display()));
+            assert_eq!(None, u64::from_display(&x.display()));
+        }
+
+        #[test]
+        fn hex_round_trip(x in proptest::num::u128::ANY) {
+            assert_eq!(Some(x), u128::from_hex(&x.display_hex()));
+            assert_eq!(Some(x as u64), u64::from_hex(&(x as u64).display_hex()));
+            assert_eq!(Some(x as u32), u32::from_hex(&(x as u32).display_hex()));
+            assert_eq!(None, u64::from_hex(&x.display_hex()));
+        }
+    }
+
+    #[test]
+    fn from_display_default() {
+        #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        struct Opaque(u8);
+
+        impl Entity for Opaque {
+            fn display(&self) -> String {
+                format!("opaque-{}", self.0)
+            }
+
+            fn decrement(self) -> Self {
+                Self(self.0.wrapping_sub(1))
+            }
+
+            fn increment(self) -> Self {
+                Self(self.0.wrapping_add(1))
+            }
+
+            fn max_value() -> Self {
+                Self(u8::MAX)
+            }
+        }
+
+        assert_eq!(None, Opaque::from_display(&Opaque(7).display()));
+        assert_eq!("6f70617175652d37", Opaque(7).display_hex());
+        assert_eq!(None, Opaque::from_hex(&Opaque(7).display_hex()));
+    }
+
+    #[test]
+    fn display_hex() {
+        assert_eq!("34120000", 0x1234u32.display_hex());
+        assert_eq!(16, 0x1234u64.display_hex().len());
+        assert_eq!(32, 0x1234u128.display_hex().len());
+        assert_eq!(Some(0x1234u32), u32::from_hex("34120000"));
+        assert_eq!(None, u32::from_hex("3412000"));
+        assert_eq!(None, u32::from_hex("3412000A"));
+    }
+
+    #[test]
+    fn step_by() {
+        let expected: Vec<u128> = (0..1000).map(|i| i * 8).collect();
+        assert_eq!(expected, u128::step_by(0, 8, 1000));
+        assert_eq!(vec![u32::MAX - 1, 0, 2], u32::step_by(u32::MAX - 1, 2, 3));
+        assert_eq!(vec![7u64, 7, 7], u64::step_by(7, 0, 3));
+        assert!(u64::step_by(7, 1, 0).is_empty());
+    }
+
+    #[test]
+    fn range_inclusive() {
+        assert_eq!(
+            vec![5u64, 6, 7],
+            u64::range_inclusive(5, 7).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![9u64], u64::range_inclusive(9, 9).collect::<Vec<_>>());
+        assert_eq!(0, u64::range_inclusive(9, 8).count());
+        assert_eq!(
+            vec![u32::MAX - 1, u32::MAX],
+            u32::range_inclusive(u32::MAX - 1, u32::MAX).collect::<Vec<_>>()
+        );
+    }
+
+    /// Check that merging the maps built from `a` and `b` yields the sorted union of the two.
+    pub fn check_merge_sorted<EM: EntityMap<u128>>(a: Vec<u128>, b: Vec<u128>) {
+        // NOTE(rescrv):  Random entities rarely collide, so share every other entity of a with b.
+        let mut b: Vec<u128> = b.into_iter().chain(a.iter().step_by(2).copied()).collect();
+        b.sort();
+        b.dedup();
+        let mut union: Vec<u128> = a.iter().chain(b.iter()).copied().collect();
+        union.sort();
+        union.dedup();
+        let merged = EM::merge_sorted(EM::from_iter(a), EM::from_iter(b));
+        check_entity_map(union, merged);
+    }
+
+    /// Check that the gaps and the entities of the map, taken in order, tile the entity space.
+    pub fn check_gaps<E: Entity, EM: EntityMap<E>>(entities: &[E], map: &EM) {
+        let mut gaps = map.gaps();
+        let mut cursor = Some(E::default());
+        for entity in entities.iter().copied() {
+            // SAFETY(rescrv):  Only the maximum entity ends the space, and it sorts last.
+            let expected = cursor.unwrap();
+            if expected < entity {
+                assert_eq!(Some((expected, entity)), gaps.next());
+            }
+            cursor = (entity != E::max_value()).then(|| entity.increment());
+        }
+        if let Some(expected) = cursor.filter(|c| *c < E::max_value()) {
+            assert_eq!(Some((expected, E::max_value())), gaps.next());
+        }
+        assert_eq!(None, gaps.next());
+    }
+
+    #[test]
+    fn gaps() {
+        let map = VecEntityMap::from_iter([0u32, 1, 5, 6, 9, u32::MAX]);
+        assert_eq!(
+            vec![(2, 5), (7, 9), (10, u32::MAX)],
+            map.gaps().collect::<Vec<_>>()
+        );
+        let map = VecEntityMap::from_iter([3u32, 4]);
+        assert_eq!(vec![(0, 3), (5, u32::MAX)], map.gaps().collect::<Vec<_>>());
+        let map = VecEntityMap::<u64>::from_iter([]);
+        assert_eq!(vec![(0, u64::MAX)], map.gaps().collect::<Vec<_>>());
+        let map = VecEntityMap::from_iter([u64::MAX - 1]);
+        assert_eq!(vec![(0, u64::MAX - 1)], map.gaps().collect::<Vec<_>>());
+        // Over a small space, the gaps and the entities reconstruct every entity in order.
+        let present = [0u32, 2, 3, 7, 8, 9, 12];
+        let map = FastEntityMap::from_iter(present);
+        let mut space: Vec<u32> = map.iter().collect();
+        for (start, end) in map.gaps() {
+            space.extend((start..end).take_while(|e| *e < 16));
+        }
+        space.sort();
+        assert_eq!((0..16).collect::<Vec<u32>>(), space);
+    }
+
     pub fn check_entity_map<E: Entity, EM: EntityMap<E>>(entities: Vec<E>, map: EM) {
+        check_gaps(&entities, &map);
         assert_eq!(entities.is_empty(), map.is_empty());
         assert_eq!(entities.len(), map.len());
         for (idx, (lhs, rhs)) in std::iter::zip(map.iter(), entities.iter()).enumerate() {
             assert_eq!(lhs, *rhs);
             assert_eq!(lhs, map.get(idx));
             assert_eq!(idx, map.offset_of(lhs));
+            assert_eq!(Some(idx), map.exact_offset_of(lhs));
             assert_eq!(Some(lhs), map.lower_bound(lhs));
             if idx > 0 && entities[idx - 1].increment() != entities[idx] {
                 assert_eq!(idx, map.offset_of(lhs.decrement()));
+                assert_eq!(None, map.exact_offset_of(lhs.decrement()));
                 assert_eq!(Some(lhs), map.lower_bound(lhs.decrement()));
             }
         }
+        if let Some(last) = entities.last() {
+            if *last != E::max_value() {
+                assert_eq!(None, map.exact_offset_of(last.increment()));
+                assert_eq!(None, map.lower_bound(last.increment()));
+            }
+        }
         for (expected, returned) in std::iter::zip(entities.iter(), map.into_iter()) {
             assert_eq!(*expected, returned);
         }
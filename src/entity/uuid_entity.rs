@@ -0,0 +1,99 @@
+use uuid::Uuid;
+
+use super::Entity;
+
+/////////////////////////////////////////////// Uuid ///////////////////////////////////////////////
+
+/// UUIDs order, increment, and decrement as their big-endian `u128` representation, which agrees
+/// with the byte-wise ordering of Uuid.  The default entity is the nil UUID and the maximum is the
+/// max UUID.  Entities display in the standard hyphenated format.
+impl Entity for Uuid {
+    fn display(&self) -> String {
+        self.hyphenated().to_string()
+    }
+
+    fn from_display(display: &str) -> Option<Self> {
+        let uuid = Uuid::try_parse(display).ok()?;
+        if uuid.display() == display {
+            Some(uuid)
+        } else {
+            None
+        }
+    }
+
+    fn display_hex(&self) -> String {
+        self.simple().to_string()
+    }
+
+    fn from_hex(hex: &str) -> Option<Self> {
+        let uuid = Uuid::try_parse(hex).ok()?;
+        if uuid.display_hex() == hex {
+            Some(uuid)
+        } else {
+            None
+        }
+    }
+
+    fn decrement(self) -> Self {
+        Uuid::from_u128(self.as_u128().wrapping_sub(1))
+    }
+
+    fn increment(self) -> Self {
+        Uuid::from_u128(self.as_u128().wrapping_add(1))
+    }
+
+    fn max_value() -> Self {
+        Uuid::max()
+    }
+}
+
+/////////////////////////////////////////////// tests //////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extremes() {
+        assert_eq!(Uuid::nil(), Uuid::default());
+        assert_eq!(Uuid::from_u128(u128::MAX), Uuid::max_value());
+        assert_eq!(Uuid::nil(), Uuid::max_value().increment());
+        assert_eq!(Uuid::max_value(), Uuid::nil().decrement());
+        let uuid = Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0ff").unwrap();
+        assert_eq!(
+            Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe100").unwrap(),
+            uuid.increment()
+        );
+        assert_eq!("67e55044-10b1-426f-9247-bb680e5fe0ff", uuid.display());
+        assert_eq!(None, Uuid::from_display("67e5504410b1426f9247bb680e5fe0ff"));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn ordering(x in proptest::num::u128::ANY, y in proptest::num::u128::ANY) {
+            let (ux, uy) = (Uuid::from_u128(x), Uuid::from_u128(y));
+            assert_eq!(x.cmp(&y), ux.cmp(&uy));
+            if ux < Uuid::max_value() {
+                assert!(ux < ux.increment());
+            }
+            if ux > Uuid::default() {
+                assert!(ux.decrement() < ux);
+            }
+        }
+
+        #[test]
+        fn increment_decrement_round_trip(x in proptest::num::u128::ANY) {
+            let ux = Uuid::from_u128(x);
+            assert_eq!(ux, ux.increment().decrement());
+            assert_eq!(ux, ux.decrement().increment());
+        }
+
+        #[test]
+        fn display_round_trip(x in proptest::num::u128::ANY) {
+            let ux = Uuid::from_u128(x);
+            assert_eq!(Some(ux), Uuid::from_display(&ux.display()));
+            assert_eq!(Some(ux), Uuid::from_hex(&ux.display_hex()));
+            assert_eq!(None, Uuid::from_hex(&ux.display()));
+        }
+    }
+}
@@ -3,11 +3,98 @@ use super::{Entity, EntityMap};
 /////////////////////////////////////////// VecEntityMap ///////////////////////////////////////////
 
 /// VecEntityMap uses binary search over a vector of entities.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct VecEntityMap<E: Entity> {
     entities: Vec<E>,
 }
 
+impl<E: Entity> VecEntityMap<E> {
+    /// The number of entities the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.entities.capacity()
+    }
+
+    /// Reserve room for at least `additional` more entities.
+    pub fn reserve(&mut self, additional: usize) {
+        self.entities.reserve(additional);
+    }
+
+    /// The sorted entities of the map, with the capacity of the map.
+    pub(crate) fn into_vec(self) -> Vec<E> {
+        self.entities
+    }
+
+    /// Split the map at offset, leaving `[0, at)` in self and returning `[at, len)`.
+    ///
+    /// # Panics
+    ///
+    /// If at > self.len().
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let entities = self.entities.split_off(at);
+        Self { entities }
+    }
+
+    /// Insert `entity` at `offset`, which must be where it sorts.
+    pub(crate) fn insert(&mut self, offset: usize, entity: E) {
+        debug_assert!(offset == 0 || self.entities[offset - 1] < entity);
+        debug_assert!(offset == self.entities.len() || entity < self.entities[offset]);
+        self.entities.insert(offset, entity);
+    }
+
+    /// Move the entities of `other`, which must all sort after those of self, onto the end.
+    pub(crate) fn append(&mut self, mut other: Self) {
+        debug_assert!(
+            self.entities.is_empty()
+                || other.entities.is_empty()
+                || self.entities[self.entities.len() - 1] < other.entities[0]
+        );
+        self.entities.append(&mut other.entities);
+    }
+
+    /// Remove and return the entity at `offset`.
+    pub(crate) fn remove(&mut self, offset: usize) -> E {
+        self.entities.remove(offset)
+    }
+
+    /// Build a map from entities in any order, sorting them and dropping duplicates first.
+    pub fn from_unsorted_iter<I: IntoIterator<Item = E>>(entities: I) -> Self {
+        let mut entities: Vec<E> = entities.into_iter().collect();
+        entities.sort_unstable();
+        entities.dedup();
+        Self { entities }
+    }
+
+    /// The smallest entity greater than `after` that is not in the map.  Binary search finds
+    /// `after`'s successor, then the scan runs forward through consecutive entities.
+    pub(crate) fn next_free(&self, after: E) -> Option<E> {
+        if after == E::max_value() {
+            return None;
+        }
+        let mut candidate = after.increment();
+        for entity in self.entities[self.offset_of(candidate)..].iter() {
+            if *entity != candidate {
+                break;
+            }
+            if candidate == E::max_value() {
+                return None;
+            }
+            candidate = candidate.increment();
+        }
+        Some(candidate)
+    }
+
+    /// The entities as a sorted, duplicate-free slice.
+    pub fn as_slice(&self) -> &[E] {
+        &self.entities
+    }
+}
+
+impl<E: Entity> AsRef<[E]> for VecEntityMap<E> {
+    fn as_ref(&self) -> &[E] {
+        self.as_slice()
+    }
+}
+
 impl<E: Entity> EntityMap<E> for VecEntityMap<E> {
     type Iter<'a> = std::iter::Copied<std::slice::Iter<'a, E>> where Self: 'a;
 
@@ -24,12 +111,12 @@ impl<E: Entity> EntityMap<E> for VecEntityMap<E> {
     }
 
     fn offset_of(&self, entity: E) -> usize {
-        self.entities.partition_point(|e| *e < entity)
+        E::partition_point(&self.entities, entity)
     }
 
     fn exact_offset_of(&self, entity: E) -> Option<usize> {
-        let offset = self.entities.partition_point(|e| *e < entity);
-        if self.entities[offset] == entity {
+        let offset = self.offset_of(entity);
+        if offset < self.entities.len() && self.entities[offset] == entity {
             Some(offset)
         } else {
             None
@@ -48,6 +135,11 @@ impl<E: Entity> EntityMap<E> for VecEntityMap<E> {
     fn iter(&self) -> Self::Iter<'_> {
         self.entities.iter().copied()
     }
+
+    fn from_sorted_vec(entities: Vec<E>) -> Self {
+        debug_assert!(entities.windows(2).all(|w| w[0] < w[1]));
+        Self { entities }
+    }
 }
 
 impl<E: Entity> IntoIterator for VecEntityMap<E> {
@@ -59,13 +151,87 @@ impl<E: Entity> IntoIterator for VecEntityMap<E> {
     }
 }
 
+/// The entities must arrive sorted and free of duplicates; use [VecEntityMap::from_unsorted_iter]
+/// otherwise.  Debug builds check this and panic.
 impl<E: Entity> FromIterator<E> for VecEntityMap<E> {
     fn from_iter<I: IntoIterator<Item = E>>(entities: I) -> Self {
         let entities: Vec<E> = entities.into_iter().collect();
+        debug_assert!(
+            entities.windows(2).all(|w| w[0] < w[1]),
+            "VecEntityMap requires sorted, deduplicated input"
+        );
         Self { entities }
     }
 }
 
+/////////////////////////////////////////////// avx2 ///////////////////////////////////////////////
+
+/// AVX2 implementations of [Entity::partition_point] for the entity types that fit in a vector
+/// lane.  Binary search narrows the slice down to a small window and then the entities in the
+/// window get compared against the target several-at-a-time.
+#[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+pub(crate) mod avx2 {
+    use std::arch::x86_64::*;
+
+    const WINDOW: usize = 32;
+
+    fn narrow<E: Copy + Ord>(entities: &[E], entity: E) -> (usize, usize) {
+        let mut lo = 0;
+        let mut hi = entities.len();
+        while hi - lo > WINDOW {
+            let mid = lo + (hi - lo) / 2;
+            if entities[mid] < entity {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        (lo, hi)
+    }
+
+    pub fn partition_point_u64(entities: &[u64], entity: u64) -> usize {
+        let (lo, hi) = narrow(entities, entity);
+        let mut chunks = entities[lo..hi].chunks_exact(4);
+        let mut count = 0;
+        // SAFETY(rescrv):  This module is only compiled when avx2 is enabled and every load reads
+        // exactly four u64 from a chunk of four u64.
+        unsafe {
+            // Flip the sign bit so that the signed comparison orders like an unsigned one.
+            let bias = _mm256_set1_epi64x(i64::MIN);
+            let needle = _mm256_xor_si256(_mm256_set1_epi64x(entity as i64), bias);
+            for chunk in &mut chunks {
+                let haystack = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+                let haystack = _mm256_xor_si256(haystack, bias);
+                let less = _mm256_cmpgt_epi64(needle, haystack);
+                count += _mm256_movemask_pd(_mm256_castsi256_pd(less)).count_ones() as usize;
+            }
+        }
+        count += chunks.remainder().iter().filter(|e| **e < entity).count();
+        lo + count
+    }
+
+    pub fn partition_point_u32(entities: &[u32], entity: u32) -> usize {
+        let (lo, hi) = narrow(entities, entity);
+        let mut chunks = entities[lo..hi].chunks_exact(8);
+        let mut count = 0;
+        // SAFETY(rescrv):  This module is only compiled when avx2 is enabled and every load reads
+        // exactly eight u32 from a chunk of eight u32.
+        unsafe {
+            // Flip the sign bit so that the signed comparison orders like an unsigned one.
+            let bias = _mm256_set1_epi32(i32::MIN);
+            let needle = _mm256_xor_si256(_mm256_set1_epi32(entity as i32), bias);
+            for chunk in &mut chunks {
+                let haystack = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+                let haystack = _mm256_xor_si256(haystack, bias);
+                let less = _mm256_cmpgt_epi32(needle, haystack);
+                count += _mm256_movemask_ps(_mm256_castsi256_ps(less)).count_ones() as usize;
+            }
+        }
+        count += chunks.remainder().iter().filter(|e| **e < entity).count();
+        lo + count
+    }
+}
+
 /////////////////////////////////////////////// tests //////////////////////////////////////////////
 
 #[cfg(test)]
@@ -74,16 +240,24 @@ mod tests {
 
     use proptest::strategy::Strategy;
 
-    use super::super::tests::check_entity_map;
+    use super::super::tests::{check_entity_map, check_merge_sorted};
     use super::*;
 
-    use crate::tests::{arb_entity, is_free_of_duplicates};
+    use crate::tests::{arb_entity, is_free_of_duplicates, with_extremes};
 
     proptest::prop_compose! {
-        fn arb_entities_vec_map()(mut entities in proptest::collection::vec(arb_entity(), 0..(15 * 15 * 15)).prop_filter("dedupe", is_free_of_duplicates)) -> Vec<u128> {
+        fn arb_entities_vec_map()(mut entities in proptest::collection::vec(arb_entity(), 0..(15 * 15 * 15)).prop_filter("dedupe", is_free_of_duplicates), default in proptest::bool::ANY, max in proptest::bool::ANY) -> Vec<u128> {
             entities.sort();
             entities.dedup();
-            entities
+            with_extremes(entities, default, max)
+        }
+    }
+
+    proptest::prop_compose! {
+        fn arb_partition_point()(mut entities in proptest::collection::vec(proptest::num::u64::ANY, 0..4096), queries in proptest::collection::vec(proptest::num::u64::ANY, 0..64)) -> (Vec<u64>, Vec<u64>) {
+            entities.sort();
+            entities.dedup();
+            (entities, queries)
         }
     }
 
@@ -93,5 +267,80 @@ mod tests {
             let vec_map = VecEntityMap::from_iter(entities.clone().into_iter());
             check_entity_map(entities, vec_map);
         }
+
+        #[test]
+        fn vec_map_merge_sorted(a in arb_entities_vec_map(), b in arb_entities_vec_map()) {
+            check_merge_sorted::<VecEntityMap<u128>>(a, b);
+        }
+
+        #[test]
+        fn vec_map_from_sorted_vec(entities in arb_entities_vec_map()) {
+            let vec_map = VecEntityMap::from_sorted_vec(entities.clone());
+            let from_iter = VecEntityMap::from_iter(entities.clone());
+            assert!(std::iter::zip(vec_map.iter(), from_iter.iter()).all(|(lhs, rhs)| lhs == rhs));
+            assert_eq!(from_iter, vec_map);
+            check_entity_map(entities, vec_map);
+        }
+
+        #[test]
+        fn vec_map_as_slice(entities in arb_entities_vec_map()) {
+            let vec_map = VecEntityMap::from_iter(entities.clone());
+            assert_eq!(vec_map.iter().collect::<Vec<_>>(), vec_map.as_slice());
+            assert!(vec_map.as_slice().windows(2).all(|w| w[0] < w[1]));
+            let slice: &[u128] = vec_map.as_ref();
+            assert_eq!(&entities[..], slice);
+        }
+
+        #[test]
+        fn vec_map_clone_eq(entities in arb_entities_vec_map()) {
+            let vec_map = VecEntityMap::from_iter(entities.clone());
+            let cloned = vec_map.clone();
+            assert_eq!(vec_map, cloned);
+            if let Some((_, fewer)) = entities.split_last() {
+                assert_ne!(vec_map, VecEntityMap::from_iter(fewer.iter().copied()));
+            }
+            check_entity_map(entities, cloned);
+        }
+
+        #[test]
+        fn vec_map_from_unsorted_iter(entities in arb_entities_vec_map()) {
+            let mut shuffled = entities.clone();
+            shuffled.reverse();
+            shuffled.extend(entities.iter().step_by(3).copied());
+            let vec_map = VecEntityMap::from_unsorted_iter(shuffled);
+            check_entity_map(entities, vec_map);
+        }
+
+        #[test]
+        fn partition_point(input in arb_partition_point()) {
+            let (entities, mut queries) = input;
+            queries.extend(entities.iter().copied());
+            queries.extend(entities.iter().map(|e| e.increment()));
+            queries.extend([u64::MIN, u64::MAX]);
+            let mut entities32: Vec<u32> = entities.iter().map(|e| *e as u32).collect();
+            entities32.sort();
+            entities32.dedup();
+            for query in queries.into_iter() {
+                let expected = entities.partition_point(|e| *e < query);
+                assert_eq!(expected, u64::partition_point(&entities, query));
+                let query = query as u32;
+                let expected = entities32.partition_point(|e| *e < query);
+                assert_eq!(expected, u32::partition_point(&entities32, query));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "VecEntityMap requires sorted, deduplicated input")]
+    fn from_iter_unsorted() {
+        VecEntityMap::from_iter([3u64, 1, 2]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "VecEntityMap requires sorted, deduplicated input")]
+    fn from_iter_duplicates() {
+        VecEntityMap::from_iter([1u64, 2, 2]);
     }
 }
@@ -0,0 +1,54 @@
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+fn decode_one(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        _ => None,
+    }
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        encoded.push(HEX[(b >> 4) as usize] as char);
+        encoded.push(HEX[(b & 0x0f) as usize] as char);
+    }
+    encoded
+}
+
+/// Decode the output of [encode].  Returns None if the string has an odd length or contains
+/// anything but lowercase hex digits.
+pub fn decode(encoded: &str) -> Option<Vec<u8>> {
+    let pairs = encoded.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    pairs
+        .map(|pair| Some((decode_one(pair[0])? << 4) | decode_one(pair[1])?))
+        .collect()
+}
+
+/////////////////////////////////////////////// tests //////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest::proptest! {
+        #[test]
+        fn round_trip(bytes in proptest::collection::vec(proptest::num::u8::ANY, 0..=32)) {
+            assert_eq!(Some(bytes.clone()), decode(&encode(&bytes)));
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(Some(vec![]), decode(""));
+        assert_eq!(None, decode("0"));
+        assert_eq!(None, decode("0g"));
+        assert_eq!(None, decode("0A"));
+        assert_eq!(Some(vec![0x0a]), decode("0a"));
+        assert_eq!("00ff10", encode(&[0, 255, 16]));
+    }
+}
@@ -3,22 +3,46 @@
 mod base64;
 mod component;
 mod entity;
+mod hex;
 mod partitioning;
 mod thread_pool;
 
 pub use component::{
-    ComponentChange, ComponentCollection, ComponentRef, CopyOnWriteComponentCollection,
-    CopyOnWriteComponentRef, InsertOptimizedComponentCollection, InsertOptimizedComponentRef,
-    MutableComponentCollection, MutableComponentRef,
+    sort_changes, ComponentChange, ComponentCollection, ComponentRef,
+    CopyOnWriteComponentCollection, CopyOnWriteComponentRef, InsertOptimizedComponentCollection,
+    InsertOptimizedComponentDrainIter, InsertOptimizedComponentRef, MutableComponentCollection,
+    MutableComponentRef, OrderedComponentCollection, OrderedComponentRef, SortedByComponent,
+    SupportsDirectMutation,
 };
 pub use entity::{
-    Entity, EntityMap, FastEntityMap, FastEntityMapIntoIterator, FastEntityMapIterator,
+    BuildIdentityHasher, Entity, EntityMap, FastEntityMap, FastEntityMapBuilder,
+    FastEntityMapIntoIterator, FastEntityMapIterator, IdentityHasher, Ipv4Entity, Ipv6Entity,
     VecEntityMap,
 };
+#[cfg(feature = "serde")]
+pub use partitioning::CheckpointError;
 pub use partitioning::{
-    NopPartitioningScheme, Partitioned, PartitioningScheme, VecPartitioningScheme,
+    parallel_over, ApplyError, NopPartitioningScheme, Partitioned, PartitionedApplyHandle,
+    PartitionedChanges, PartitionedIter, PartitioningScheme, ValidationError,
+    VecPartitioningScheme, WeightedPartitioningScheme,
 };
-pub use thread_pool::{ThreadPool, WorkUnit};
+pub use thread_pool::{ThreadPool, ThreadPoolScope, WorkUnit};
+
+///////////////////////////////////////// check_collection /////////////////////////////////////////
+
+/// Emit a compile error if the identifier is not a collection type provided by this crate.  Used
+/// by [system] and [system_parallel] to give a clear error for typos in the collection type.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! check_collection {
+    (CopyOnWriteComponentCollection) => {};
+    (MutableComponentCollection) => {};
+    (InsertOptimizedComponentCollection) => {};
+    (OrderedComponentCollection) => {};
+    ($collection:ident) => {
+        compile_error!(concat!("Unknown collection type: ", stringify!($collection)));
+    };
+}
 
 ////////////////////////////////////////////// system //////////////////////////////////////////////
 
@@ -26,60 +50,260 @@ pub use thread_pool::{ThreadPool, WorkUnit};
 /// that are component collections and return a tuple of vectors of changes for each component
 /// collection.  It is up to the user to subsequently pass this state to the `apply` method of the
 /// component collections.
+///
+/// A `run_until` method is generated alongside `run`.  It takes a closure that gets called with
+/// each entity after it is processed; returning `ControlFlow::Break` stops the run early with the
+/// changes gathered so far.
+///
+/// To spread a run across several ticks, `run_limited` processes at most `max_entities` entities
+/// and returns, after the changes, the entity to resume from.  Pass it to `run_resumed` on the
+/// next tick, and so on until it comes back `None`.  Resuming may come back with no changes and
+/// `None` when the previous call happened to stop on the last entity.
+///
+/// A `preview_entities` method is generated as well.  It walks the same entities `run` would,
+/// returning them without calling `process`, so tests can assert which entities a system touches.
+///
+/// An arg that sees no changes gets back an empty vector that has not allocated, so read-mostly
+/// systems pay nothing for the collections they only read.
+///
+/// For a system run every frame, `run_into` takes a tuple of caller-owned buffers, one per arg,
+/// in place of returning new vectors.  It clears the buffers and refills them in entity order, so
+/// they come back sorted and ready for `apply`.  Once the buffers have grown to fit a run, later
+/// runs reuse their capacity and don't allocate:
+///
+/// ```ignore
+/// let mut bufs = (vec![], vec![]);
+/// loop {
+///     MySystem.run_into(&mut bufs, &mut a, &mut b);
+///     a.apply(bufs.0.drain(..));
+///     b.apply(bufs.1.drain(..));
+/// }
+/// ```
+///
+/// Systems may be generic.  Generic parameters take at most one bound inline; further bounds go
+/// in a where clause between the entity type and the collections:
+///
+/// ```ignore
+/// system! {
+///     Damage<R: Rng><u128> where R: Send {
+///         health: CopyOnWriteComponentCollection<u64>,
+///     }
+/// }
+/// ```
+///
+/// The component types may not refer to the system's generic parameters.
+///
+/// To skip entities without calling `process`, give a filter ahead of the system.  The filter
+/// gets the entity and a reference to each component, in the order of the collections, and the
+/// entity is processed only if it returns true.  Skipped entities produce no changes, and
+/// `run_until`'s closure is not called for them, so they don't count against `run_limited`.  The
+/// filter is called with the refs that `process` would get, so it saves the call to `process` and
+/// the gathering of changes, not the construction of the refs:
+///
+/// ```ignore
+/// system! {
+///     #[filter = |_, health| *health > 0]
+///     Regenerate<u128> {
+///         health: CopyOnWriteComponentCollection<u64>,
+///     }
+/// }
+/// ```
+///
+/// The collection types must be one of the collections provided by this crate:
+///
+/// ```compile_fail
+/// use tnaps::{system, ComponentChange, ComponentCollection, CopyOnWriteComponentRef, Entity};
+///
+/// struct MySystem;
+///
+/// system! {
+///     MySystem<u128> {
+///         a: CopyOnwriteComponentCollection<u8>,
+///     }
+/// }
+/// ```
 #[macro_export]
 macro_rules! system {
     ($system:ident <$entity:ty> {}) => {
         compile_error!("A system operates on 1 or more component collections.  Found: 0.");
     };
 
-    ($system:ident <$entity:ty> { $($arg:ident: $collection:ident <$t:ty>,)+ }) => {
-        impl $system {
-            fn run(&self, $($arg: &mut $crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+) {
-                #[derive(Default)]
-                struct Results {
-                    $($arg: Vec<($entity, ComponentChange<$t>)>,)+
+    ($(#[filter = $filter:expr])? $system:ident <$entity:ty> { $($arg:ident: $collection:ident <$t:ty>,)+ }) => {
+        $crate::system! {
+            @impl $system [] <$entity> [] [] [$($filter)?] { $($arg: $collection<$t>,)+ }
+        }
+    };
+
+    ($(#[filter = $filter:expr])? $system:ident <$($gen:ident $(: $bound:path)?),+> <$entity:ty> $(where $($wty:ty: $wb:path),+ $(,)?)? { $($arg:ident: $collection:ident <$t:ty>,)+ }) => {
+        $crate::system! {
+            @impl $system [$($gen $(: $bound)?),+] <$entity> [$($($wty: $wb,)+)?] [] [$($filter)?] { $($arg: $collection<$t>,)+ }
+        }
+    };
+
+    (@impl $system:ident [$($gen:ident $(: $bound:path)?),*] <$entity:ty> [$($wty:ty: $wb:path,)*] [$($ctx:ident: $ctxty:ty)?] [$($filter:expr)?] { $($arg:ident: $collection:ident <$t:ty>,)+ }) => {
+        $($crate::check_collection!($collection);)+
+
+        impl<$($gen $(: $bound)?),*> $system<$($gen),*> where $($wty: $wb,)* {
+            #[allow(unused_variables, unused_mut, clippy::too_many_arguments)]
+            fn run(&self, $($ctx: &$ctxty,)? $($arg: &mut $crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+) {
+                self.run_until($($ctx,)? |_| std::ops::ControlFlow::Continue(()), $($arg),+)
+            }
+
+            #[allow(unused_variables, unused_mut, clippy::too_many_arguments)]
+            fn run_until<P: FnMut($entity) -> std::ops::ControlFlow<()>>(&self, $($ctx: &$ctxty,)? until: P, $($arg: &mut $crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+) {
+                self.run_from($($ctx,)? <$entity as Default>::default(), until, $($arg),+)
+            }
+
+            #[allow(dead_code, unused_variables, unused_mut, clippy::too_many_arguments)]
+            fn run_limited(&self, $($ctx: &$ctxty,)? max_entities: usize, $($arg: &mut $crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+ Option<$entity>) {
+                self.run_resumed($($ctx,)? <$entity as Default>::default(), max_entities, $($arg),+)
+            }
+
+            #[allow(dead_code, unused_variables, unused_mut, clippy::too_many_arguments)]
+            fn run_resumed(&self, $($ctx: &$ctxty,)? resume_from: $entity, max_entities: usize, $($arg: &mut $crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+ Option<$entity>) {
+                // NOTE(rescrv):  until is only consulted after processing an entity, so a limit
+                // of zero has to stop before the run starts.
+                if max_entities == 0 {
+                    return ($(Vec::<($entity, ComponentChange<$t>)>::new(),)+ Some(resume_from));
                 }
-                let mut target = <$entity as Default>::default();
-                let mut results = Results::default();
+                let mut processed = 0usize;
+                let mut remaining = None;
+                let ($($arg,)+) = self.run_from($($ctx,)? resume_from, |entity: $entity| {
+                    processed += 1;
+                    if processed < max_entities {
+                        return std::ops::ControlFlow::Continue(());
+                    }
+                    if entity != <$entity as $crate::Entity>::max_value() {
+                        remaining = Some(entity.increment());
+                    }
+                    std::ops::ControlFlow::Break(())
+                }, $($arg),+);
+                ($($arg,)+ remaining)
+            }
+
+            #[allow(unused_variables, unused_mut, clippy::too_many_arguments)]
+            fn run_from<P: FnMut($entity) -> std::ops::ControlFlow<()>>(&self, $($ctx: &$ctxty,)? start: $entity, until: P, $($arg: &mut $crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+) {
+                let mut bufs = ($(Vec::<($entity, ComponentChange<$t>)>::new(),)+);
+                self.run_from_into($($ctx,)? start, until, &mut bufs, $($arg),+);
+                bufs
+            }
+
+            #[allow(dead_code, unused_variables, unused_mut, clippy::too_many_arguments)]
+            fn run_into(&self, $($ctx: &$ctxty,)? bufs: &mut ($(Vec<($entity, ComponentChange<$t>)>,)+), $($arg: &mut $crate::$collection<$entity, $t>),+) {
+                {
+                    let ($($arg,)+) = &mut *bufs;
+                    $($arg.clear();)+
+                }
+                self.run_from_into($($ctx,)? <$entity as Default>::default(), |_| std::ops::ControlFlow::Continue(()), bufs, $($arg),+)
+            }
+
+            // NOTE(rescrv):  The zipper behind run_from and run_into.  It appends each arg's
+            // changes to the matching buffer in bufs.
+            #[allow(unused_variables, unused_mut, clippy::too_many_arguments)]
+            fn run_from_into<P: FnMut($entity) -> std::ops::ControlFlow<()>>(&self, $($ctx: &$ctxty,)? start: $entity, mut until: P, bufs: &mut ($(Vec<($entity, ComponentChange<$t>)>,)+), $($arg: &mut $crate::$collection<$entity, $t>),+) {
+                struct Results<'b> {
+                    $($arg: &'b mut Vec<($entity, ComponentChange<$t>)>,)+
+                }
+                let results = {
+                    let ($($arg,)+) = bufs;
+                    Results { $($arg,)+ }
+                };
+                let mut target = start;
+                let filter = $crate::__system_filter!(@make [$($filter)?] <$entity> [$($t),+]);
+                // NOTE(rescrv):  No arg can see more changes than the smallest collection has
+                // entities, so reserve that much on the first change rather than growing one push
+                // at a time.  Args that never change don't allocate at all.
+                let capacity = [$($arg.len(),)+].into_iter().min().unwrap_or(0);
                 'zipper: loop {
+                    // NOTE(rescrv):  Leapfrog every arg to the largest lower bound in one pass, so
+                    // that an arg ahead of the target moves the others with it rather than sending
+                    // the zipper back to recheck each arg ahead of it.  Only when some arg lacks
+                    // the target does it take another pass.
+                    let mut next = target;
                     $(
-                        let Some(lb) = $arg.lower_bound(target) else {
+                        let Some(lb) = $arg.lower_bound(next) else {
                             break 'zipper;
                         };
-                        if lb > target {
-                            target = lb;
-                            continue 'zipper;
-                        }
+                        next = lb;
                     )+
+                    if next > target {
+                        target = next;
+                        continue 'zipper;
+                    }
                     // SAFETY(rescrv):  We know that target is an entity that exists in all args.
                     $(let mut $arg = $arg.get_ref(target).expect("target should be present");)+
-                    self.process(target, $(&mut $arg),+);
-                    // Gather changes.
-                    $(
-                        let $arg = $arg.change();
-                        if !$arg.is_no_change() {
-                            results.$arg.push((target, $arg));
+                    if $crate::__system_filter!(@call [$($filter)?] filter, target, $(&*$arg),+) {
+                        self.process($($ctx,)? target, $(&mut $arg),+);
+                        // Gather changes.
+                        $(
+                            let $arg = $arg.change();
+                            if !$arg.is_no_change() {
+                                if results.$arg.capacity() == 0 {
+                                    results.$arg.reserve(capacity);
+                                }
+                                results.$arg.push((target, $arg));
+                            }
+                        )+
+                        if until(target).is_break() {
+                            break 'zipper;
                         }
-                    )+
+                    }
+                    // NOTE(rescrv):  Incrementing the maximum entity wraps around to the default
+                    // entity, which would start the run over.
+                    if target == <$entity as $crate::Entity>::max_value() {
+                        break 'zipper;
+                    }
                     // Make it so we move past this entity.
                     target = target.increment();
                 }
-                ($(results.$arg,)+)
             }
 
-            fn run_subset(&self, entities: &[$entity], $($arg: &mut $crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+) {
+            #[allow(dead_code, unused_variables, clippy::too_many_arguments)]
+            fn preview_entities(&self, $($arg: &$crate::$collection<$entity, $t>),+) -> Vec<$entity> {
+                let mut target = <$entity as Default>::default();
+                let mut entities = vec![];
+                let filter = $crate::__system_filter!(@make [$($filter)?] <$entity> [$($t),+]);
+                'zipper: loop {
+                    let mut next = target;
+                    $(
+                        let Some(lb) = $crate::ComponentCollection::lower_bound($arg, next) else {
+                            break 'zipper;
+                        };
+                        next = lb;
+                    )+
+                    if next > target {
+                        target = next;
+                        continue 'zipper;
+                    }
+                    if $crate::__system_filter!(@call [$($filter)?] filter, target, $(&*$crate::ComponentCollection::get_ref($arg, target).expect("target should be present")),+) {
+                        entities.push(target);
+                    }
+                    if target == <$entity as $crate::Entity>::max_value() {
+                        break 'zipper;
+                    }
+                    target = target.increment();
+                }
+                entities
+            }
+
+            #[allow(dead_code, unused_variables, unused_mut, clippy::too_many_arguments)]
+            fn run_subset(&self, $($ctx: &$ctxty,)? entities: &[$entity], $($arg: &mut $crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+) {
                 #[derive(Default)]
                 struct Results {
                     $($arg: Vec<($entity, ComponentChange<$t>)>,)+
                 }
                 let mut results = Results::default();
+                let filter = $crate::__system_filter!(@make [$($filter)?] <$entity> [$($t),+]);
                 for target in entities.iter() {
                     $(
                         let Some(mut $arg) = $arg.get_ref(target.clone()) else {
                             continue;
                         };
                     )+
-                    self.process(target.clone(), $(&mut $arg),+);
+                    if !$crate::__system_filter!(@call [$($filter)?] filter, target.clone(), $(&*$arg),+) {
+                        continue;
+                    }
+                    self.process($($ctx,)? target.clone(), $(&mut $arg),+);
                     // Gather changes.
                     $(
                         let $arg = $arg.change();
@@ -95,24 +319,319 @@ macro_rules! system {
     };
 }
 
+/// Build (`@make`) and call (`@call`) the filter of a [system], if it has one.  Without a filter,
+/// `@call` is true and does not evaluate the components passed to it.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __system_filter {
+    (@make [] <$entity:ty> [$($t:ty),+]) => {
+        ()
+    };
+
+    (@make [$filter:expr] <$entity:ty> [$($t:ty),+]) => {{
+        // NOTE(rescrv):  Pass the filter through a function with the full signature so that a
+        // closure's arguments need no type annotations.
+        fn filter<F: Fn($entity, $(&$t),+) -> bool>(f: F) -> F {
+            f
+        }
+        filter($filter)
+    }};
+
+    (@call [] $f:ident, $target:expr, $($component:expr),+) => {
+        true
+    };
+
+    (@call [$filter:expr] $f:ident, $target:expr, $($component:expr),+) => {
+        $f($target, $($component),+)
+    };
+}
+
+////////////////////////////////////////// system_context //////////////////////////////////////////
+
+/// Define a run method for a system that reads a shared context, like configuration tables or the
+/// current time, that is not a component.  The generated `run` and `run_subset` methods take a
+/// `&Ctx` ahead of the component collections and pass it ahead of the entity to `process`.
+///
+/// ```ignore
+/// system_with_context! {
+///     MySystem<u128, Ctx = GameContext> {
+///         a: CopyOnWriteComponentCollection<u64>,
+///     }
+/// }
+///
+/// impl MySystem {
+///     fn process(&self, ctx: &GameContext, entity: u128, a: &mut CopyOnWriteComponentRef<u64>) {
+///         // ...
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! system_with_context {
+    ($system:ident <$entity:ty, Ctx = $ctx:ty> {}) => {
+        compile_error!("A system operates on 1 or more component collections.  Found: 0.");
+    };
+
+    ($system:ident <$entity:ty, Ctx = $ctx:ty> { $($arg:ident: $collection:ident <$t:ty>,)+ }) => {
+        $crate::system! {
+            @impl $system [] <$entity> [] [context: $ctx] [] { $($arg: $collection<$t>,)+ }
+        }
+    };
+}
+
+/////////////////////////////////////////////// rayon //////////////////////////////////////////////
+
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "rayon")]
+    pub use rayon;
+}
+
+/// Expand to the tokens given when tnaps is built with the `rayon` feature, and to nothing
+/// otherwise.  The check has to happen here because a `cfg` in a macro's expansion would test the
+/// calling crate's features rather than tnaps'.
+#[cfg(feature = "rayon")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_rayon {
+    ($($tt:tt)*) => { $($tt)* };
+}
+
+#[cfg(not(feature = "rayon"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_rayon {
+    ($($tt:tt)*) => {};
+}
+
+////////////////////////////////////////// system_parallel /////////////////////////////////////////
+
 /// Define a run method for the described system that operates in parallel using a thread pool.
 /// The generated method will take a list of args that are component collections and return a tuple
 /// of vectors of changes for each component collection.  It is up to the user to subsequently pass
 /// this state to the `apply` method of the component collections.
+///
+/// Each partition's refs are acquired in order of the partition's address rather than argument
+/// order, so two parallel systems that share lock-holding collections like
+/// MutableComponentCollection may run concurrently without deadlock, regardless of the order in
+/// which they list those collections.
+///
+/// The collections are taken by shared reference, so a `Partitioned` wrapped in an `Arc` may be
+/// shared between concurrent runs and passed as `&arc`; deref coercion takes care of the rest.
+///
+/// A MutableComponentCollection mutates in place under a single lock, so a lone collection would
+/// serialize every work unit.  Partition it instead:  each partition is its own collection with
+/// its own lock over a disjoint range of entities, so work units mutate their partitions
+/// concurrently and return no changes for the updates they made in place.
+///
+/// Dispatching to the thread pool costs more than it saves for small inputs, so when the smallest
+/// collection holds fewer than `MIN_ENTITIES_FOR_PARALLEL` entities, `run` processes every
+/// partition inline on the calling thread instead.  The threshold defaults to 1024 and may be
+/// overridden per system:
+///
+/// ```ignore
+/// system_parallel! {
+///     #[min_parallel = 512]
+///     MySystem<u128> {
+///         a: CopyOnWriteComponentCollection<u64>,
+///     }
+/// }
+/// ```
+///
+/// A `run_parallel_subset` method is generated alongside `run`.  It processes only the listed
+/// entities, like `run_subset` for [system], but takes the thread pool and the partitioned
+/// collections.  It bins the list by partition and splits the bins into chunks across the
+/// threads, so a sparse dirty set never scans whole partitions.  Each partition's changes come
+/// back already in place for `apply`.  The list need not be sorted, but a sorted list saves a
+/// copy.  It applies the same threshold to the length of the list.
+///
+/// With the `rayon` feature, a `run_rayon` method processes each partition as a task on rayon's
+/// global pool instead of a [ThreadPool], for projects that already use rayon.  It takes the same
+/// collections as `run` and returns the same changes, without the inline threshold.
+///
+/// To count the entities processed across every partition, name an `AtomicUsize` field of the
+/// system after `#[count_entities]`.  Attributes may be given in any order.  Each run
+/// method resets the field to zero when called and increments it once per entity processed, so
+/// it holds the run's total once the run's results are in hand:
+///
+/// ```ignore
+/// struct MySystem {
+///     processed: AtomicUsize,
+/// }
+///
+/// system_parallel! {
+///     #[count_entities = processed]
+///     MySystem<u128> {
+///         a: CopyOnWriteComponentCollection<u64>,
+///     }
+/// }
+/// ```
+///
+/// Every run method checks that its collections are partitioned under equal schemes, first by
+/// `Arc::ptr_eq` and then, for schemes built independently, by
+/// [PartitioningScheme::structural_eq].  Each collection's changes carry that collection's own
+/// scheme, so they apply to it either way.  Checking structurally is linear in the number of
+/// dividers; a caller that knows its schemes agree may skip the check with
+/// `#[verify_scheme_equality = false]`:
+///
+/// ```ignore
+/// system_parallel! {
+///     #[min_parallel = 512]
+///     #[verify_scheme_equality = false]
+///     MySystem<u128> {
+///         a: CopyOnWriteComponentCollection<u64>,
+///     }
+/// }
+/// ```
 #[macro_export]
 macro_rules! system_parallel {
-    ($system:ident <$entity:ty> {}) => {
+    ($(#[$attr:meta])* $system:ident <$entity:ty> {}) => {
         compile_error!("A system operates on 1 or more component collections.  Found: 0.");
     };
 
-    ($system:ident <$entity:ty> { $($arg:ident: $collection:ident <$t:ty>,)+ }) => {
+    ($(#[$($attr:tt)+])* $system:ident <$entity:ty> { $($body:tt)* }) => {
+        $crate::system_parallel! {
+            @attrs [] [] [] $(#[$($attr)+])* $system <$entity> { $($body)* }
+        }
+    };
+
+    // NOTE(rescrv):  Munch the attributes one at a time so they may come in any order.  Each slot
+    // must be empty to take its attribute, so a repeated attribute fails to match.
+    (@attrs [] [$($count:ident)?] [$($verify:expr)?] #[min_parallel = $min:expr] $($rest:tt)+) => {
+        $crate::system_parallel! {
+            @attrs [$min] [$($count)?] [$($verify)?] $($rest)+
+        }
+    };
+
+    (@attrs [$($min:expr)?] [] [$($verify:expr)?] #[count_entities = $count:ident] $($rest:tt)+) => {
+        $crate::system_parallel! {
+            @attrs [$($min)?] [$count] [$($verify)?] $($rest)+
+        }
+    };
+
+    (@attrs [$($min:expr)?] [$($count:ident)?] [] #[verify_scheme_equality = $verify:expr] $($rest:tt)+) => {
+        $crate::system_parallel! {
+            @attrs [$($min)?] [$($count)?] [$verify] $($rest)+
+        }
+    };
+
+    (@attrs [$($min:expr)?] [$($count:ident)?] [$($verify:expr)?] $system:ident <$entity:ty> { $($arg:ident: $collection:ident <$t:ty>,)+ }) => {
+        $crate::system_parallel! {
+            @impl $system <$entity> [$crate::system_parallel!(@min_parallel $($min)?)] [$($count)?] [$($verify)?] { $($arg: $collection<$t>,)+ }
+        }
+    };
+
+    (@min_parallel) => {
+        1024
+    };
+
+    (@min_parallel $min:expr) => {
+        $min
+    };
+
+    (@verify) => {
+        true
+    };
+
+    (@verify $verify:expr) => {
+        $verify
+    };
+
+    // NOTE(rescrv):  Check that every collection is partitioned under an equal scheme, returning
+    // the first scheme and a struct that holds each collection's own scheme by name.  Each
+    // collection's changes carry that collection's own scheme, so they apply to it even when the
+    // schemes are equal without sharing an Arc.
+    (@schemes $entity:ty { $($arg:ident),+ }) => {{
+        struct Schemes {
+            $($arg: std::sync::Arc<dyn $crate::PartitioningScheme<$entity>>,)+
+        }
+        let schemes = [$($arg.partitioning_scheme(),)+];
+        let ptr = std::sync::Arc::clone(schemes[0]);
+        if Self::VERIFY_SCHEME_EQUALITY {
+            for scheme in schemes {
+                if !std::sync::Arc::ptr_eq(&ptr, scheme) && !$crate::PartitioningScheme::structural_eq(&*ptr, &**scheme) {
+                    panic!("parallel system run with different partitioning schemes");
+                }
+            }
+        }
+        (ptr, Schemes { $($arg: std::sync::Arc::clone($arg.partitioning_scheme()),)+ })
+    }};
+
+    (@impl $system:ident <$entity:ty> [$min:expr] [$($count:ident)?] [$($verify:expr)?] { $($arg:ident: $collection:ident <$t:ty>,)+ }) => {
+        $($crate::check_collection!($collection);)+
+
         impl $system {
+            /// Below this many entities in the smallest collection, run without the thread pool.
+            const MIN_ENTITIES_FOR_PARALLEL: usize = $min;
+            /// Whether to check that every collection is partitioned under an equal scheme.
+            const VERIFY_SCHEME_EQUALITY: bool = $crate::system_parallel!(@verify $($verify)?);
+
+            // NOTE(rescrv):  The zipper over one partition of each collection, shared by `run` and
+            // `run_rayon`.
+            #[allow(clippy::too_many_arguments)]
+            fn process_partition(&self, $($arg: &$crate::$collection<$entity, $t>),+) -> ($(Vec<($entity, ComponentChange<$t>)>,)+) {
+                #[derive(Default)]
+                struct Intermediate {
+                    $($arg: Vec<($entity, ComponentChange<$t>)>,)+
+                }
+                let mut target = <$entity as Default>::default();
+                let mut results = Intermediate::default();
+                'zipper: loop {
+                    // NOTE(rescrv):  Leapfrog as in system!'s run_until.
+                    let mut next = target;
+                    $(
+                        let Some(lb) = $crate::ComponentCollection::lower_bound($arg, next) else {
+                            break 'zipper;
+                        };
+                        next = lb;
+                    )+
+                    if next > target {
+                        target = next;
+                        continue 'zipper;
+                    }
+                    // NOTE(rescrv):  Refs take the collection's lock, and another system may hold
+                    // refs into the same collections concurrently.  Acquire them in order of
+                    // address so that argument order cannot cause a lock-order inversion.
+                    let mut order = [$($arg as *const $crate::$collection<$entity, $t> as *const () as usize,)+];
+                    order.sort_unstable();
+                    $(let mut $arg = ($arg, None);)+
+                    for addr in order {
+                        $(
+                            if $arg.1.is_none() && addr == $arg.0 as *const $crate::$collection<$entity, $t> as *const () as usize {
+                                // SAFETY(rescrv):  We know that target is an entity that exists in all args.
+                                $arg.1 = Some($crate::ComponentCollection::get_ref($arg.0, target).expect("target should be present"));
+                                continue;
+                            }
+                        )+
+                    }
+                    $(let mut $arg = $arg.1.expect("every ref acquired in address order");)+
+                    self.process(target, $(&mut $arg),+);
+                    $(self.$count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);)?
+                    // Gather changes.
+                    $(
+                        let $arg = $arg.change();
+                        if !$arg.is_no_change() {
+                            results.$arg.push((target, $arg));
+                        }
+                    )+
+                    // NOTE(rescrv):  Incrementing the maximum entity wraps around to the
+                    // default entity, which would start the partition over.
+                    if target == <$entity as $crate::Entity>::max_value() {
+                        break 'zipper;
+                    }
+                    // Make it so we move past this entity.
+                    target = target.increment();
+                }
+                ($(results.$arg,)+)
+            }
+
+            #[allow(clippy::too_many_arguments)]
             fn run(self: std::sync::Arc<Self>, thread_pool: &ThreadPool,
                    $($arg: &$crate::Partitioned<$entity, $t, $crate::$collection<$entity, $t>>),+)
-                -> impl FnOnce() -> ($(Vec<Vec<($entity, ComponentChange<$t>)>>,)+)
+                -> impl FnOnce() -> ($($crate::PartitionedChanges<$entity, $t>,)+)
             {
                 use std::sync::atomic::{AtomicUsize, Ordering};
                 use std::sync::{Arc, Condvar, Mutex};
+                $(self.$count.store(0, Ordering::Relaxed);)?
                 let system = Arc::clone(&self);
                 #[derive(Default)]
                 struct Intermediate {
@@ -127,32 +646,8 @@ macro_rules! system_parallel {
                 }
                 impl WorkInput {
                     fn gather_results(&self, system: Arc<$system>) -> Intermediate {
-                        let mut target = <$entity as Default>::default();
-                        let mut results = Intermediate::default();
-                        'zipper: loop {
-                            $(
-                                let Some(lb) = self.$arg.lower_bound(target) else {
-                                    break 'zipper;
-                                };
-                                if lb > target {
-                                    target = lb;
-                                    continue 'zipper;
-                                }
-                            )+
-                            // SAFETY(rescrv):  We know that target is an entity that exists in all args.
-                            $(let mut $arg = self.$arg.get_ref(target).expect("target should be present");)+
-                            system.process(target, $(&mut $arg),+);
-                            // Gather changes.
-                            $(
-                                let $arg = $arg.change();
-                                if !$arg.is_no_change() {
-                                    results.$arg.push((target, $arg));
-                                }
-                            )+
-                            // Make it so we move past this entity.
-                            target = target.increment();
-                        }
-                        results
+                        let ($($arg,)+) = system.process_partition($(&self.$arg),+);
+                        Intermediate { $($arg,)+ }
                     }
                 }
                 struct AggregatePartitions {
@@ -202,22 +697,19 @@ macro_rules! system_parallel {
                             // SAFETY(rescrv):  We wait until all partitions have been set.
                             // About 20 lines north of here we set Some(results) atomic with
                             // incrementing of the done count.
-                            let mut partition = partition.take().unwrap();
+                            let partition = partition.take().unwrap();
                             $(results.$arg.push(partition.$arg);)+
                         }
                         ($(results.$arg,)+)
                     }
                 }
-                $(let ptr = $arg.partitioning_scheme();)+
-                $(
-                    if !Arc::ptr_eq(ptr, $arg.partitioning_scheme()) {
-                        panic!("parallel system run with different partitioning schemes");
-                    }
-                )+
+                let (ptr, schemes) = $crate::system_parallel!(@schemes $entity { $($arg),+ });
                 // NOTE(rescrv):  There's always one more partition in the collection than the
                 // partitioning scheme.  This is so that we capture everything greater-equal than
                 // the last partition listed (or, if there are no partitions).
-                let partitions = ptr.len() + 1;
+                let partitions = $crate::PartitioningScheme::len(&*ptr) + 1;
+                let estimate = [$($arg.len(),)+].into_iter().min().unwrap_or(0);
+                let serial = estimate < Self::MIN_ENTITIES_FOR_PARALLEL;
                 let agg = Arc::new(AggregatePartitions::new(partitions));
                 for partition in 0..partitions {
                     $(
@@ -233,18 +725,267 @@ macro_rules! system_parallel {
                     let agg = Arc::clone(&agg);
                     let work_unit: Box<$crate::WorkUnit> = Box::new(move || {
                         let results = work_input.gather_results(system);
+                        // NOTE(rescrv):  Release the partitions before signaling completion so
+                        // that the caller may apply changes as soon as the wait returns.
+                        drop(work_input);
                         agg.done(partition, results);
                     });
-                    thread_pool.enqueue(work_unit);
+                    if serial {
+                        work_unit();
+                    } else {
+                        thread_pool.enqueue(work_unit);
+                    }
                 }
                 move || {
-                    agg.wait()
+                    let ($($arg,)+) = agg.wait();
+                    ($($crate::PartitionedChanges::new(&schemes.$arg, $arg),)+)
+                }
+            }
+
+            // NOTE(rescrv):  Not every system runs on subsets; don't warn those that don't.
+            #[allow(dead_code, clippy::too_many_arguments)]
+            fn run_parallel_subset(&self, thread_pool: &$crate::ThreadPool, entities: &[$entity],
+                                   $($arg: &$crate::Partitioned<$entity, $t, $crate::$collection<$entity, $t>>),+)
+                -> ($($crate::PartitionedChanges<$entity, $t>,)+)
+            {
+                use std::sync::Arc;
+                $(self.$count.store(0, std::sync::atomic::Ordering::Relaxed);)?
+                #[derive(Default)]
+                struct Intermediate {
+                    $($arg: Vec<($entity, $crate::ComponentChange<$t>)>,)+
+                }
+                struct WorkInput {
+                    $($arg: Arc<$crate::$collection<$entity, $t>>,)+
+                }
+                impl WorkInput {
+                    fn gather_results(&self, system: &$system, entities: &[$entity], results: &mut Intermediate) {
+                        'entities: for target in entities.iter().copied() {
+                            // NOTE(rescrv):  Acquire refs in order of address for the same reason
+                            // as `run`.
+                            let mut order = [$(Arc::as_ptr(&self.$arg) as *const () as usize,)+];
+                            order.sort_unstable();
+                            $(let mut $arg = None;)+
+                            for addr in order {
+                                $(
+                                    if $arg.is_none() && addr == Arc::as_ptr(&self.$arg) as *const () as usize {
+                                        let Some(r) = $crate::ComponentCollection::get_ref(&*self.$arg, target) else {
+                                            continue 'entities;
+                                        };
+                                        $arg = Some(r);
+                                        continue;
+                                    }
+                                )+
+                            }
+                            $(let mut $arg = $arg.expect("every ref acquired in address order");)+
+                            system.process(target, $(&mut $arg),+);
+                            $(system.$count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);)?
+                            $(
+                                let $arg = $crate::ComponentRef::change($arg);
+                                if !$arg.is_no_change() {
+                                    results.$arg.push((target, $arg));
+                                }
+                            )+
+                        }
+                    }
+                }
+                // NOTE(rescrv):  Binning needs a sorted list.  Sort a copy only when the caller
+                // didn't, dropping duplicates so that no entity is processed twice.
+                let sorted;
+                let entities = if entities.windows(2).all(|w| w[0] < w[1]) {
+                    entities
+                } else {
+                    let mut copy = entities.to_vec();
+                    copy.sort_unstable();
+                    copy.dedup();
+                    sorted = copy;
+                    &sorted[..]
+                };
+                let (ptr, schemes) = $crate::system_parallel!(@schemes $entity { $($arg),+ });
+                // NOTE(rescrv):  There's one more partition than the scheme has dividers.  Partition
+                // i holds [partition(i - 1), partition(i)), so each bin ends at the first entity
+                // not less than its divider.
+                let partitions = $crate::PartitioningScheme::len(&*ptr) + 1;
+                let mut bins = Vec::with_capacity(partitions);
+                let mut start = 0;
+                for partition in 0..partitions {
+                    let end = if partition + 1 < partitions {
+                        let divider = $crate::PartitioningScheme::partition(&*ptr, partition);
+                        start + entities[start..].partition_point(|e| *e < divider)
+                    } else {
+                        entities.len()
+                    };
+                    bins.push(&entities[start..end]);
+                    start = end;
+                }
+                // NOTE(rescrv):  Split each bin into chunks so that a subset concentrated in a few
+                // partitions still spreads across every thread.  A partition's chunks stay in
+                // order, so its changes come back sorted.
+                let serial = entities.len() < Self::MIN_ENTITIES_FOR_PARALLEL;
+                let chunk_size = if serial {
+                    entities.len().max(1)
+                } else {
+                    entities.len().div_ceil(thread_pool.num_threads().max(1)).max(1)
+                };
+                let mut work = vec![];
+                for (partition, bin) in bins.into_iter().enumerate() {
+                    if bin.is_empty() {
+                        continue;
+                    }
+                    $(
+                        let Some($arg) = $arg.get_partition_by_index(partition) else {
+                            continue;
+                        };
+                    )+
+                    for chunk in bin.chunks(chunk_size) {
+                        work.push((partition, chunk, WorkInput { $($arg: Arc::clone(&$arg),)+ }));
+                    }
+                }
+                let mut results: Vec<Intermediate> = work.iter().map(|_| Intermediate::default()).collect();
+                if serial {
+                    for ((_, chunk, work_input), results) in std::iter::zip(work.iter(), results.iter_mut()) {
+                        work_input.gather_results(self, chunk, results);
+                    }
+                } else {
+                    thread_pool.scope(|scope| {
+                        for ((_, chunk, work_input), results) in std::iter::zip(work.iter(), results.iter_mut()) {
+                            scope.enqueue(move || work_input.gather_results(self, chunk, results));
+                        }
+                    });
+                }
+                $(let mut $arg: Vec<Vec<($entity, $crate::ComponentChange<$t>)>> = (0..partitions).map(|_| vec![]).collect();)+
+                for ((partition, _, _), results) in std::iter::zip(work.iter(), results.into_iter()) {
+                    $($arg[*partition].extend(results.$arg);)+
+                }
+                ($($crate::PartitionedChanges::new(&schemes.$arg, $arg),)+)
+            }
+
+            $crate::__if_rayon! {
+                /// Run the system over every partition as rayon tasks on the global rayon pool,
+                /// rather than on a `ThreadPool`.  Changes are identical to `run`.
+                #[allow(dead_code, clippy::too_many_arguments)]
+                fn run_rayon(&self, $($arg: &$crate::Partitioned<$entity, $t, $crate::$collection<$entity, $t>>),+)
+                    -> ($($crate::PartitionedChanges<$entity, $t>,)+)
+                {
+                    use $crate::__private::rayon::iter::{IntoParallelIterator, ParallelIterator};
+                    $(self.$count.store(0, std::sync::atomic::Ordering::Relaxed);)?
+                    #[derive(Default)]
+                    struct Intermediate {
+                        $($arg: Vec<($entity, $crate::ComponentChange<$t>)>,)+
+                    }
+                    let (ptr, schemes) = $crate::system_parallel!(@schemes $entity { $($arg),+ });
+                    let partitions = $crate::PartitioningScheme::len(&*ptr) + 1;
+                    let results: Vec<Intermediate> = (0..partitions).into_par_iter().map(|partition| {
+                        $(
+                            let Some($arg) = $arg.get_partition_by_index(partition) else {
+                                return Intermediate::default();
+                            };
+                        )+
+                        let ($($arg,)+) = self.process_partition($(&$arg),+);
+                        Intermediate { $($arg,)+ }
+                    }).collect();
+                    $(let mut $arg: Vec<Vec<($entity, $crate::ComponentChange<$t>)>> = Vec::with_capacity(partitions);)+
+                    for partition in results.into_iter() {
+                        $($arg.push(partition.$arg);)+
+                    }
+                    ($($crate::PartitionedChanges::new(&schemes.$arg, $arg),)+)
+                }
+            }
+        }
+    };
+}
+
+/////////////////////////////////////////////// world //////////////////////////////////////////////
+
+/// Declare a world: a struct that holds one component collection per field, alongside a second
+/// struct that holds the same collections after they have been partitioned.
+///
+/// ```ignore
+/// world! {
+///     pub Demo<u128> partitioned PartitionedDemo {
+///         position: CopyOnWriteComponentCollection<(f64, f64)>,
+///         velocity: MutableComponentCollection<(f64, f64)>,
+///     }
+/// }
+/// ```
+///
+/// The world gets a `partition_all` method that partitions every collection by the same scheme
+/// and returns the partitioned world.  Both worlds get an `apply_all` method that takes a tuple
+/// with one set of changes per field, in declaration order, and passes each to that field's
+/// `apply`.  A [system] or [system_parallel] declared over the same fields in the same order
+/// returns exactly that tuple, so a system that falls out of sync with its world fails to compile.
+/// Systems over a subset of the fields apply their changes to those fields directly.
+#[macro_export]
+macro_rules! world {
+    ($vis:vis $world:ident <$entity:ty> partitioned $partitioned:ident {}) => {
+        compile_error!("A world holds 1 or more component collections.  Found: 0.");
+    };
+
+    ($vis:vis $world:ident <$entity:ty> partitioned $partitioned:ident { $($field:ident: $collection:ident <$t:ty>,)+ }) => {
+        $($crate::check_collection!($collection);)+
+
+        #[derive(Debug, Default)]
+        $vis struct $world {
+            $($vis $field: $crate::$collection<$entity, $t>,)+
+        }
+
+        impl $world {
+            /// Partition every collection by `partitioning`.
+            $vis fn partition_all(self, partitioning: &std::sync::Arc<dyn $crate::PartitioningScheme<$entity>>) -> $partitioned {
+                $partitioned {
+                    $($field: $crate::Partitioned::from(partitioning, $crate::ComponentCollection::partition(self.$field, &**partitioning)),)+
                 }
             }
+
+            /// Apply one set of changes to each collection, in declaration order.
+            $vis fn apply_all(&mut self, changes: ($(Vec<($entity, $crate::ComponentChange<$t>)>,)+)) {
+                let ($($field,)+) = changes;
+                $($crate::ComponentCollection::apply(&mut self.$field, $field);)+
+            }
+        }
+
+        #[derive(Debug, Default)]
+        $vis struct $partitioned {
+            $($vis $field: $crate::Partitioned<$entity, $t, $crate::$collection<$entity, $t>>,)+
+        }
+
+        impl $partitioned {
+            /// Apply one set of partitioned changes to each collection, in declaration order.
+            $vis fn apply_all(&mut self, changes: ($($crate::PartitionedChanges<$entity, $t>,)+)) {
+                let ($($field,)+) = changes;
+                $(self.$field.apply($field);)+
+            }
         }
     };
 }
 
+////////////////////////////////////////////// changes /////////////////////////////////////////////
+
+/// Build a vector of changes suitable for `apply`, sorted by entity.  Each entry maps an entity
+/// to a value or to `unbind`.  Entries for the same entity compose in the order written, so the
+/// last one wins.
+///
+/// ```
+/// use tnaps::{changes, ComponentChange};
+///
+/// let changes: Vec<(u128, ComponentChange<u64>)> = changes![(3 => 30), (1 => unbind), (2 => 20)];
+/// assert_eq!(vec![1, 2, 3], changes.iter().map(|(e, _)| *e).collect::<Vec<_>>());
+/// assert!(matches!(changes[0].1, ComponentChange::Unbind));
+/// ```
+#[macro_export]
+macro_rules! changes {
+    (@change unbind) => {
+        $crate::ComponentChange::unbind()
+    };
+
+    (@change $value:expr) => {
+        $crate::ComponentChange::value($value)
+    };
+
+    ($(($entity:expr => $($change:tt)+)),* $(,)?) => {
+        $crate::sort_changes(vec![$(($entity, $crate::changes!(@change $($change)+)),)*])
+    };
+}
+
 /////////////////////////////////////////////// tests //////////////////////////////////////////////
 
 #[cfg(test)]
@@ -259,6 +1000,18 @@ mod tests {
         }
     }
 
+    /// Add the default and maximum entities, which [arb_entity] never generates, to a sorted and
+    /// deduplicated vector of entities.
+    pub fn with_extremes(mut entities: Vec<u128>, default: bool, max: bool) -> Vec<u128> {
+        if default {
+            entities.insert(0, 0);
+        }
+        if max {
+            entities.push(u128::MAX);
+        }
+        entities
+    }
+
     pub fn is_free_of_duplicates(entities: &Vec<u128>) -> bool {
         let mut entities = entities.clone();
         let len = entities.len();
@@ -266,4 +1019,1830 @@ mod tests {
         entities.dedup();
         entities.len() == len
     }
+
+    mod generic_system {
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+
+        trait Multiplier {
+            fn multiplier(&self) -> u64;
+        }
+
+        struct Double;
+
+        impl Multiplier for Double {
+            fn multiplier(&self) -> u64 {
+                2
+            }
+        }
+
+        struct Scale<M: Multiplier>(M);
+
+        system! {
+            Scale<M: Multiplier><u128> where M: Send {
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl<M: Multiplier> Scale<M> {
+            fn process(&self, _: u128, value: &mut CopyOnWriteComponentRef<u64>) {
+                value.update(|v| *v *= self.0.multiplier());
+            }
+        }
+
+        #[test]
+        fn generic_system() {
+            let mut values = CopyOnWriteComponentCollection::from_iter(vec![(1u128, 1u64), (2, 2)]);
+            let (changes,) = Scale(Double).run(&mut values);
+            values.apply(changes);
+            let (changes,) = Scale(Double).run_subset(&[2], &mut values);
+            values.apply(changes);
+            assert_eq!(vec![(1, 2), (2, 8)], values.consume().collect::<Vec<_>>());
+        }
+    }
+
+    mod run_until {
+        use std::ops::ControlFlow;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+
+        struct Increment;
+
+        system! {
+            Increment<u128> {
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Increment {
+            fn process(&self, _: u128, value: &mut CopyOnWriteComponentRef<u64>) {
+                value.update(|v| *v += 1);
+            }
+        }
+
+        #[test]
+        fn run_until() {
+            let mut values =
+                CopyOnWriteComponentCollection::from_iter((1..=100u128).map(|e| (e, 0u64)));
+            let mut processed = 0;
+            let (changes,) = Increment.run_until(
+                |_| {
+                    processed += 1;
+                    if processed >= 10 {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                },
+                &mut values,
+            );
+            assert_eq!(10, changes.len());
+            values.apply(changes);
+            let values: Vec<(u128, u64)> = values.consume().collect();
+            assert_eq!(100, values.len());
+            for (e, v) in values.iter() {
+                assert_eq!(if *e <= 10 { 1 } else { 0 }, *v);
+            }
+            let mut values = CopyOnWriteComponentCollection::from_iter(values);
+            let (changes,) = Increment.run(&mut values);
+            assert_eq!(100, changes.len());
+            let (changes,) = Increment.run_subset(&[1, 2], &mut values);
+            assert_eq!(2, changes.len());
+        }
+
+        struct Pair;
+
+        system! {
+            Pair<u128> {
+                lhs: CopyOnWriteComponentCollection<u64>,
+                rhs: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Pair {
+            fn process(
+                &self,
+                _: u128,
+                lhs: &mut CopyOnWriteComponentRef<u64>,
+                rhs: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                lhs.update(|v| *v += 1);
+                if **rhs > 50 {
+                    rhs.unbind();
+                }
+            }
+        }
+
+        fn summarize(changes: &[(u128, ComponentChange<u64>)]) -> Vec<(u128, Option<u64>)> {
+            changes
+                .iter()
+                .map(|(e, c)| match c {
+                    ComponentChange::Value(v) => (*e, Some(*v)),
+                    _ => (*e, None),
+                })
+                .collect()
+        }
+
+        #[test]
+        fn run_limited() {
+            let lhs: Vec<(u128, u64)> = (1..=100u128)
+                .chain([u128::MAX])
+                .map(|e| (e, e as u8 as u64))
+                .collect();
+            let rhs: Vec<(u128, u64)> = (1..=100u128)
+                .step_by(3)
+                .chain([u128::MAX])
+                .map(|e| (e, e as u8 as u64))
+                .collect();
+            let mut lhs = CopyOnWriteComponentCollection::from_iter(lhs);
+            let mut rhs = CopyOnWriteComponentCollection::from_iter(rhs);
+            let (expected_lhs, expected_rhs) = Pair.run(&mut lhs, &mut rhs);
+            for max_entities in [1, 7, 35, 100] {
+                let mut calls = 1;
+                let (mut lhs_changes, mut rhs_changes, mut remaining) =
+                    Pair.run_limited(max_entities, &mut lhs, &mut rhs);
+                while let Some(resume_from) = remaining {
+                    let (l, r, next) =
+                        Pair.run_resumed(resume_from, max_entities, &mut lhs, &mut rhs);
+                    assert!(l.len() <= max_entities);
+                    lhs_changes.extend(l);
+                    rhs_changes.extend(r);
+                    remaining = next;
+                    calls += 1;
+                }
+                assert_eq!(expected_lhs.len().div_ceil(max_entities), calls);
+                assert_eq!(summarize(&expected_lhs), summarize(&lhs_changes));
+                assert_eq!(summarize(&expected_rhs), summarize(&rhs_changes));
+            }
+            let (changes, _, remaining) = Pair.run_limited(0, &mut lhs, &mut rhs);
+            assert!(changes.is_empty());
+            assert_eq!(Some(0), remaining);
+        }
+    }
+
+    mod no_change {
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+
+        struct Accumulate;
+
+        system! {
+            Accumulate<u128> {
+                rates: CopyOnWriteComponentCollection<u64>,
+                totals: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Accumulate {
+            fn process(
+                &self,
+                _: u128,
+                rates: &mut CopyOnWriteComponentRef<u64>,
+                totals: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                let rate = **rates;
+                if rate > 0 {
+                    totals.update(|t| *t += rate);
+                }
+            }
+        }
+
+        #[test]
+        fn unchanged_args_do_not_allocate() {
+            let mut rates =
+                CopyOnWriteComponentCollection::from_iter((1..=100u128).map(|e| (e, 0u64)));
+            let mut totals =
+                CopyOnWriteComponentCollection::from_iter((1..=100u128).map(|e| (e, 0u64)));
+            let (rate_changes, total_changes) = Accumulate.run(&mut rates, &mut totals);
+            assert_eq!(0, rate_changes.capacity());
+            assert_eq!(0, total_changes.capacity());
+            let mut rates = CopyOnWriteComponentCollection::from_iter(
+                (1..=100u128).map(|e| (e, (e % 2) as u64)),
+            );
+            let (rate_changes, total_changes) = Accumulate.run(&mut rates, &mut totals);
+            assert_eq!(0, rate_changes.capacity());
+            assert_eq!(50, total_changes.len());
+            assert_eq!(100, total_changes.capacity());
+            let (rate_changes, _) = Accumulate.run_subset(&[1, 2, 3], &mut rates, &mut totals);
+            assert_eq!(0, rate_changes.capacity());
+            totals.apply(total_changes);
+            assert_eq!(Some(1), totals.get_cloned(1));
+            assert_eq!(Some(0), totals.get_cloned(2));
+        }
+    }
+
+    mod run_into {
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+
+        struct Transfer;
+
+        system! {
+            Transfer<u128> {
+                from: CopyOnWriteComponentCollection<u64>,
+                to: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Transfer {
+            fn process(
+                &self,
+                _: u128,
+                from: &mut CopyOnWriteComponentRef<u64>,
+                to: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                if **from > 0 {
+                    from.update(|f| *f -= 1);
+                    to.update(|t| *t += 1);
+                }
+            }
+        }
+
+        type Changes = Vec<(u128, ComponentChange<u64>)>;
+
+        fn values(changes: &Changes) -> Vec<(u128, Option<u64>)> {
+            changes
+                .iter()
+                .map(|(e, c)| match c {
+                    ComponentChange::Value(v) => (*e, Some(*v)),
+                    _ => (*e, None),
+                })
+                .collect()
+        }
+
+        #[test]
+        fn matches_run() {
+            let mut from = CopyOnWriteComponentCollection::from_iter(
+                (1..=64u128).map(|e| (e, (e % 4) as u64)),
+            );
+            let mut to = CopyOnWriteComponentCollection::from_iter(
+                (1..=64u128).step_by(2).map(|e| (e, 0u64)),
+            );
+            let mut bufs: (Changes, Changes) = (vec![(0, ComponentChange::Unbind)], vec![]);
+            for _ in 0..4 {
+                let (from_changes, to_changes) = Transfer.run(&mut from, &mut to);
+                Transfer.run_into(&mut bufs, &mut from, &mut to);
+                assert_eq!(values(&from_changes), values(&bufs.0));
+                assert_eq!(values(&to_changes), values(&bufs.1));
+                assert!(bufs.0.windows(2).all(|w| w[0].0 < w[1].0));
+                from.apply(from_changes);
+                to.apply(to_changes);
+            }
+            assert!(bufs.0.is_empty());
+            assert!(bufs.1.is_empty());
+        }
+    }
+
+    mod context_system {
+        use std::collections::HashMap;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+
+        struct Prices {
+            table: HashMap<&'static str, u64>,
+        }
+
+        struct Appraise;
+
+        system_with_context! {
+            Appraise<u128, Ctx = Prices> {
+                items: CopyOnWriteComponentCollection<&'static str>,
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Appraise {
+            fn process(
+                &self,
+                ctx: &Prices,
+                _: u128,
+                item: &mut CopyOnWriteComponentRef<&'static str>,
+                value: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                let price = ctx.table.get(**item).copied().unwrap_or(0);
+                value.update(|v| *v = price);
+            }
+        }
+
+        #[test]
+        fn context_system() {
+            let prices = Prices {
+                table: HashMap::from([("sword", 100), ("shield", 50)]),
+            };
+            let mut items = CopyOnWriteComponentCollection::from_iter(vec![
+                (1u128, "sword"),
+                (2, "shield"),
+                (3, "rock"),
+            ]);
+            let mut values = CopyOnWriteComponentCollection::from_iter(vec![(1u128, 0u64), (3, 1)]);
+            let (item_changes, value_changes) = Appraise.run(&prices, &mut items, &mut values);
+            assert!(item_changes.is_empty());
+            values.apply(value_changes);
+            assert_eq!(vec![(1, 100), (3, 0)], values.consume().collect::<Vec<_>>());
+            let mut values = CopyOnWriteComponentCollection::from_iter(vec![(2u128, 0u64)]);
+            let (_, value_changes) = Appraise.run_subset(&prices, &[2], &mut items, &mut values);
+            values.apply(value_changes);
+            assert_eq!(vec![(2, 50)], values.consume().collect::<Vec<_>>());
+        }
+    }
+
+    mod shared_partitioned {
+        use std::sync::Arc;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{Partitioned, PartitioningScheme, VecPartitioningScheme};
+
+        struct Double;
+
+        system_parallel! {
+            Double<u128> {
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Double {
+            fn process(&self, _: u128, value: &mut CopyOnWriteComponentRef<u64>) {
+                value.update(|v| *v *= 2);
+            }
+        }
+
+        struct Negate;
+
+        system_parallel! {
+            Negate<u128> {
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Negate {
+            fn process(&self, _: u128, value: &mut CopyOnWriteComponentRef<u64>) {
+                value.update(|v| *v = v.wrapping_neg());
+            }
+        }
+
+        #[test]
+        fn shared_between_concurrent_systems() {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![4, 8]));
+            let values =
+                CopyOnWriteComponentCollection::from_iter((1..12u128).map(|e| (e, e as u64)));
+            let shared = Arc::new(Partitioned::from(
+                &partitioning,
+                values.partition(&*partitioning),
+            ));
+            assert!(Arc::ptr_eq(&partitioning, shared.partitioning_scheme()));
+            assert!(shared.get_partition_by_index(0).is_some());
+            let thread_pool = ThreadPool::new("shared_partitioned", 2);
+            let (doubled, negated) = std::thread::scope(|s| {
+                let doubled = s.spawn(|| Arc::new(Double).run(&thread_pool, &shared)());
+                let negated = s.spawn(|| Arc::new(Negate).run(&thread_pool, &shared)());
+                (doubled.join().unwrap(), negated.join().unwrap())
+            });
+            let doubled: Vec<_> = doubled.0.into_iter().flatten().collect();
+            let negated: Vec<_> = negated.0.into_iter().flatten().collect();
+            for ((e1, c1), (e2, c2)) in std::iter::zip(doubled.iter(), negated.iter()) {
+                assert_eq!(e1, e2);
+                assert!(matches!(c1, ComponentChange::Value(v) if *v == *e1 as u64 * 2));
+                assert!(
+                    matches!(c2, ComponentChange::Value(v) if *v == (*e2 as u64).wrapping_neg())
+                );
+            }
+            assert_eq!(11, doubled.len());
+            assert_eq!(11, negated.len());
+        }
+    }
+
+    mod parallel_over {
+        use std::sync::Arc;
+
+        use crate::{parallel_over, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{ComponentChange, CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{Partitioned, PartitioningScheme, VecPartitioningScheme};
+
+        struct Collatz;
+
+        system_parallel! {
+            Collatz<u64> {
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Collatz {
+            fn process(&self, _: u64, value: &mut CopyOnWriteComponentRef<u64>) {
+                collatz(value);
+            }
+        }
+
+        fn collatz(value: &mut CopyOnWriteComponentRef<u64>) {
+            match **value {
+                1 => value.unbind(),
+                v if v & 1 == 0 => value.update(|v| *v /= 2),
+                _ => value.update(|v| *v = *v * 3 + 1),
+            }
+        }
+
+        #[test]
+        fn matches_system_parallel() {
+            let partitioning: Arc<dyn PartitioningScheme<u64>> =
+                Arc::new(VecPartitioningScheme::from(vec![100, 200, 300]));
+            let values =
+                CopyOnWriteComponentCollection::from_iter((1..=1000u64).map(|e| (e, e % 64 + 1)));
+            let mut by_system = Partitioned::from(&partitioning, values.partition(&*partitioning));
+            let values =
+                CopyOnWriteComponentCollection::from_iter((1..=1000u64).map(|e| (e, e % 64 + 1)));
+            let mut by_closure = Partitioned::from(&partitioning, values.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("parallel_over", 4);
+            while !by_system.is_empty() {
+                let (changes,) = Arc::new(Collatz).run(&thread_pool, &by_system)();
+                by_system.apply(changes);
+                let changes = parallel_over(&thread_pool, &by_closure, |_, value| collatz(value));
+                by_closure.apply(changes);
+                assert_eq!(by_system.len(), by_closure.len());
+                for e in 1..=1000u64 {
+                    assert_eq!(by_system.get_cloned(e), by_closure.get_cloned(e));
+                }
+            }
+            assert!(by_closure.is_empty());
+            let changes = parallel_over(&thread_pool, &by_closure, |_, value| collatz(value));
+            assert!(changes.iter().all(Vec::is_empty));
+            thread_pool.shutdown();
+        }
+
+        #[test]
+        fn inline_thread_pool() {
+            let partitioning: Arc<dyn PartitioningScheme<u64>> =
+                Arc::new(VecPartitioningScheme::from(vec![100, 200, 300]));
+            let values = || {
+                // NOTE(rescrv):  More entities than MIN_ENTITIES_FOR_PARALLEL, so the pool gets used.
+                let values = CopyOnWriteComponentCollection::from_iter(
+                    (1..=4000u64).map(|e| (e, e % 64 + 1)),
+                );
+                Partitioned::from(&partitioning, values.partition(&*partitioning))
+            };
+            let mut threaded = values();
+            let mut inline = values();
+            let thread_pool = ThreadPool::new("inline_thread_pool", 4);
+            let inline_pool = ThreadPool::new("inline_thread_pool", 0);
+            while !threaded.is_empty() {
+                let (changes,) = Arc::new(Collatz).run(&thread_pool, &threaded)();
+                threaded.apply(changes);
+                let (changes,) = Arc::new(Collatz).run(&inline_pool, &inline)();
+                inline.apply(changes);
+                assert_eq!(threaded.len(), inline.len());
+                for e in 1..=4000u64 {
+                    assert_eq!(threaded.get_cloned(e), inline.get_cloned(e));
+                }
+            }
+            assert!(inline.is_empty());
+            thread_pool.shutdown();
+            inline_pool.shutdown();
+        }
+
+        #[test]
+        fn no_change_is_elided() {
+            let partitioning: Arc<dyn PartitioningScheme<u64>> =
+                Arc::new(VecPartitioningScheme::from(vec![u64::MAX]));
+            let values =
+                CopyOnWriteComponentCollection::from_iter(vec![(1u64, 1u64), (u64::MAX, 2)]);
+            let values = Partitioned::from(&partitioning, values.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("parallel_over", 2);
+            let changes = parallel_over(&thread_pool, &values, |e, value| {
+                if e == u64::MAX {
+                    value.update(|v| *v += 1);
+                }
+            });
+            let changes = changes.into_inner();
+            assert!(changes[0].is_empty());
+            assert_eq!(1, changes[1].len());
+            assert!(matches!(
+                changes[1][0],
+                (u64::MAX, ComponentChange::Value(3))
+            ));
+            thread_pool.shutdown();
+        }
+    }
+
+    mod full_range {
+        use std::sync::Arc;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{Partitioned, PartitioningScheme, VecPartitioningScheme};
+
+        struct Visit;
+
+        system! {
+            Visit<u64> {
+                visits: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Visit {
+            fn process(&self, _: u64, visits: &mut CopyOnWriteComponentRef<u64>) {
+                visits.update(|v| *v += 1);
+            }
+        }
+
+        struct VisitParallel;
+
+        system_parallel! {
+            #[min_parallel = 0]
+            VisitParallel<u64> {
+                visits: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl VisitParallel {
+            fn process(&self, _: u64, visits: &mut CopyOnWriteComponentRef<u64>) {
+                visits.update(|v| *v += 1);
+            }
+        }
+
+        const ENTITIES: [u64; 4] = [0, 1, u64::MAX - 1, u64::MAX];
+
+        #[test]
+        fn system_visits_default_and_max_once() {
+            let mut visits = CopyOnWriteComponentCollection::from_iter(ENTITIES.map(|e| (e, 0u64)));
+            let (changes,) = Visit.run(&mut visits);
+            visits.apply(changes);
+            assert_eq!(
+                ENTITIES.map(|e| (e, 1)).to_vec(),
+                visits.consume().collect::<Vec<_>>()
+            );
+            let mut visits = CopyOnWriteComponentCollection::from_iter(ENTITIES.map(|e| (e, 0u64)));
+            let (changes,) = Visit.run_subset(&ENTITIES, &mut visits);
+            assert_eq!(4, changes.len());
+        }
+
+        #[test]
+        fn system_parallel_visits_default_and_max_once() {
+            let partitioning: Arc<dyn PartitioningScheme<u64>> =
+                Arc::new(VecPartitioningScheme::from(vec![1, u64::MAX]));
+            let visits = CopyOnWriteComponentCollection::from_iter(ENTITIES.map(|e| (e, 0u64)));
+            let mut visits = Partitioned::from(&partitioning, visits.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("full_range", 2);
+            let (changes,) = Arc::new(VisitParallel).run(&thread_pool, &visits)();
+            visits.apply(changes);
+            assert_eq!(
+                ENTITIES.map(|e| (e, 1)).to_vec(),
+                visits.consume().collect::<Vec<_>>()
+            );
+            thread_pool.shutdown();
+        }
+    }
+
+    mod parallel_subset {
+        use std::sync::{Arc, Mutex};
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{MutableComponentCollection, MutableComponentRef};
+        use crate::{Partitioned, PartitioningScheme, VecPartitioningScheme};
+
+        fn scale(weight: &mut MutableComponentRef<u64>, value: &mut CopyOnWriteComponentRef<u64>) {
+            let weight = **weight;
+            value.update(|v| *v *= weight);
+        }
+
+        struct Scale;
+
+        system! {
+            Scale<u128> {
+                weights: MutableComponentCollection<u64>,
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Scale {
+            fn process(
+                &self,
+                _: u128,
+                weight: &mut MutableComponentRef<u64>,
+                value: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                scale(weight, value);
+            }
+        }
+
+        struct ScaleParallel;
+
+        system_parallel! {
+            #[min_parallel = 16]
+            ScaleParallel<u128> {
+                weights: MutableComponentCollection<u64>,
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl ScaleParallel {
+            fn process(
+                &self,
+                _: u128,
+                weight: &mut MutableComponentRef<u64>,
+                value: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                scale(weight, value);
+            }
+        }
+
+        fn weights() -> MutableComponentCollection<u128, u64> {
+            MutableComponentCollection::from_iter(
+                (1..=1000u128)
+                    .filter(|e| e % 7 != 0)
+                    .map(|e| (e, (e % 5) as u64)),
+            )
+        }
+
+        fn values() -> CopyOnWriteComponentCollection<u128, u64> {
+            CopyOnWriteComponentCollection::from_iter((1..=1000u128).map(|e| (e, e as u64)))
+        }
+
+        #[test]
+        fn matches_run_subset() {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![250, 500, 750]));
+            let thread_pool = ThreadPool::new("parallel_subset", 4);
+            for len in [5, 500] {
+                // Unsorted, with entities missing from weights and from both collections.
+                let mut subset: Vec<u128> =
+                    (0..len as u128).map(|x| (x * 389) % 1200 + 1).collect();
+                subset.reverse();
+                let mut expected_weights = weights();
+                let mut expected_values = values();
+                let mut sorted = subset.clone();
+                sorted.sort();
+                let (_, expected) =
+                    Scale.run_subset(&sorted, &mut expected_weights, &mut expected_values);
+                let weights = Partitioned::from(&partitioning, weights().partition(&*partitioning));
+                let values = Partitioned::from(&partitioning, values().partition(&*partitioning));
+                let (weight_changes, value_changes) =
+                    ScaleParallel.run_parallel_subset(&thread_pool, &subset, &weights, &values);
+                assert!(weight_changes.iter().all(Vec::is_empty));
+                assert_eq!(4, value_changes.len());
+                for (partition, changes) in value_changes.iter().enumerate() {
+                    for (e, _) in changes.iter() {
+                        assert_eq!(partition, partitioning.partition_of(*e));
+                    }
+                }
+                let value_changes: Vec<_> = value_changes.into_iter().flatten().collect();
+                assert_eq!(expected.len(), value_changes.len());
+                for ((e1, c1), (e2, c2)) in std::iter::zip(expected.iter(), value_changes.iter()) {
+                    assert_eq!(e1, e2);
+                    match (c1, c2) {
+                        (ComponentChange::Value(v1), ComponentChange::Value(v2)) => {
+                            assert_eq!(v1, v2)
+                        }
+                        _ => panic!("expected values"),
+                    }
+                }
+            }
+            // The whole collection for comparison.
+            let (_, expected) = Scale.run(&mut weights(), &mut values());
+            let weights = Partitioned::from(&partitioning, weights().partition(&*partitioning));
+            let values = Partitioned::from(&partitioning, values().partition(&*partitioning));
+            let (_, value_changes) = Arc::new(ScaleParallel).run(&thread_pool, &weights, &values)();
+            assert_eq!(expected.len(), value_changes.into_iter().flatten().count());
+            thread_pool.shutdown();
+        }
+
+        struct Record {
+            processed: Mutex<Vec<u128>>,
+        }
+
+        system_parallel! {
+            #[min_parallel = 0]
+            Record<u128> {
+                weights: MutableComponentCollection<u64>,
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Record {
+            fn process(
+                &self,
+                entity: u128,
+                weight: &mut MutableComponentRef<u64>,
+                value: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                self.processed.lock().unwrap().push(entity);
+                scale(weight, value);
+            }
+        }
+
+        #[test]
+        fn sharded_run_subset() {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![250, 500, 750]));
+            let thread_pool = ThreadPool::new("sharded_run_subset", 4);
+            let weights = Partitioned::from(&partitioning, weights().partition(&*partitioning));
+            let values = Partitioned::from(&partitioning, values().partition(&*partitioning));
+            // Dividers, entities missing from weights, and entities past the last binding.
+            let subset: Vec<u128> = vec![1, 7, 249, 250, 251, 500, 700, 749, 750, 1000, 1001, 1100];
+            let record = Arc::new(Record {
+                processed: Mutex::new(vec![]),
+            });
+            let (weight_changes, value_changes) =
+                record.run_parallel_subset(&thread_pool, &subset, &weights, &values);
+            let mut processed = record.processed.lock().unwrap().clone();
+            processed.sort();
+            let expected: Vec<u128> = subset
+                .iter()
+                .copied()
+                .filter(|e| *e <= 1000 && e % 7 != 0)
+                .collect();
+            assert_eq!(expected, processed);
+            assert!(weight_changes.iter().all(Vec::is_empty));
+            assert_eq!(4, value_changes.len());
+            let mut changed = vec![];
+            for (partition, changes) in value_changes.iter().enumerate() {
+                for (e, change) in changes.iter() {
+                    assert_eq!(partition, partitioning.partition_of(*e));
+                    match change {
+                        ComponentChange::Value(v) => assert_eq!((*e as u64) * (*e as u64 % 5), *v),
+                        _ => panic!("expected values"),
+                    }
+                    changed.push(*e);
+                }
+            }
+            assert_eq!(expected, changed);
+            let mut values = values;
+            values.apply(value_changes);
+            assert_eq!(Some(249 * 4), values.get_cloned(249));
+            let (_, value_changes) =
+                record.run_parallel_subset(&thread_pool, &[], &weights, &values);
+            assert!(value_changes.iter().all(Vec::is_empty));
+            // Dispatch to the thread pool, too.
+            record.processed.lock().unwrap().clear();
+            let subset: Vec<u128> = (1..=1000u128).step_by(3).collect();
+            let (_, value_changes) =
+                record.run_parallel_subset(&thread_pool, &subset, &weights, &values);
+            let expected: Vec<u128> = subset.iter().copied().filter(|e| e % 7 != 0).collect();
+            assert_eq!(expected.len(), record.processed.lock().unwrap().len());
+            assert_eq!(
+                expected,
+                value_changes
+                    .into_iter()
+                    .flatten()
+                    .map(|(e, _)| e)
+                    .collect::<Vec<_>>()
+            );
+            // And the whole collection for comparison.
+            record.processed.lock().unwrap().clear();
+            let _ = Arc::clone(&record).run(&thread_pool, &weights, &values)();
+            assert_eq!(weights.len(), record.processed.lock().unwrap().len());
+            thread_pool.shutdown();
+        }
+
+        #[test]
+        fn sharded_run_subset_unsorted() {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![500]));
+            let thread_pool = ThreadPool::new("sharded_run_subset_unsorted", 0);
+            let weights = Partitioned::from(&partitioning, weights().partition(&*partitioning));
+            let values = Partitioned::from(&partitioning, values().partition(&*partitioning));
+            let record = Record {
+                processed: Mutex::new(vec![]),
+            };
+            let (_, value_changes) =
+                record.run_parallel_subset(&thread_pool, &[501, 2, 1, 2], &weights, &values);
+            let mut processed = record.processed.lock().unwrap().clone();
+            processed.sort();
+            assert_eq!(vec![1, 2, 501], processed);
+            let changed: Vec<Vec<u128>> = value_changes
+                .iter()
+                .map(|changes| changes.iter().map(|(e, _)| *e).collect())
+                .collect();
+            assert_eq!(vec![vec![1, 2], vec![501]], changed);
+        }
+    }
+
+    mod min_parallel {
+        use std::collections::HashSet;
+        use std::sync::{Arc, Mutex};
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{Partitioned, PartitionedChanges, PartitioningScheme, VecPartitioningScheme};
+
+        #[derive(Default)]
+        struct Threads(Mutex<HashSet<Option<String>>>);
+
+        impl Threads {
+            fn record(&self) {
+                let name = std::thread::current().name().map(String::from);
+                self.0.lock().unwrap().insert(name);
+            }
+
+            fn on_pool(&self) -> bool {
+                let threads = self.0.lock().unwrap();
+                !threads.is_empty()
+                    && threads.iter().all(|t| {
+                        t.as_ref()
+                            .map(|t| t.starts_with("min_parallel:"))
+                            .unwrap_or(false)
+                    })
+            }
+        }
+
+        #[derive(Default)]
+        struct Default1024(Threads);
+
+        system_parallel! {
+            Default1024<u128> {
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Default1024 {
+            fn process(&self, _: u128, value: &mut CopyOnWriteComponentRef<u64>) {
+                self.0.record();
+                value.update(|v| *v += 1);
+            }
+        }
+
+        #[derive(Default)]
+        struct Threshold16(Threads);
+
+        system_parallel! {
+            #[min_parallel = 16]
+            Threshold16<u128> {
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Threshold16 {
+            fn process(&self, _: u128, value: &mut CopyOnWriteComponentRef<u64>) {
+                self.0.record();
+                value.update(|v| *v += 1);
+            }
+        }
+
+        fn values(
+            count: u128,
+        ) -> Partitioned<u128, u64, CopyOnWriteComponentCollection<u128, u64>> {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![count / 2]));
+            let values =
+                CopyOnWriteComponentCollection::from_iter((1..=count).map(|e| (e, e as u64)));
+            Partitioned::from(&partitioning, values.partition(&*partitioning))
+        }
+
+        fn flatten(changes: PartitionedChanges<u128, u64>) -> Vec<(u128, u64)> {
+            changes
+                .into_iter()
+                .flatten()
+                .map(|(e, c)| match c {
+                    ComponentChange::Value(v) => (e, v),
+                    _ => panic!("expected a value"),
+                })
+                .collect()
+        }
+
+        #[test]
+        fn threshold() {
+            assert_eq!(1024, Default1024::MIN_ENTITIES_FOR_PARALLEL);
+            assert_eq!(16, Threshold16::MIN_ENTITIES_FOR_PARALLEL);
+            let thread_pool = ThreadPool::new("min_parallel", 2);
+            for (count, parallel) in [(15u128, false), (16, true), (64, true)] {
+                let values = values(count);
+                let serial = Arc::new(Default1024::default());
+                let (serial_changes,) = Arc::clone(&serial).run(&thread_pool, &values)();
+                assert!(!serial.0.on_pool());
+                let threshold = Arc::new(Threshold16::default());
+                let (threshold_changes,) = Arc::clone(&threshold).run(&thread_pool, &values)();
+                assert_eq!(parallel, threshold.0.on_pool());
+                let serial_changes = flatten(serial_changes);
+                assert_eq!(count as usize, serial_changes.len());
+                assert_eq!(serial_changes, flatten(threshold_changes));
+            }
+        }
+    }
+
+    mod apply_after_run {
+        use std::sync::Arc;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{Partitioned, PartitioningScheme, VecPartitioningScheme};
+
+        struct Increment;
+
+        system_parallel! {
+            Increment<u128> {
+                values: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Increment {
+            fn process(&self, _: u128, value: &mut CopyOnWriteComponentRef<u64>) {
+                value.update(|v| *v += 1);
+            }
+        }
+
+        #[test]
+        fn partitions_released_before_wait_returns() {
+            const ROUNDS: u64 = 256;
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![16, 32, 48]));
+            let values = CopyOnWriteComponentCollection::from_iter((1..64u128).map(|e| (e, 0u64)));
+            let mut values = Partitioned::from(&partitioning, values.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("apply_after_run", 4);
+            for _ in 0..ROUNDS {
+                // NOTE(rescrv):  apply panics if a work unit still holds its partition.
+                let (changes,) = Arc::new(Increment).run(&thread_pool, &values)();
+                values.apply(changes);
+            }
+            for entity in 1..64u128 {
+                assert_eq!(ROUNDS, *values.get_ref(entity).unwrap());
+            }
+        }
+    }
+
+    mod lock_order {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{MutableComponentCollection, MutableComponentRef};
+        use crate::{
+            NopPartitioningScheme, Partitioned, PartitioningScheme, VecPartitioningScheme,
+        };
+
+        struct Forward;
+
+        system_parallel! {
+            Forward<u128> {
+                a: MutableComponentCollection<u64>,
+                b: MutableComponentCollection<u64>,
+            }
+        }
+
+        impl Forward {
+            fn process(
+                &self,
+                _: u128,
+                a: &mut MutableComponentRef<u64>,
+                b: &mut MutableComponentRef<u64>,
+            ) {
+                a.update(|a| *a += 1);
+                b.update(|b| *b += 1);
+            }
+        }
+
+        struct Backward;
+
+        system_parallel! {
+            Backward<u128> {
+                b: MutableComponentCollection<u64>,
+                a: MutableComponentCollection<u64>,
+            }
+        }
+
+        impl Backward {
+            fn process(
+                &self,
+                _: u128,
+                b: &mut MutableComponentRef<u64>,
+                a: &mut MutableComponentRef<u64>,
+            ) {
+                b.update(|b| *b += 1);
+                a.update(|a| *a += 1);
+            }
+        }
+
+        #[test]
+        fn opposite_argument_order() {
+            const ENTITIES: u128 = 1024;
+            const ROUNDS: u64 = 64;
+            let partitioning: Arc<dyn PartitioningScheme<u128>> = Arc::new(NopPartitioningScheme);
+            let a = MutableComponentCollection::from_iter((1..=ENTITIES).map(|e| (e, 0u64)));
+            let b = MutableComponentCollection::from_iter((1..=ENTITIES).map(|e| (e, 0u64)));
+            let a = Partitioned::from(&partitioning, a.partition(&*partitioning));
+            let b = Partitioned::from(&partitioning, b.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("lock_order", 2);
+            for _ in 0..ROUNDS {
+                let forward = Arc::new(Forward).run(&thread_pool, &a, &b);
+                let backward = Arc::new(Backward).run(&thread_pool, &b, &a);
+                let (changes_a, changes_b) = forward();
+                assert!(changes_a
+                    .iter()
+                    .chain(changes_b.iter())
+                    .all(|x| x.is_empty()));
+                let (changes_b, changes_a) = backward();
+                assert!(changes_a
+                    .iter()
+                    .chain(changes_b.iter())
+                    .all(|x| x.is_empty()));
+            }
+            for entity in 1..=ENTITIES {
+                assert_eq!(2 * ROUNDS, *a.get_ref(entity).unwrap());
+                assert_eq!(2 * ROUNDS, *b.get_ref(entity).unwrap());
+            }
+        }
+
+        struct Increment {
+            processed: AtomicUsize,
+        }
+
+        system_parallel! {
+            #[min_parallel = 0]
+            #[count_entities = processed]
+            Increment<u128> {
+                a: MutableComponentCollection<u64>,
+            }
+        }
+
+        impl Increment {
+            fn process(&self, entity: u128, a: &mut MutableComponentRef<u64>) {
+                a.update(|a| *a += entity as u64);
+            }
+        }
+
+        #[test]
+        fn mutable_partitions_in_place() {
+            const ENTITIES: u128 = 4096;
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![512, 1024, 2048, 3072]));
+            let a = MutableComponentCollection::from_iter((1..=ENTITIES).map(|e| (e, 0u64)));
+            let a = Partitioned::from(&partitioning, a.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("mutable_partitions", 4);
+            let system = Arc::new(Increment {
+                processed: AtomicUsize::new(0),
+            });
+            for _ in 0..4 {
+                let (changes,) = Arc::clone(&system).run(&thread_pool, &a)();
+                assert_eq!(5, changes.len());
+                assert!(changes.iter().all(|x| x.is_empty()));
+                assert_eq!(ENTITIES as usize, system.processed.load(Ordering::Relaxed));
+            }
+            for entity in 1..=ENTITIES {
+                assert_eq!(4 * entity as u64, *a.get_ref(entity).unwrap());
+            }
+            thread_pool.shutdown();
+        }
+    }
+
+    mod world {
+        use std::sync::Arc;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{MutableComponentCollection, MutableComponentRef};
+        use crate::{PartitioningScheme, VecPartitioningScheme};
+
+        world! {
+            Demo<u64> partitioned PartitionedDemo {
+                position: CopyOnWriteComponentCollection<i64>,
+                velocity: MutableComponentCollection<i64>,
+            }
+        }
+
+        struct Move;
+
+        system! {
+            Move<u64> {
+                position: CopyOnWriteComponentCollection<i64>,
+                velocity: MutableComponentCollection<i64>,
+            }
+        }
+
+        impl Move {
+            fn process(
+                &self,
+                _: u64,
+                position: &mut CopyOnWriteComponentRef<i64>,
+                velocity: &mut MutableComponentRef<i64>,
+            ) {
+                position.update(|p| *p += **velocity);
+                if **position >= 100 {
+                    velocity.unbind();
+                }
+            }
+        }
+
+        struct MoveParallel;
+
+        system_parallel! {
+            #[min_parallel = 0]
+            MoveParallel<u64> {
+                position: CopyOnWriteComponentCollection<i64>,
+                velocity: MutableComponentCollection<i64>,
+            }
+        }
+
+        impl MoveParallel {
+            fn process(
+                &self,
+                e: u64,
+                position: &mut CopyOnWriteComponentRef<i64>,
+                velocity: &mut MutableComponentRef<i64>,
+            ) {
+                Move.process(e, position, velocity)
+            }
+        }
+
+        fn demo() -> Demo {
+            Demo {
+                position: CopyOnWriteComponentCollection::from_iter((1..=64u64).map(|e| (e, 0i64))),
+                velocity: MutableComponentCollection::from_iter((1..=64u64).map(|e| (e, e as i64))),
+            }
+        }
+
+        type Bindings = Vec<(u64, i64)>;
+
+        fn expected(rounds: i64) -> (Bindings, Bindings) {
+            let mut positions = vec![];
+            let mut velocities = vec![];
+            for e in 1..=64i64 {
+                let mut position = 0;
+                let mut moving = true;
+                for _ in 0..rounds {
+                    if moving {
+                        position += e;
+                        moving = position < 100;
+                    }
+                }
+                positions.push((e as u64, position));
+                if moving {
+                    velocities.push((e as u64, e));
+                }
+            }
+            (positions, velocities)
+        }
+
+        #[test]
+        fn apply_all() {
+            let mut demo = demo();
+            for _ in 0..3 {
+                let changes = Move.run(&mut demo.position, &mut demo.velocity);
+                demo.apply_all(changes);
+            }
+            let (positions, velocities) = expected(3);
+            assert_eq!(positions, demo.position.consume().collect::<Vec<_>>());
+            assert_eq!(velocities, demo.velocity.consume().collect::<Vec<_>>());
+            let mut demo = self::demo();
+            let (positions, velocities) =
+                Move.run_subset(&[1, 64], &mut demo.position, &mut demo.velocity);
+            assert_eq!(2, positions.len());
+            assert!(velocities.is_empty());
+        }
+
+        #[test]
+        fn partition_all_then_apply_all() {
+            let partitioning: Arc<dyn PartitioningScheme<u64>> =
+                Arc::new(VecPartitioningScheme::from(vec![16, 32, 48]));
+            let mut demo = demo().partition_all(&partitioning);
+            let thread_pool = ThreadPool::new("world", 2);
+            for _ in 0..3 {
+                let changes =
+                    Arc::new(MoveParallel).run(&thread_pool, &demo.position, &demo.velocity)();
+                demo.apply_all(changes);
+            }
+            let (positions, velocities) = expected(3);
+            assert_eq!(positions, demo.position.consume().collect::<Vec<_>>());
+            assert_eq!(velocities, demo.velocity.consume().collect::<Vec<_>>());
+            thread_pool.shutdown();
+        }
+    }
+
+    mod preview {
+        use std::collections::BTreeSet;
+
+        use proptest::collection::btree_set;
+
+        use super::arb_entity;
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{InsertOptimizedComponentCollection, InsertOptimizedComponentRef};
+        use crate::{MutableComponentCollection, MutableComponentRef};
+
+        struct Preview;
+
+        system! {
+            Preview<u128> {
+                a: CopyOnWriteComponentCollection<()>,
+                b: MutableComponentCollection<()>,
+                c: InsertOptimizedComponentCollection<()>,
+            }
+        }
+
+        impl Preview {
+            fn process(
+                &self,
+                _: u128,
+                _: &mut CopyOnWriteComponentRef<()>,
+                _: &mut MutableComponentRef<()>,
+                _: &mut InsertOptimizedComponentRef<u128, ()>,
+            ) {
+                panic!("preview_entities should not process");
+            }
+        }
+
+        #[test]
+        fn run_without_entities() {
+            let mut a = CopyOnWriteComponentCollection::default();
+            let mut b = MutableComponentCollection::default();
+            let mut c = InsertOptimizedComponentCollection::default();
+            let _ = Preview.run(&mut a, &mut b, &mut c);
+            let changes = Preview.run_subset(&[], &mut a, &mut b, &mut c);
+            assert!(changes.0.is_empty() && changes.1.is_empty() && changes.2.is_empty());
+        }
+
+        proptest::proptest! {
+            #[test]
+            fn preview_is_intersection(
+                a in btree_set(arb_entity(), 0..256),
+                b in btree_set(arb_entity(), 0..256),
+                c in btree_set(arb_entity(), 0..256),
+                shared in btree_set(arb_entity(), 0..64),
+                extremes in proptest::bool::ANY,
+            ) {
+                let mut shared = shared;
+                if extremes {
+                    shared.insert(0);
+                    shared.insert(u128::MAX);
+                }
+                let bind = |entities: &BTreeSet<u128>| entities.union(&shared).map(|e| (*e, ())).collect::<Vec<_>>();
+                let ca = CopyOnWriteComponentCollection::from_iter(bind(&a));
+                let cb = MutableComponentCollection::from_iter(bind(&b));
+                let cc = InsertOptimizedComponentCollection::from_iter(bind(&c));
+                let expected: Vec<u128> = a.union(&shared).copied().collect::<BTreeSet<_>>()
+                    .intersection(&b.union(&shared).copied().collect())
+                    .copied().collect::<BTreeSet<_>>()
+                    .intersection(&c.union(&shared).copied().collect())
+                    .copied().collect();
+                proptest::prop_assert_eq!(expected, Preview.preview_entities(&ca, &cb, &cc));
+            }
+        }
+    }
+
+    mod filter {
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{MutableComponentCollection, MutableComponentRef};
+
+        struct Drain;
+
+        system! {
+            #[filter = |_, health, regen| *health > 0 && *regen > 0]
+            Drain<u64> {
+                health: CopyOnWriteComponentCollection<u64>,
+                regen: MutableComponentCollection<u64>,
+            }
+        }
+
+        impl Drain {
+            fn process(
+                &self,
+                _: u64,
+                health: &mut CopyOnWriteComponentRef<u64>,
+                regen: &mut MutableComponentRef<u64>,
+            ) {
+                assert!(**health > 0 && **regen > 0);
+                health.update(|h| *h -= 1);
+                regen.unbind();
+            }
+        }
+
+        fn collections() -> (
+            CopyOnWriteComponentCollection<u64, u64>,
+            MutableComponentCollection<u64, u64>,
+        ) {
+            let health = CopyOnWriteComponentCollection::from_iter((1..=20u64).map(|e| (e, e % 3)));
+            let regen = MutableComponentCollection::from_iter((1..=20u64).map(|e| (e, e % 4)));
+            (health, regen)
+        }
+
+        #[test]
+        fn filtered_entities_produce_no_changes() {
+            let expected: Vec<u64> = (1..=20u64).filter(|e| e % 3 > 0 && e % 4 > 0).collect();
+            let (mut health, mut regen) = collections();
+            assert_eq!(expected, Drain.preview_entities(&health, &regen));
+            let (health_changes, regen_changes) = Drain.run(&mut health, &mut regen);
+            assert_eq!(
+                expected,
+                health_changes.iter().map(|(e, _)| *e).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                expected,
+                regen_changes.iter().map(|(e, _)| *e).collect::<Vec<_>>()
+            );
+            assert!(health_changes
+                .iter()
+                .all(|(e, c)| matches!(c, ComponentChange::Value(h) if *h == e % 3 - 1)));
+            assert!(regen_changes
+                .iter()
+                .all(|(_, c)| matches!(c, ComponentChange::Unbind)));
+            let all: Vec<u64> = (1..=20u64).collect();
+            let (health_changes, _) = Drain.run_subset(&all, &mut health, &mut regen);
+            assert_eq!(
+                expected,
+                health_changes.iter().map(|(e, _)| *e).collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn filtered_entities_do_not_count_against_limit() {
+            let (mut health, mut regen) = collections();
+            let (health_changes, _, resume) = Drain.run_limited(2, &mut health, &mut regen);
+            assert_eq!(
+                vec![1, 2],
+                health_changes.iter().map(|(e, _)| *e).collect::<Vec<_>>()
+            );
+            assert_eq!(Some(3), resume);
+            let (health_changes, _, resume) = Drain.run_resumed(3, 2, &mut health, &mut regen);
+            assert_eq!(
+                vec![5, 7],
+                health_changes.iter().map(|(e, _)| *e).collect::<Vec<_>>()
+            );
+            assert_eq!(Some(8), resume);
+        }
+    }
+
+    mod ordered {
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{OrderedComponentCollection, OrderedComponentRef};
+
+        struct Halve;
+
+        system! {
+            Halve<u64> {
+                values: OrderedComponentCollection<u64>,
+            }
+        }
+
+        impl Halve {
+            fn process(&self, _: u64, value: &mut OrderedComponentRef<u64, u64>) {
+                if **value & 1 == 1 {
+                    value.unbind();
+                } else {
+                    value.update(|v| *v /= 2);
+                }
+            }
+        }
+
+        #[test]
+        fn in_place_and_unbind() {
+            let mut values = OrderedComponentCollection::from_iter((1..=8u64).map(|e| (e, e * 4)));
+            for _ in 0..3 {
+                let (changes,) = Halve.run(&mut values);
+                values.apply(changes);
+            }
+            assert_eq!(
+                vec![(2, 1), (4, 2), (6, 3), (8, 4)],
+                values.consume().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    mod wide {
+        use std::collections::BTreeSet;
+
+        use proptest::collection::btree_set;
+
+        use super::arb_entity;
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{InsertOptimizedComponentCollection, InsertOptimizedComponentRef};
+        use crate::{MutableComponentCollection, MutableComponentRef};
+
+        struct Wide;
+
+        system! {
+            Wide<u128> {
+                a: CopyOnWriteComponentCollection<u64>,
+                b: MutableComponentCollection<u64>,
+                c: InsertOptimizedComponentCollection<u64>,
+                d: CopyOnWriteComponentCollection<u64>,
+                e: MutableComponentCollection<u64>,
+                f: InsertOptimizedComponentCollection<u64>,
+            }
+        }
+
+        impl Wide {
+            #[allow(clippy::too_many_arguments)]
+            fn process(
+                &self,
+                _: u128,
+                a: &mut CopyOnWriteComponentRef<u64>,
+                b: &mut MutableComponentRef<u64>,
+                c: &mut InsertOptimizedComponentRef<u128, u64>,
+                d: &mut CopyOnWriteComponentRef<u64>,
+                e: &mut MutableComponentRef<u64>,
+                f: &mut InsertOptimizedComponentRef<u128, u64>,
+            ) {
+                let sum = **a + **b + **c + **d + **e + **f;
+                a.update(|x| *x = sum);
+            }
+        }
+
+        proptest::proptest! {
+            #[test]
+            fn matches_naive_intersection(
+                sets in proptest::collection::vec(btree_set(arb_entity(), 0..256), 6),
+                shared in btree_set(arb_entity(), 0..64),
+                extremes in proptest::bool::ANY,
+            ) {
+                let mut shared = shared;
+                if extremes {
+                    shared.insert(0);
+                    shared.insert(u128::MAX);
+                }
+                let sets: Vec<BTreeSet<u128>> = sets.into_iter().map(|s| s.union(&shared).copied().collect()).collect();
+                let bind = |idx: usize| sets[idx].iter().map(|e| (*e, idx as u64 + 1)).collect::<Vec<_>>();
+                let mut a = CopyOnWriteComponentCollection::from_iter(bind(0));
+                let mut b = MutableComponentCollection::from_iter(bind(1));
+                let mut c = InsertOptimizedComponentCollection::from_iter(bind(2));
+                let mut d = CopyOnWriteComponentCollection::from_iter(bind(3));
+                let mut e = MutableComponentCollection::from_iter(bind(4));
+                let mut f = InsertOptimizedComponentCollection::from_iter(bind(5));
+                // The naive implementation:  every entity of the first set that is in all others.
+                let expected: Vec<(u128, u64)> = sets[0].iter()
+                    .filter(|x| sets[1..].iter().all(|s| s.contains(x)))
+                    .map(|x| (*x, 21))
+                    .collect();
+                let preview = Wide.preview_entities(&a, &b, &c, &d, &e, &f);
+                proptest::prop_assert_eq!(expected.iter().map(|(x, _)| *x).collect::<Vec<_>>(), preview);
+                let (changes, _, _, _, _, _) = Wide.run(&mut a, &mut b, &mut c, &mut d, &mut e, &mut f);
+                let changes: Vec<(u128, u64)> = changes.into_iter().map(|(x, change)| match change {
+                    ComponentChange::Value(v) => (x, v),
+                    _ => panic!("expected a value"),
+                }).collect();
+                proptest::prop_assert_eq!(expected, changes);
+                let changes = Wide.run_subset(&[], &mut a, &mut b, &mut c, &mut d, &mut e, &mut f);
+                proptest::prop_assert!(changes.0.is_empty() && changes.1.is_empty() && changes.2.is_empty());
+                proptest::prop_assert!(changes.3.is_empty() && changes.4.is_empty() && changes.5.is_empty());
+            }
+        }
+    }
+
+    mod changes {
+        use crate::{ComponentChange, ComponentCollection, MutableComponentCollection};
+
+        #[test]
+        fn constructors() {
+            assert!(matches!(
+                ComponentChange::value(5u64),
+                ComponentChange::Value(5)
+            ));
+            assert!(matches!(
+                ComponentChange::<u64>::unbind(),
+                ComponentChange::Unbind
+            ));
+            assert!(matches!(
+                ComponentChange::from(5u64),
+                ComponentChange::Value(5)
+            ));
+        }
+
+        #[test]
+        fn sorted_for_apply() {
+            let changes: Vec<(u128, ComponentChange<u64>)> = changes![];
+            assert!(changes.is_empty());
+            let mut collection =
+                MutableComponentCollection::from_iter(vec![(1u128, 1u64), (2, 2), (4, 4)]);
+            let changes = changes![
+                (4 => unbind),
+                (3 => 30 + 3),
+                (1 => unbind),
+                (5 => 5),
+                (1 => 10),
+                (5 => unbind),
+            ];
+            assert_eq!(
+                vec![1, 3, 4, 5],
+                changes.iter().map(|(e, _)| *e).collect::<Vec<_>>()
+            );
+            collection.apply(changes);
+            assert_eq!(
+                vec![(1, 10), (2, 2), (3, 33)],
+                collection.consume().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    mod count_entities {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{MutableComponentCollection, MutableComponentRef};
+        use crate::{Partitioned, PartitioningScheme, VecPartitioningScheme};
+
+        struct Count {
+            processed: AtomicUsize,
+        }
+
+        system_parallel! {
+            #[count_entities = processed]
+            Count<u128> {
+                a: CopyOnWriteComponentCollection<u64>,
+                b: MutableComponentCollection<u64>,
+            }
+        }
+
+        impl Count {
+            fn process(
+                &self,
+                _: u128,
+                _: &mut CopyOnWriteComponentRef<u64>,
+                _: &mut MutableComponentRef<u64>,
+            ) {
+            }
+        }
+
+        struct CountInline {
+            processed: AtomicUsize,
+        }
+
+        system_parallel! {
+            #[count_entities = processed]
+            #[min_parallel = 1_000_000]
+            CountInline<u128> {
+                a: CopyOnWriteComponentCollection<u64>,
+                b: MutableComponentCollection<u64>,
+            }
+        }
+
+        impl CountInline {
+            fn process(
+                &self,
+                _: u128,
+                _: &mut CopyOnWriteComponentRef<u64>,
+                _: &mut MutableComponentRef<u64>,
+            ) {
+            }
+        }
+
+        #[test]
+        fn counts_intersection() {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![1000, 2000, 3000]));
+            let a = CopyOnWriteComponentCollection::from_iter(
+                (1..4000u128).filter(|e| e % 2 == 0).map(|e| (e, 0u64)),
+            );
+            let b = MutableComponentCollection::from_iter(
+                (1..4000u128).filter(|e| e % 3 == 0).map(|e| (e, 0u64)),
+            );
+            let expected = (1..4000u128).filter(|e| e % 6 == 0).count();
+            let a = Partitioned::from(&partitioning, a.partition(&*partitioning));
+            let b = Partitioned::from(&partitioning, b.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("count_entities", 4);
+            let count = Arc::new(Count {
+                processed: AtomicUsize::new(0),
+            });
+            let inline = Arc::new(CountInline {
+                processed: AtomicUsize::new(0),
+            });
+            for _ in 0..2 {
+                let _ = Arc::clone(&count).run(&thread_pool, &a, &b)();
+                assert_eq!(expected, count.processed.load(Ordering::Relaxed));
+                let _ = Arc::clone(&inline).run(&thread_pool, &a, &b)();
+                assert_eq!(expected, inline.processed.load(Ordering::Relaxed));
+            }
+            let subset: Vec<u128> = (1..=100u128).collect();
+            let _ = count.run_parallel_subset(&thread_pool, &subset, &a, &b);
+            assert_eq!(16, count.processed.load(Ordering::Relaxed));
+            let _ = inline.run_parallel_subset(&thread_pool, &subset, &a, &b);
+            assert_eq!(16, inline.processed.load(Ordering::Relaxed));
+            thread_pool.shutdown();
+        }
+    }
+
+    mod scheme_equality {
+        use std::sync::Arc;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{Partitioned, PartitioningScheme, VecPartitioningScheme};
+
+        struct Add;
+
+        system_parallel! {
+            Add<u128> {
+                a: CopyOnWriteComponentCollection<u64>,
+                b: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl Add {
+            fn process(
+                &self,
+                _: u128,
+                a: &mut CopyOnWriteComponentRef<u64>,
+                b: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                let b = **b;
+                a.update(|a| *a += b);
+            }
+        }
+
+        struct TrustingAdd;
+
+        system_parallel! {
+            #[verify_scheme_equality = false]
+            #[min_parallel = 0]
+            TrustingAdd<u128> {
+                a: CopyOnWriteComponentCollection<u64>,
+                b: CopyOnWriteComponentCollection<u64>,
+            }
+        }
+
+        impl TrustingAdd {
+            fn process(
+                &self,
+                _: u128,
+                a: &mut CopyOnWriteComponentRef<u64>,
+                b: &mut CopyOnWriteComponentRef<u64>,
+            ) {
+                let b = **b;
+                a.update(|a| *a += b);
+            }
+        }
+
+        type Collection = Partitioned<u128, u64, CopyOnWriteComponentCollection<u128, u64>>;
+
+        fn collection(dividers: Vec<u128>, entities: std::ops::Range<u128>) -> Collection {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(dividers));
+            let collection =
+                CopyOnWriteComponentCollection::from_iter(entities.map(|e| (e, e as u64)));
+            Partitioned::from(&partitioning, collection.partition(&*partitioning))
+        }
+
+        #[test]
+        fn structurally_equal_schemes() {
+            let mut a = collection(vec![10, 20], 1..30);
+            let b = collection(vec![10, 20], 1..30);
+            assert!(!Arc::ptr_eq(
+                a.partitioning_scheme(),
+                b.partitioning_scheme()
+            ));
+            assert!(a
+                .partitioning_scheme()
+                .structural_eq(&**b.partitioning_scheme()));
+            let thread_pool = ThreadPool::new("scheme_equality", 2);
+            let (a_changes, b_changes) = Arc::new(Add).run(&thread_pool, &a, &b)();
+            assert!(Arc::ptr_eq(
+                a.partitioning_scheme(),
+                a_changes.partitioning_scheme()
+            ));
+            assert!(Arc::ptr_eq(
+                b.partitioning_scheme(),
+                b_changes.partitioning_scheme()
+            ));
+            a.apply(a_changes);
+            assert_eq!(
+                (1..30u128).map(|e| (e, e as u64 * 2)).collect::<Vec<_>>(),
+                a.consume().collect::<Vec<_>>()
+            );
+            thread_pool.shutdown();
+        }
+
+        #[test]
+        fn shared_arc_skips_structural_check() {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![10]));
+            let a = CopyOnWriteComponentCollection::from_iter((1..20u128).map(|e| (e, e as u64)));
+            let b = CopyOnWriteComponentCollection::from_iter((1..20u128).map(|e| (e, 1u64)));
+            let a = Partitioned::from(&partitioning, a.partition(&*partitioning));
+            let b = Partitioned::from(&partitioning, b.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("scheme_equality", 2);
+            let (a_changes, _) = Arc::new(Add).run(&thread_pool, &a, &b)();
+            assert!(Arc::ptr_eq(&partitioning, a_changes.partitioning_scheme()));
+            let a_changes: Vec<_> = a_changes.into_iter().flatten().collect();
+            assert_eq!(19, a_changes.len());
+            for (e, c) in a_changes {
+                assert!(matches!(c, ComponentChange::Value(v) if v == e as u64 + 1));
+            }
+            thread_pool.shutdown();
+        }
+
+        #[test]
+        #[should_panic(expected = "parallel system run with different partitioning schemes")]
+        fn different_schemes() {
+            let a = collection(vec![10, 20], 1..30);
+            let b = collection(vec![10, 30], 1..30);
+            let thread_pool = ThreadPool::new("scheme_equality", 2);
+            let _ = Arc::new(Add).run(&thread_pool, &a, &b)();
+        }
+
+        #[test]
+        fn unverified_different_schemes() {
+            // NOTE(rescrv):  The schemes disagree only above the entities, so trusting them is safe.
+            let a = collection(vec![10, 20], 1..10);
+            let b = collection(vec![10, 30], 1..10);
+            let thread_pool = ThreadPool::new("scheme_equality", 2);
+            let (a_changes, _) = Arc::new(TrustingAdd).run(&thread_pool, &a, &b)();
+            let a_changes: Vec<_> = a_changes.into_iter().flatten().collect();
+            assert_eq!(9, a_changes.len());
+            for (e, c) in a_changes {
+                assert!(matches!(c, ComponentChange::Value(v) if v == e as u64 * 2));
+            }
+            let subset: Vec<u128> = (1..10u128).collect();
+            let (a_changes, _) = TrustingAdd.run_parallel_subset(&thread_pool, &subset, &a, &b);
+            assert_eq!(9, a_changes.into_iter().flatten().count());
+            thread_pool.shutdown();
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    mod rayon_parallel {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        use crate::{ComponentChange, ComponentCollection, ComponentRef, Entity, ThreadPool};
+        use crate::{CopyOnWriteComponentCollection, CopyOnWriteComponentRef};
+        use crate::{MutableComponentCollection, MutableComponentRef};
+        use crate::{Partitioned, PartitioningScheme, VecPartitioningScheme};
+
+        struct Step {
+            processed: AtomicUsize,
+        }
+
+        system_parallel! {
+            #[count_entities = processed]
+            Step<u128> {
+                position: CopyOnWriteComponentCollection<u64>,
+                velocity: MutableComponentCollection<u64>,
+            }
+        }
+
+        impl Step {
+            fn process(
+                &self,
+                entity: u128,
+                position: &mut CopyOnWriteComponentRef<u64>,
+                velocity: &mut MutableComponentRef<u64>,
+            ) {
+                if entity > 3500 {
+                    position.unbind();
+                } else {
+                    position.update(|p| *p += **velocity);
+                }
+                if entity < 2500 {
+                    velocity.update(|v| *v /= 2);
+                }
+            }
+        }
+
+        type World = (
+            Partitioned<u128, u64, CopyOnWriteComponentCollection<u128, u64>>,
+            Partitioned<u128, u64, MutableComponentCollection<u128, u64>>,
+        );
+
+        fn world(partitioning: &Arc<dyn PartitioningScheme<u128>>) -> World {
+            let position = CopyOnWriteComponentCollection::from_iter(
+                (1..4000u128).filter(|e| e % 2 == 0).map(|e| (e, e as u64)),
+            );
+            let velocity = MutableComponentCollection::from_iter(
+                (1..4000u128)
+                    .filter(|e| e % 3 == 0)
+                    .map(|e| (e, e as u64 * 3)),
+            );
+            (
+                Partitioned::from(partitioning, position.partition(&**partitioning)),
+                Partitioned::from(partitioning, velocity.partition(&**partitioning)),
+            )
+        }
+
+        fn collect<C: ComponentCollection<u128, u64>>(
+            collection: &Partitioned<u128, u64, C>,
+        ) -> Vec<(u128, u64)> {
+            (1..4000u128)
+                .filter_map(|e| collection.get_cloned(e).map(|c| (e, c)))
+                .collect()
+        }
+
+        #[test]
+        fn matches_thread_pool() {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![1000, 2000, 3000, 5000]));
+            let expected = (1..4000u128).filter(|e| e % 6 == 0).count();
+            let thread_pool = ThreadPool::new("rayon_parallel", 4);
+            let step = Arc::new(Step {
+                processed: AtomicUsize::new(0),
+            });
+            // Mutable components change in place, so each run gets a world of its own.
+            let mut pool = world(&partitioning);
+            let (position, velocity) = Arc::clone(&step).run(&thread_pool, &pool.0, &pool.1)();
+            assert_eq!(expected, step.processed.load(Ordering::Relaxed));
+            pool.0.apply(position);
+            pool.1.apply(velocity);
+            let mut rayon = world(&partitioning);
+            let (position, velocity) = step.run_rayon(&rayon.0, &rayon.1);
+            assert_eq!(expected, step.processed.load(Ordering::Relaxed));
+            assert!(Arc::ptr_eq(&partitioning, position.partitioning_scheme()));
+            rayon.0.apply(position);
+            rayon.1.apply(velocity);
+            assert_eq!(collect(&pool.0), collect(&rayon.0));
+            assert_eq!(collect(&pool.1), collect(&rayon.1));
+            assert_ne!(collect(&world(&partitioning).0), collect(&rayon.0));
+            thread_pool.shutdown();
+        }
+    }
 }
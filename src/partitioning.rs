@@ -1,14 +1,18 @@
 use std::fmt::Debug;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Condvar, Mutex};
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
 
-use crate::component::{apply_component_changes, ComponentChange, ComponentCollection};
+use crate::component::{lower_bound_after, ComponentChange, ComponentCollection, ComponentRef};
 use crate::{Entity, ThreadPool, WorkUnit};
 
 //////////////////////////////////////// PartitioningScheme ////////////////////////////////////////
 
-/// PartitioningScheme divides a totally-ordered entity-space into partitions.
-pub trait PartitioningScheme<E: Entity>: Debug {
+/// PartitioningScheme divides a totally-ordered entity-space into partitions.  Schemes are Send
+/// and Sync so that a Partitioned collection may be shared between threads.
+pub trait PartitioningScheme<E: Entity>: Debug + Send + Sync {
     /// Whether the partitioning scheme has dividers.
     fn is_empty(&self) -> bool;
     /// The number of partition dividers.  There will be one more partition than this number.
@@ -17,8 +21,132 @@ pub trait PartitioningScheme<E: Entity>: Debug {
     fn partition(&self, partition: usize) -> E;
     /// Compute the first partition in which the entity could reside.
     fn lower_bound(&self, entity: E) -> usize;
+
+    /// Compute the partition in which the entity resides.  Partition i holds the entities in the
+    /// half-open interval `[partition(i - 1), partition(i))`, so an entity equal to a divider
+    /// resides in the partition after the one returned by [Self::lower_bound].
+    fn partition_of(&self, entity: E) -> usize {
+        let partition = self.lower_bound(entity);
+        if partition < self.len() && self.partition(partition) == entity {
+            partition + 1
+        } else {
+            partition
+        }
+    }
+
+    /// Return the dividers `(lower, upper)` bounding the half-open interval `[lower, upper)` of
+    /// entities that reside in partition.  The first partition has no lower divider and the last
+    /// partition has no upper divider; each is None where the interval is unbounded.  Returns None
+    /// if there is no such partition.
+    fn entities_in_partition(&self, partition: usize) -> Option<(Option<E>, Option<E>)> {
+        if partition > self.len() {
+            return None;
+        }
+        let lower = if partition > 0 { Some(self.partition(partition - 1)) } else { None };
+        let upper = if partition < self.len() { Some(self.partition(partition)) } else { None };
+        Some((lower, upper))
+    }
+
+    /// True if and only if other has the same dividers as this scheme, and therefore places every
+    /// entity in the same partition.  Unlike `Arc::ptr_eq`, this compares schemes by value, so
+    /// two schemes built independently from the same dividers are equal.
+    fn structural_eq(&self, other: &dyn PartitioningScheme<E>) -> bool {
+        self.len() == other.len() && (0..self.len()).all(|p| self.partition(p) == other.partition(p))
+    }
+}
+
+impl<E: Entity> PartitioningScheme<E> for Arc<dyn PartitioningScheme<E>> {
+    fn is_empty(&self) -> bool {
+        (**self).is_empty()
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn partition(&self, partition: usize) -> E {
+        (**self).partition(partition)
+    }
+
+    fn lower_bound(&self, entity: E) -> usize {
+        (**self).lower_bound(entity)
+    }
+
+    fn partition_of(&self, entity: E) -> usize {
+        (**self).partition_of(entity)
+    }
+
+    fn entities_in_partition(&self, partition: usize) -> Option<(Option<E>, Option<E>)> {
+        (**self).entities_in_partition(partition)
+    }
+
+    fn structural_eq(&self, other: &dyn PartitioningScheme<E>) -> bool {
+        (**self).structural_eq(other)
+    }
+}
+
+impl<E: Entity, PS: PartitioningScheme<E> + ?Sized> PartitioningScheme<E> for &PS {
+    fn is_empty(&self) -> bool {
+        (**self).is_empty()
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn partition(&self, partition: usize) -> E {
+        (**self).partition(partition)
+    }
+
+    fn lower_bound(&self, entity: E) -> usize {
+        (**self).lower_bound(entity)
+    }
+
+    fn partition_of(&self, entity: E) -> usize {
+        (**self).partition_of(entity)
+    }
+
+    fn entities_in_partition(&self, partition: usize) -> Option<(Option<E>, Option<E>)> {
+        (**self).entities_in_partition(partition)
+    }
+
+    fn structural_eq(&self, other: &dyn PartitioningScheme<E>) -> bool {
+        (**self).structural_eq(other)
+    }
 }
 
+////////////////////////////////////////// ValidationError /////////////////////////////////////////
+
+/// An inconsistency found by validating a [Partitioned] collection against a scheme.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ValidationError {
+    /// The collection has a different number of partitions than the scheme.
+    PartitionCount {
+        /// The number of partitions the scheme calls for.
+        expected: usize,
+        /// The number of partitions in the collection.
+        found: usize,
+    },
+    /// An entity sits in a partition other than the one the scheme assigns it.
+    MisplacedEntity {
+        /// The partition holding the entity.
+        partition: usize,
+        /// The entity, formatted with Debug.
+        entity: String,
+    },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::PartitionCount { expected, found } => write!(f, "expected {} partitions, found {}", expected, found),
+            Self::MisplacedEntity { partition, entity } => write!(f, "entity {} misplaced in partition {}", entity, partition),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
 /////////////////////////////////////// NopPartitioningScheme //////////////////////////////////////
 
 /// NopPartitioningScheme provides no partitioning whatsoever.
@@ -58,7 +186,7 @@ impl<E: Entity> From<Vec<E>> for VecPartitioningScheme<E> {
     }
 }
 
-impl<E: Entity> PartitioningScheme<E> for VecPartitioningScheme<E> {
+impl<E: Entity + Send + Sync> PartitioningScheme<E> for VecPartitioningScheme<E> {
     fn is_empty(&self) -> bool {
         self.entities.is_empty()
     }
@@ -77,27 +205,164 @@ impl<E: Entity> PartitioningScheme<E> for VecPartitioningScheme<E> {
     }
 }
 
+//////////////////////////////////// WeightedPartitioningScheme ////////////////////////////////////
+
+/// Pick dividers so that each partition carries roughly the same total weight, rather than the
+/// same number of entities.  Useful when components vary widely in how much work they take to
+/// process.  Every partition but the last ends at the first entity that takes its running total
+/// to its share of the overall weight, so no partition exceeds its share by more than the heaviest
+/// entity.  An entity heavy enough to cover several shares ends only one partition, so there may
+/// be fewer partitions than requested.
+#[derive(Debug)]
+pub struct WeightedPartitioningScheme<E: Entity> {
+    dividers: VecPartitioningScheme<E>,
+}
+
+impl<E: Entity> WeightedPartitioningScheme<E> {
+    /// Balance `partitions` partitions over the weighted entities.  The entities need not be
+    /// sorted, but must be unique.
+    pub fn new<I: IntoIterator<Item = (E, u64)>>(weights: I, partitions: usize) -> Self {
+        let mut weights: Vec<(E, u64)> = weights.into_iter().collect();
+        weights.sort_by_key(|(e, _)| *e);
+        let total: u128 = weights.iter().map(|(_, w)| *w as u128).sum();
+        let partitions = partitions.max(1) as u128;
+        let mut dividers = vec![];
+        let mut running = 0u128;
+        let mut shares = 0u128;
+        for (idx, (_, weight)) in weights.iter().enumerate() {
+            running += *weight as u128;
+            // NOTE(rescrv):  The number of whole shares of the total covered so far.  An entity
+            // that covers several at once ends a single partition.
+            let covered = (running * partitions).checked_div(total).unwrap_or(0);
+            if covered > shares && (dividers.len() as u128) + 1 < partitions && idx + 1 < weights.len() {
+                dividers.push(weights[idx + 1].0);
+                shares = covered;
+            }
+        }
+        let dividers = VecPartitioningScheme::from(dividers);
+        Self { dividers }
+    }
+
+    /// Balance `partitions` partitions over the entities, weighing each with `weight`.
+    pub fn from_fn<I: IntoIterator<Item = E>, F: Fn(E) -> u64>(entities: I, weight: F, partitions: usize) -> Self {
+        Self::new(entities.into_iter().map(|e| (e, weight(e))), partitions)
+    }
+}
+
+impl<E: Entity + Send + Sync> PartitioningScheme<E> for WeightedPartitioningScheme<E> {
+    fn is_empty(&self) -> bool {
+        self.dividers.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.dividers.len()
+    }
+
+    fn partition(&self, partition: usize) -> E {
+        self.dividers.partition(partition)
+    }
+
+    fn lower_bound(&self, entity: E) -> usize {
+        self.dividers.lower_bound(entity)
+    }
+}
+
+//////////////////////////////////////// PartitionedChanges ////////////////////////////////////////
+
+/// Changes split into one vector per partition, along with the partitioning scheme they were split
+/// under.  [Partitioned::apply] refuses changes whose scheme differs from the collection's.
+pub struct PartitionedChanges<E: Entity, T: Debug> {
+    partitioning: Arc<dyn PartitioningScheme<E>>,
+    changes: Vec<Vec<(E, ComponentChange<T>)>>,
+}
+
+impl<E: Entity, T: Debug> PartitionedChanges<E, T> {
+    /// Wrap changes that have been partitioned according to partitioning.
+    ///
+    /// # Panics
+    ///
+    /// If there is not exactly one vector of changes per partition.
+    pub fn new(partitioning: &Arc<dyn PartitioningScheme<E>>, changes: Vec<Vec<(E, ComponentChange<T>)>>) -> Self {
+        assert_eq!(partitioning.len() + 1, changes.len());
+        debug_assert!(changes.iter().enumerate().all(|(idx, c)| c.iter().all(|(e, _)| partitioning.partition_of(*e) == idx)));
+        let partitioning = Arc::clone(partitioning);
+        Self {
+            partitioning,
+            changes,
+        }
+    }
+
+    /// The partitioning scheme the changes were partitioned under.
+    pub fn partitioning_scheme(&self) -> &Arc<dyn PartitioningScheme<E>> {
+        &self.partitioning
+    }
+
+    /// Return the per-partition changes, dropping the partitioning scheme.
+    pub fn into_inner(self) -> Vec<Vec<(E, ComponentChange<T>)>> {
+        self.changes
+    }
+}
+
+impl<E: Entity, T: Debug> IntoIterator for PartitionedChanges<E, T> {
+    type Item = Vec<(E, ComponentChange<T>)>;
+    type IntoIter = std::vec::IntoIter<Vec<(E, ComponentChange<T>)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<E: Entity, T: Debug> std::ops::Deref for PartitionedChanges<E, T> {
+    type Target = [Vec<(E, ComponentChange<T>)>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.changes
+    }
+}
+
 //////////////////////////////////////////// Partitioned ///////////////////////////////////////////
 
 /// Partitioned wraps another collection type and partitions it according to the partitioning
 /// scheme provided.
+///
+/// The number of components is counted when the collection is built and recounted for each
+/// partition that changes are applied to, so that `len` need not visit every partition.  Inserting
+/// into a partition directly, as [crate::InsertOptimizedComponentCollection::insert] allows
+/// through [Partitioned::get_partition_by_index], goes uncounted.
 pub struct Partitioned<E: Entity, T: Debug, C: ComponentCollection<E, T>> {
     partitioning: Arc<dyn PartitioningScheme<E>>,
     partitions: Vec<Option<Arc<C>>>,
+    total_len: usize,
     _phantom_t: std::marker::PhantomData<T>,
 }
 
 impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> Partitioned<E, T, C> {
     /// Create a new partitioned collection from the partitioning and partitions provided.
+    ///
+    /// # Panics
+    ///
+    /// If there is not exactly one more partition than the partitioning scheme has dividers.
     pub fn from(partitioning: &Arc<dyn PartitioningScheme<E>>, partitions: Vec<Option<C>>) -> Self {
+        assert_eq!(partitioning.len() + 1, partitions.len());
         let partitioning = Arc::clone(partitioning);
         let partitions = partitions.into_iter().map(|x| x.map(Arc::new)).collect();
+        Self::from_arcs(partitioning, partitions)
+    }
+
+    fn from_arcs(partitioning: Arc<dyn PartitioningScheme<E>>, partitions: Vec<Option<Arc<C>>>) -> Self {
         let _phantom_t = std::marker::PhantomData;
-        Self {
+        let mut this = Self {
             partitioning,
             partitions,
+            total_len: 0,
             _phantom_t,
-        }
+        };
+        this.recount();
+        this
+    }
+
+    fn recount(&mut self) {
+        self.total_len = self.partitions.iter().flatten().map(|p| p.len()).fold(0usize, usize::saturating_add);
     }
 
     /// The partitioning scheme in use by this partitioned collection.
@@ -105,6 +370,45 @@ impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> Partitioned<E, T, C> {
         &self.partitioning
     }
 
+    /// Check that this collection is partitioned consistently with scheme:  that it has one
+    /// partition per interval and that every entity sits in the partition given by
+    /// [PartitioningScheme::partition_of].  This is a debugging aid for the parallel path, where a
+    /// mis-partitioned collection is otherwise undefined behavior.
+    pub fn validate_against(&self, scheme: &dyn PartitioningScheme<E>) -> Result<(), ValidationError> {
+        let expected = scheme.len() + 1;
+        let found = self.partitions.len();
+        if expected != found {
+            return Err(ValidationError::PartitionCount { expected, found });
+        }
+        for (partition, collection) in self.partitions.iter().enumerate() {
+            let Some(collection) = collection else {
+                continue;
+            };
+            // NOTE(rescrv):  Collections are sorted, so the first and last entities bound the rest.
+            for entity in [collection.first(), collection.last()].into_iter().flatten() {
+                if scheme.partition_of(entity) != partition {
+                    let entity = format!("{:?}", entity);
+                    return Err(ValidationError::MisplacedEntity { partition, entity });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Iterate every entity in order, each with a clone of its component, without consuming the
+    /// collection.  No lock is held between items, so the iterator may be collected, zipped, or
+    /// peeked like any other.
+    pub fn iter(&self) -> PartitionedIter<'_, E, T, C>
+    where
+        T: Clone,
+    {
+        PartitionedIter {
+            partitioned: self,
+            partition: 0,
+            next: self.partitions[0].as_ref().and_then(|p| p.first()),
+        }
+    }
+
     /// Return the N'th partition.
     pub fn get_partition_by_index(&self, partition: usize) -> Option<Arc<C>> {
         if partition < self.partitions.len() {
@@ -114,14 +418,89 @@ impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> Partitioned<E, T, C> {
         }
     }
 
+    /// The number of entities in each partition, as (partition_index, entity_count).  Every
+    /// partition is listed, including empty ones.
+    pub fn component_histogram(&self) -> Vec<(usize, usize)> {
+        self.partitions.iter().enumerate().map(|(idx, p)| (idx, p.as_ref().map(|c| c.len()).unwrap_or(0))).collect()
+    }
+
+    /// True if the most populous partition holds no more than tolerance times the mean number of
+    /// entities per partition.  A tolerance of 1.0 requires every partition to be the same size.
+    pub fn is_balanced(&self, tolerance: f64) -> bool {
+        let histogram = self.component_histogram();
+        let max = histogram.iter().map(|(_, c)| *c).max().unwrap_or(0);
+        let total: usize = histogram.iter().map(|(_, c)| *c).sum();
+        // NOTE(rescrv):  max <= tolerance * total / partitions, without dividing.
+        max as f64 * histogram.len() as f64 <= tolerance * total as f64
+    }
+
+    /// Compute [ComponentCollection::lower_bound] for each of queries, which must be sorted.  Each
+    /// scan resumes from the partition where the previous one stopped, and a query already covered
+    /// by the previous answer is answered without a scan.
+    pub fn lower_bound_batch(&self, queries: &[E]) -> Vec<Option<E>> {
+        debug_assert!(queries.windows(2).all(|w| w[0] <= w[1]));
+        let mut answers = Vec::with_capacity(queries.len());
+        let mut partition = 0;
+        let mut answer = None;
+        for query in queries.iter().copied() {
+            if answer.map(|answer| query <= answer).unwrap_or(false) {
+                answers.push(answer);
+                continue;
+            }
+            // NOTE(rescrv):  The previous scan found nothing at or above the previous query in the
+            // partitions it passed over, so neither will this one.
+            partition = partition.max(self.partitioning.lower_bound(query));
+            answer = None;
+            while partition < self.partitions.len() {
+                answer = self.partitions[partition].as_ref().and_then(|p| p.lower_bound(query));
+                if answer.is_some() {
+                    break;
+                }
+                partition += 1;
+            }
+            answers.push(answer);
+        }
+        answers
+    }
+
+    /// Consume the collection like [ComponentCollection::consume], or hand it back intact if
+    /// someone holds a reference to a partition so that the caller may retry once it is released.
+    pub fn try_consume(self) -> Result<<Self as ComponentCollection<E, T>>::Consumed, Self> {
+        // NOTE(rescrv):  We own self, so a partition with a strong count of one cannot be cloned
+        // out from under us between this check and into_inner.
+        if self.partitions.iter().flatten().any(|p| Arc::strong_count(p) > 1) {
+            return Err(self);
+        }
+        let mut partitions = Vec::with_capacity(self.partitions.len());
+        for partition in self.partitions.into_iter().flatten() {
+            // SAFETY(rescrv):  Checked above that we hold the only reference.
+            partitions.push(Arc::into_inner(partition).unwrap().consume());
+        }
+        Ok(partitions.into_iter().flatten())
+    }
+
     /// Apply the pre-partitioned changes to the collection.
     ///
-    /// Behavior is undefined if the changes are not partitioned according to the partitioning of
-    /// this partitioned collection.
-    pub fn apply(&mut self, partitioned_changes: Vec<Vec<(E, ComponentChange<T>)>>) {
-        self.apply_inner(partitioned_changes, |col, chan| {
-            apply_component_changes(col, chan.into_iter())
-        })
+    /// # Panics
+    ///
+    /// If the changes were partitioned under a different partitioning scheme than this collection.
+    pub fn apply(&mut self, partitioned_changes: PartitionedChanges<E, T>) {
+        let partitioned_changes = self.check_partitioning(partitioned_changes);
+        self.apply_inner(partitioned_changes, Self::apply_to_partition)
+    }
+
+    // NOTE(rescrv):  Go through the partition's own apply so that each collection type takes its
+    // in-place path where it has one rather than always rebuilding.
+    fn apply_to_partition(mut partition: C, changes: Vec<(E, ComponentChange<T>)>) -> C {
+        partition.apply(changes);
+        partition
+    }
+
+    fn check_partitioning(&self, partitioned_changes: PartitionedChanges<E, T>) -> Vec<Vec<(E, ComponentChange<T>)>> {
+        if !Arc::ptr_eq(&self.partitioning, partitioned_changes.partitioning_scheme()) {
+            panic!("changes were partitioned under a different partitioning scheme");
+        }
+        partitioned_changes.into_inner()
     }
 
     fn apply_inner<F: FnMut(C, Vec<(E, ComponentChange<T>)>) -> C + Clone>(
@@ -134,114 +513,314 @@ impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> Partitioned<E, T, C> {
         for (partition, changes) in
             std::iter::zip(partitions.into_iter(), partitioned_changes.into_iter())
         {
-            self.partitions
-                .push(Self::apply_partition(partition, changes, f.clone()));
+            let before = partition.as_ref().map(|p| p.len()).unwrap_or(0);
+            let partition = Self::apply_partition(partition, changes, f.clone());
+            let after = partition.as_ref().map(|p| p.len()).unwrap_or(0);
+            self.total_len = self.total_len - before + after;
+            self.partitions.push(partition);
         }
     }
 
     fn apply_partition<F: FnMut(C, Vec<(E, ComponentChange<T>)>) -> C>(
         partition: Option<Arc<C>>,
         changes: Vec<(E, ComponentChange<T>)>,
-        mut f: F,
+        f: F,
     ) -> Option<Arc<C>> {
-        if let Some(ptr) = partition {
-            if let Some(partition) = Arc::into_inner(ptr) {
-                let partition = f(partition, changes);
-                if !partition.is_empty() {
-                    Some(Arc::new(partition))
-                } else {
-                    None
-                }
-            } else {
+        match partition.map(Arc::try_unwrap) {
+            Some(Err(_)) => {
                 panic!("`apply` method called while someone holds a reference to a partition");
             }
+            Some(Ok(partition)) => Self::apply_owned_partition(Some(partition), changes, f),
+            None => Self::apply_owned_partition(None, changes, f),
+        }
+    }
+
+    fn apply_owned_partition<F: FnMut(C, Vec<(E, ComponentChange<T>)>) -> C>(
+        partition: Option<C>,
+        changes: Vec<(E, ComponentChange<T>)>,
+        mut f: F,
+    ) -> Option<Arc<C>> {
+        let partition = match partition {
+            Some(partition) => f(partition, changes),
+            None => C::from_iter(changes),
+        };
+        if !partition.is_empty() {
+            Some(Arc::new(partition))
         } else {
-            let partition: C = C::from_iter(changes);
-            if !partition.is_empty() {
-                Some(Arc::new(partition))
-            } else {
-                None
-            }
+            None
         }
     }
 }
 
 impl<E: Entity + Send + Sync + 'static, T: Debug + Send + Sync + 'static, C: ComponentCollection<E, T> + Send + Sync + 'static> Partitioned<E, T, C> {
-    /// Use `thread_pool` to apply the pre-partitioned changes in parallel.
+    /// Use `thread_pool` to apply the pre-partitioned changes in parallel.  The returned handle
+    /// may be waited on or awaited for the application to complete.
     ///
-    /// Behavior is undefined if the changes are not partitioned according to the partitioning of
-    /// this partitioned collection.
-    pub fn apply_parallel(&mut self, thread_pool: &ThreadPool, partitioned_changes: Vec<Vec<(E, ComponentChange<T>)>>) -> impl FnOnce() + '_ {
+    /// A partition whose application panics is left empty and reported in the [ApplyError] the
+    /// handle returns.  A partition someone else holds a reference to cannot be applied to; it is
+    /// left unchanged and reported, too.  The other partitions are applied regardless.
+    ///
+    /// # Panics
+    ///
+    /// If the changes were partitioned under a different partitioning scheme than this collection.
+    pub fn apply_parallel(&mut self, thread_pool: &ThreadPool, partitioned_changes: PartitionedChanges<E, T>) -> PartitionedApplyHandle<'_, E, T, C> {
+        let partitioned_changes = self.check_partitioning(partitioned_changes);
         assert_eq!(self.partitions.len(), partitioned_changes.len());
         let partitions = std::mem::take(&mut self.partitions);
-        struct AggregatePartitions<E: Entity + Send, T: Debug + Send, C: ComponentCollection<E, T> + Send> {
-            partitions: Mutex<Vec<Option<Arc<C>>>>,
-            done: AtomicUsize,
-            wait: Condvar,
-            _phantom_e: std::marker::PhantomData<E>,
-            _phantom_t: std::marker::PhantomData<T>,
-        }
-        impl<E: Entity + Send, T: Debug + Send, C: ComponentCollection<E, T> + Send> AggregatePartitions<E, T, C> {
-            fn new(num_partitions: usize) -> Self {
-                let mut partitions = Vec::with_capacity(num_partitions);
-                for _ in 0..num_partitions {
-                    partitions.push(None);
-                }
-                let partitions = Mutex::new(partitions);
-                let done = AtomicUsize::new(0);
-                let wait = Condvar::new();
-                Self {
-                    partitions,
-                    done,
-                    wait,
-                    _phantom_e: std::marker::PhantomData,
-                    _phantom_t: std::marker::PhantomData,
-                }
-            }
-
-            fn done(&self, partition: usize, results: Option<Arc<C>>) {
-                let len = {
-                    let mut partitions = self.partitions.lock().unwrap();
-                    if partitions[partition].is_none() {
-                        // SAFETY(rescrv):  We need this Some(_) assignment to be the only
-                        // one, and it must be 1:1 with the fetch_add.
-                        partitions[partition] = results;
-                        self.done.fetch_add(1, Ordering::Relaxed);
-                    }
-                    partitions.len()
-                };
-                if len == self.done.load(Ordering::Relaxed) {
-                    self.wait.notify_all();
-                }
-            }
-
-            fn wait(&self) -> Vec<Option<Arc<C>>> {
-                let mut partitions = self.partitions.lock().unwrap();
-                while self.done.load(Ordering::Relaxed) < partitions.len() {
-                    partitions = self.wait.wait(partitions).unwrap();
-                }
-                let mut returned = vec![];
-                std::mem::swap(&mut *partitions, &mut returned);
-                returned
-            }
-        }
-        let agg = Arc::new(AggregatePartitions::new(partitions.len()));
+        let agg = Arc::new(ApplyAggregate::new(partitions.len()));
         for (idx, (partition, changes)) in
             std::iter::zip(partitions.into_iter(), partitioned_changes.into_iter()).enumerate()
         {
             let agg = Arc::clone(&agg);
             let work_unit: Box<WorkUnit> = Box::new(move || {
-                let results = Self::apply_partition(partition, changes, |col, chan|apply_component_changes(col, chan.into_iter()));
-                agg.done(idx, results);
+                let partition = match partition.map(Arc::try_unwrap) {
+                    Some(Err(shared)) => {
+                        agg.shared(idx, shared);
+                        return;
+                    }
+                    Some(Ok(partition)) => Some(partition),
+                    None => None,
+                };
+                let results = catch_unwind(AssertUnwindSafe(|| {
+                    Self::apply_owned_partition(partition, changes, Self::apply_to_partition)
+                }));
+                agg.done(idx, results.ok());
             });
             thread_pool.enqueue(work_unit);
         }
-        move || {
-            self.partitions = agg.wait();
+        PartitionedApplyHandle {
+            partitioned: self,
+            agg: Some(agg),
+        }
+    }
+}
+
+/////////////////////////////////////////// parallel_over //////////////////////////////////////////
+
+/// Call `f` on every entity of the collection, alongside a ref to the entity's component, using
+/// `thread_pool` to process the partitions in parallel.  This is a closure-based companion to
+/// [system_parallel](crate::system_parallel) for one-off computations over a single collection.
+/// Returns the changes made through the refs, ready to be applied to the collection.
+pub fn parallel_over<E, T, C, F>(thread_pool: &ThreadPool, collection: &Partitioned<E, T, C>, f: F) -> PartitionedChanges<E, T>
+where
+    E: Entity + Send + Sync,
+    T: Debug + Send,
+    C: ComponentCollection<E, T> + Send + Sync,
+    F: for<'a> Fn(E, &mut C::Ref<'a>) + Send + Sync,
+{
+    let mut changes: Vec<Vec<(E, ComponentChange<T>)>> = Vec::with_capacity(collection.partitions.len());
+    changes.resize_with(collection.partitions.len(), Vec::new);
+    thread_pool.scope(|scope| {
+        for (partition, changes) in std::iter::zip(collection.partitions.iter(), changes.iter_mut()) {
+            let Some(partition) = partition else {
+                continue;
+            };
+            let f = &f;
+            scope.enqueue(move || {
+                let mut target = E::default();
+                while let Some(entity) = partition.lower_bound(target) {
+                    // SAFETY(rescrv):  lower_bound returned entity, so it is in the partition.
+                    let mut component = partition.get_ref(entity).expect("entity should be present");
+                    f(entity, &mut component);
+                    let change = component.change();
+                    if !change.is_no_change() {
+                        changes.push((entity, change));
+                    }
+                    if entity == E::max_value() {
+                        break;
+                    }
+                    target = entity.increment();
+                }
+            });
+        }
+    });
+    PartitionedChanges::new(&collection.partitioning, changes)
+}
+
+//////////////////////////////////////////// ApplyError ////////////////////////////////////////////
+
+/// The partitions to which [Partitioned::apply_parallel] failed to apply changes.  Every other
+/// partition was applied.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ApplyError {
+    /// The indices of the partitions that panicked, in increasing order.  Each is left empty.
+    pub panicked: Vec<usize>,
+    /// The indices of the partitions that someone else held a reference to, in increasing order.
+    /// Each is left as it was, without the changes.
+    pub shared: Vec<usize>,
+}
+
+impl std::fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "applying changes panicked in partitions {:?} and skipped shared partitions {:?}", self.panicked, self.shared)
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
+////////////////////////////////////// PartitionedApplyHandle //////////////////////////////////////
+
+struct ApplyProgress<C> {
+    partitions: Vec<Option<Arc<C>>>,
+    panicked: Vec<usize>,
+    shared: Vec<usize>,
+    done: usize,
+    waker: Option<Waker>,
+}
+
+type ApplyResults<C> = Result<Vec<Option<Arc<C>>>, (Vec<Option<Arc<C>>>, ApplyError)>;
+
+struct ApplyAggregate<C> {
+    progress: Mutex<ApplyProgress<C>>,
+    wait: Condvar,
+}
+
+impl<C> ApplyAggregate<C> {
+    fn new(num_partitions: usize) -> Self {
+        let mut partitions = Vec::with_capacity(num_partitions);
+        for _ in 0..num_partitions {
+            partitions.push(None);
+        }
+        let progress = Mutex::new(ApplyProgress {
+            partitions,
+            panicked: vec![],
+            shared: vec![],
+            done: 0,
+            waker: None,
+        });
+        let wait = Condvar::new();
+        Self {
+            progress,
+            wait,
+        }
+    }
+
+    // NOTE(rescrv):  No user code runs under the progress lock, so a poisoned lock can only come
+    // from a panic in here; recover it rather than let one partition take down the rest.
+    fn lock(&self) -> MutexGuard<'_, ApplyProgress<C>> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record the results for partition, or None if applying to it panicked.
+    fn done(&self, partition: usize, results: Option<Option<Arc<C>>>) {
+        self.complete(|progress| match results {
+            Some(results) => progress.partitions[partition] = results,
+            None => progress.panicked.push(partition),
+        });
+    }
+
+    /// Record that partition was shared and so was put back without applying to it.
+    fn shared(&self, partition: usize, original: Arc<C>) {
+        self.complete(|progress| {
+            progress.partitions[partition] = Some(original);
+            progress.shared.push(partition);
+        });
+    }
+
+    fn complete(&self, record: impl FnOnce(&mut ApplyProgress<C>)) {
+        let waker = {
+            let mut progress = self.lock();
+            record(&mut progress);
+            progress.done += 1;
+            if progress.done < progress.partitions.len() {
+                return;
+            }
+            progress.waker.take()
+        };
+        self.wait.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn wait(&self) -> ApplyResults<C> {
+        let mut progress = self.lock();
+        while progress.done < progress.partitions.len() {
+            progress = self.wait.wait(progress).unwrap_or_else(PoisonError::into_inner);
+        }
+        Self::take(&mut progress)
+    }
+
+    fn poll(&self, waker: &Waker) -> Option<ApplyResults<C>> {
+        let mut progress = self.lock();
+        if progress.done < progress.partitions.len() {
+            progress.waker = Some(waker.clone());
+            None
+        } else {
+            Some(Self::take(&mut progress))
+        }
+    }
+
+    fn take(progress: &mut ApplyProgress<C>) -> ApplyResults<C> {
+        let partitions = std::mem::take(&mut progress.partitions);
+        let mut panicked = std::mem::take(&mut progress.panicked);
+        let mut shared = std::mem::take(&mut progress.shared);
+        if panicked.is_empty() && shared.is_empty() {
+            Ok(partitions)
+        } else {
+            panicked.sort();
+            shared.sort();
+            Err((partitions, ApplyError { panicked, shared }))
+        }
+    }
+}
+
+/// The in-flight application of changes started by [Partitioned::apply_parallel].  Call
+/// [Self::wait] to block until every partition has been applied, or await the handle from async
+/// code.  The collection is not usable until the application completes; dropping the handle
+/// blocks until it does, discarding any [ApplyError].
+pub struct PartitionedApplyHandle<'a, E: Entity, T: Debug, C: ComponentCollection<E, T>> {
+    partitioned: &'a mut Partitioned<E, T, C>,
+    agg: Option<Arc<ApplyAggregate<C>>>,
+}
+
+impl<'a, E: Entity, T: Debug, C: ComponentCollection<E, T>> PartitionedApplyHandle<'a, E, T, C> {
+    /// Block until the changes have been applied to every partition.
+    pub fn wait(mut self) -> Result<(), ApplyError> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<(), ApplyError> {
+        match self.agg.take() {
+            Some(agg) => self.install(agg.wait()),
+            None => Ok(()),
+        }
+    }
+
+    fn install(&mut self, results: ApplyResults<C>) -> Result<(), ApplyError> {
+        let (partitions, result) = match results {
+            Ok(partitions) => (partitions, Ok(())),
+            Err((partitions, err)) => (partitions, Err(err)),
+        };
+        self.partitioned.partitions = partitions;
+        self.partitioned.recount();
+        result
+    }
+}
+
+impl<'a, E: Entity, T: Debug, C: ComponentCollection<E, T>> Future for PartitionedApplyHandle<'a, E, T, C> {
+    type Output = Result<(), ApplyError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let Some(agg) = this.agg.as_ref() else {
+            return Poll::Ready(Ok(()));
+        };
+        if let Some(results) = agg.poll(cx.waker()) {
+            this.agg = None;
+            Poll::Ready(this.install(results))
+        } else {
+            Poll::Pending
         }
     }
 }
 
+impl<'a, E: Entity, T: Debug, C: ComponentCollection<E, T>> Drop for PartitionedApplyHandle<'a, E, T, C> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
 impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> ComponentCollection<E, T> for Partitioned<E, T, C> {
     type Ref<'a> = C::Ref<'a> where Self: 'a;
     type Consumed = std::iter::Flatten<std::vec::IntoIter<<C as ComponentCollection<E, T>>::Consumed>>;
@@ -251,7 +830,7 @@ impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> ComponentCollection<E, T
     }
 
     fn len(&self) -> usize {
-        self.partitions.iter().map(|p| p.as_ref().map(|c| c.len()).unwrap_or(0usize)).fold(0usize, usize::saturating_add)
+        self.total_len
     }
 
     fn lower_bound(&self, lower_bound: E) -> Option<E> {
@@ -269,21 +848,183 @@ impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> ComponentCollection<E, T
         None
     }
 
+    fn first(&self) -> Option<E> {
+        self.partitions.iter().flatten().find_map(|p| p.first())
+    }
+
+    fn last(&self) -> Option<E> {
+        self.partitions.iter().rev().flatten().find_map(|p| p.last())
+    }
+
+    fn contains_entity(&self, entity: E) -> bool {
+        let partition = self.partitioning.partition_of(entity);
+        self.partitions[partition].as_ref().map(|p| p.contains_entity(entity)).unwrap_or(false)
+    }
+
     fn get_ref(&self, entity: E) -> Option<Self::Ref<'_>> {
-        let partition = self.partitioning.lower_bound(entity);
+        let partition = self.partitioning.partition_of(entity);
         self.partitions[partition].as_ref().and_then(|p| p.get_ref(entity))
     }
 
-    fn consume(self) -> Self::Consumed {
-        let mut partitions = Vec::with_capacity(self.partitions.len());
-        for partition in self.partitions.into_iter().flatten() {
-            if let Some(partition) = Arc::into_inner(partition) {
-                partitions.push(partition.consume())
+    fn iter_range<'a>(&'a self, lo: E, hi: E) -> impl Iterator<Item = (E, T)>
+    where
+        T: Clone + 'a,
+    {
+        let first = self.partitioning.partition_of(lo);
+        let last = self.partitioning.partition_of(hi).max(first);
+        self.partitions[first..=last].iter().flatten().flat_map(move |p| p.iter_range(lo, hi))
+    }
+
+    fn get_cloned(&self, entity: E) -> Option<T>
+    where
+        T: Clone,
+    {
+        let partition = self.partitioning.partition_of(entity);
+        self.partitions[partition].as_ref().and_then(|p| p.get_cloned(entity))
+    }
+
+    /// Route each change to the partition that holds its entity, keeping the partitioning scheme.
+    fn apply<I: IntoIterator<Item = (E, ComponentChange<T>)>>(&mut self, changes: I) {
+        let mut partitioned: Vec<Vec<(E, ComponentChange<T>)>> = self.partitions.iter().map(|_| vec![]).collect();
+        for (entity, change) in changes {
+            partitioned[self.partitioning.partition_of(entity)].push((entity, change));
+        }
+        self.apply_inner(partitioned, Self::apply_to_partition);
+    }
+
+    fn unbind_all<I: IntoIterator<Item = E>>(&mut self, entities: I) {
+        let mut entities: Vec<E> = entities.into_iter().collect();
+        entities.sort();
+        entities.dedup();
+        let mut changes: Vec<Vec<(E, ComponentChange<T>)>> = self.partitions.iter().map(|_| vec![]).collect();
+        for entity in entities {
+            changes[self.partitioning.partition_of(entity)].push((entity, ComponentChange::Unbind));
+        }
+        let partitioning = Arc::clone(&self.partitioning);
+        self.apply(PartitionedChanges::new(&partitioning, changes));
+    }
+
+    /// Split the partition holding pivot, keeping the partitioning scheme in both halves.  Other
+    /// partitions move whole to the side they fall on.
+    ///
+    /// # Panics
+    ///
+    /// If someone holds a reference to the partition holding pivot.
+    fn split_at(self, pivot: E) -> (Self, Self) {
+        let at = self.partitioning.partition_of(pivot);
+        let mut lower = Vec::with_capacity(self.partitions.len());
+        let mut upper = Vec::with_capacity(self.partitions.len());
+        for (idx, partition) in self.partitions.into_iter().enumerate() {
+            if idx < at {
+                lower.push(partition);
+                upper.push(None);
+            } else if idx > at {
+                lower.push(None);
+                upper.push(partition);
+            } else if let Some(partition) = partition {
+                let Some(partition) = Arc::into_inner(partition) else {
+                    panic!("`split_at` method called while someone holds a reference to a partition");
+                };
+                let (lo, hi) = partition.split_at(pivot);
+                lower.push(Some(Arc::new(lo)));
+                upper.push(Some(Arc::new(hi)));
+            } else {
+                lower.push(None);
+                upper.push(None);
+            }
+        }
+        let lower = Self::from_arcs(Arc::clone(&self.partitioning), lower);
+        let upper = Self::from_arcs(self.partitioning, upper);
+        (lower, upper)
+    }
+
+    /// Split each partition by predicate, keeping the partitioning scheme in both halves.
+    ///
+    /// # Panics
+    ///
+    /// If someone holds a reference to a partition.
+    fn split_by_predicate<F: FnMut(E, &T) -> bool>(self, mut predicate: F) -> (Self, Self) {
+        let mut matching = Vec::with_capacity(self.partitions.len());
+        let mut non_matching = Vec::with_capacity(self.partitions.len());
+        for partition in self.partitions.into_iter() {
+            if let Some(partition) = partition {
+                let Some(partition) = Arc::into_inner(partition) else {
+                    panic!("`split_by_predicate` method called while someone holds a reference to a partition");
+                };
+                let (yes, no) = partition.split_by_predicate(&mut predicate);
+                matching.push(Some(yes).filter(|p| !p.is_empty()).map(Arc::new));
+                non_matching.push(Some(no).filter(|p| !p.is_empty()).map(Arc::new));
+            } else {
+                matching.push(None);
+                non_matching.push(None);
+            }
+        }
+        let matching = Self::from_arcs(Arc::clone(&self.partitioning), matching);
+        let non_matching = Self::from_arcs(self.partitioning, non_matching);
+        (matching, non_matching)
+    }
+
+    /// Drop whole partitions past the n'th entity and truncate the one partition it falls in,
+    /// keeping the partitioning scheme.
+    ///
+    /// # Panics
+    ///
+    /// If someone holds a reference to the partition that gets truncated.
+    fn truncate_to(&mut self, n: usize) {
+        let mut remaining = n;
+        for partition in self.partitions.iter_mut() {
+            let Some(p) = partition.as_mut() else {
+                continue;
+            };
+            if remaining == 0 {
+                *partition = None;
+            } else if remaining < p.len() {
+                let Some(p) = Arc::get_mut(p) else {
+                    panic!("`truncate_to` method called while someone holds a reference to a partition");
+                };
+                p.truncate_to(remaining);
+                remaining = 0;
             } else {
-                panic!("`consume` method called while someone holds a reference to a partition");
+                remaining -= p.len();
             }
         }
-        partitions.into_iter().flatten()
+        self.total_len = self.total_len.min(n);
+    }
+
+    /// Drop whole partitions before the n'th entity and skip into the one partition it falls in,
+    /// keeping the partitioning scheme.
+    ///
+    /// # Panics
+    ///
+    /// If someone holds a reference to the partition that gets skipped into.
+    fn skip_first(&mut self, n: usize) {
+        let mut remaining = n;
+        for partition in self.partitions.iter_mut() {
+            if remaining == 0 {
+                break;
+            }
+            let Some(p) = partition.as_mut() else {
+                continue;
+            };
+            if remaining < p.len() {
+                let Some(p) = Arc::get_mut(p) else {
+                    panic!("`skip_first` method called while someone holds a reference to a partition");
+                };
+                p.skip_first(remaining);
+                remaining = 0;
+            } else {
+                remaining -= p.len();
+                *partition = None;
+            }
+        }
+        self.total_len = self.total_len.saturating_sub(n);
+    }
+
+    fn consume(self) -> Self::Consumed {
+        match self.try_consume() {
+            Ok(consumed) => consumed,
+            Err(_) => panic!("`consume` method called while someone holds a reference to a partition"),
+        }
     }
 }
 
@@ -291,20 +1032,18 @@ impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> Default for Partitioned<
     fn default() -> Self {
         let partitioning = Arc::new(NopPartitioningScheme);
         let partitions = vec![None];
-        let _phantom_t = std::marker::PhantomData;
-        Self {
-            partitioning,
-            partitions,
-            _phantom_t,
-        }
+        Self::from_arcs(partitioning, partitions)
     }
 }
 
 impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> Debug for Partitioned<E, T, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        f.debug_struct("Partitioned<E, X>")
-            .field("partitioning", &self.partitioning)
-            .field("partitions", &self.partitions)
+        // NOTE(rescrv):  Show the number of entities in each partition rather than the partitions
+        // themselves; it's the skew between partitions that's actionable when debugging.
+        let partitions: Vec<Option<usize>> = self.partitions.iter().map(|p| p.as_ref().map(|c| c.len())).collect();
+        f.debug_struct("Partitioned")
+            .field("scheme", &self.partitioning)
+            .field("partitions", &partitions)
             .finish()
     }
 }
@@ -314,12 +1053,7 @@ impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> FromIterator<(E, T)> for
         let components = C::from_iter(iter);
         let partitioning = Arc::new(NopPartitioningScheme);
         let partitions = vec![Some(Arc::new(components))];
-        let _phantom_t = std::marker::PhantomData;
-        Self {
-            partitioning,
-            partitions,
-            _phantom_t,
-        }
+        Self::from_arcs(partitioning, partitions)
     }
 }
 
@@ -328,12 +1062,197 @@ impl<E: Entity, T: Debug, C: ComponentCollection<E, T>> FromIterator<(E, Compone
         let components = C::from_iter(iter);
         let partitioning = Arc::new(NopPartitioningScheme);
         let partitions = vec![Some(Arc::new(components))];
-        let _phantom_t = std::marker::PhantomData;
-        Self {
-            partitioning,
-            partitions,
-            _phantom_t,
+        Self::from_arcs(partitioning, partitions)
+    }
+}
+
+////////////////////////////////////////// PartitionedIter /////////////////////////////////////////
+
+/// The iterator returned by [Partitioned::iter].  It walks the partitions in order, skipping those
+/// that are empty.
+pub struct PartitionedIter<'a, E: Entity, T: Debug, C: ComponentCollection<E, T>> {
+    partitioned: &'a Partitioned<E, T, C>,
+    partition: usize,
+    next: Option<E>,
+}
+
+impl<'a, E: Entity, T: Debug + Clone + 'a, C: ComponentCollection<E, T> + 'a> Iterator for PartitionedIter<'a, E, T, C> {
+    type Item = (E, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let partitions = &self.partitioned.partitions;
+        loop {
+            let partition = partitions[self.partition].as_ref();
+            if let (Some(p), Some(entity)) = (partition, self.next) {
+                self.next = lower_bound_after(&**p, entity);
+                return Some((entity, p.get_cloned(entity)?));
+            }
+            if self.partition + 1 >= partitions.len() {
+                return None;
+            }
+            self.partition += 1;
+            self.next = partitions[self.partition].as_ref().and_then(|p| p.first());
+        }
+    }
+}
+
+//////////////////////////////////////////// checkpoint ////////////////////////////////////////////
+
+/// An error restoring a [Partitioned] collection from bytes.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum CheckpointError {
+    /// The buffer ended before the checkpoint did.
+    Truncated,
+    /// The buffer is not laid out like a checkpoint.
+    Malformed,
+    /// An entity or component failed to decode.
+    Decode(rmp_serde::decode::Error),
+}
+
+#[cfg(feature = "serde")]
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Truncated => write!(f, "checkpoint truncated"),
+            Self::Malformed => write!(f, "checkpoint malformed"),
+            Self::Decode(err) => write!(f, "checkpoint failed to decode: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for CheckpointError {}
+
+#[cfg(feature = "serde")]
+struct CheckpointReader<'a> {
+    bytes: &'a [u8],
+}
+
+#[cfg(feature = "serde")]
+impl<'a> CheckpointReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], CheckpointError> {
+        if self.bytes.len() < len {
+            return Err(CheckpointError::Truncated);
         }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, CheckpointError> {
+        let bytes = self.read_bytes(8)?;
+        // SAFETY(rescrv):  read_bytes returns exactly eight bytes.
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_value<V: serde::de::DeserializeOwned>(&mut self) -> Result<V, CheckpointError> {
+        let len = self.read_u64()? as usize;
+        let bytes = self.read_bytes(len)?;
+        rmp_serde::from_slice(bytes).map_err(CheckpointError::Decode)
+    }
+}
+
+#[cfg(feature = "serde")]
+fn write_value<V: serde::Serialize>(buf: &mut Vec<u8>, value: &V) {
+    let bytes = rmp_serde::to_vec(value).expect("checkpointed values should serialize");
+    buf.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    buf.extend_from_slice(&bytes);
+}
+
+#[cfg(feature = "serde")]
+impl<
+        E: Entity + Send + Sync + serde::Serialize + serde::de::DeserializeOwned + 'static,
+        T: Debug + serde::Serialize + serde::de::DeserializeOwned,
+        C: ComponentCollection<E, T>,
+    > Partitioned<E, T, C>
+{
+    /// Checkpoint the collection to a byte buffer.  The layout is the number of dividers in the
+    /// partitioning scheme followed by each divider, then the number of partitions followed by
+    /// each partition.  A partition is a present flag, the number of entities in the partition,
+    /// and then the entity-component pairs in order.  Every integer is a little-endian u64 and
+    /// every entity, divider, or pair is a length-prefixed MessagePack value.
+    ///
+    /// # Panics
+    ///
+    /// If an entity or component fails to serialize.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend_from_slice(&(self.partitioning.len() as u64).to_le_bytes());
+        for idx in 0..self.partitioning.len() {
+            write_value(&mut buf, &self.partitioning.partition(idx));
+        }
+        buf.extend_from_slice(&(self.partitions.len() as u64).to_le_bytes());
+        for partition in self.partitions.iter() {
+            let Some(partition) = partition else {
+                buf.extend_from_slice(&0u64.to_le_bytes());
+                continue;
+            };
+            buf.extend_from_slice(&1u64.to_le_bytes());
+            buf.extend_from_slice(&(partition.len() as u64).to_le_bytes());
+            let mut target = E::default();
+            while let Some(entity) = partition.lower_bound(target) {
+                // SAFETY(rescrv):  lower_bound returned the entity, so it is present.
+                let component = partition.get_ref(entity).expect("entity should be present");
+                write_value(&mut buf, &(entity, &*component));
+                if entity == E::max_value() {
+                    break;
+                }
+                target = entity.increment();
+            }
+        }
+        buf
+    }
+
+    /// Restore a collection from the output of [Self::to_bytes].  The partitioning scheme of the
+    /// restored collection is a [VecPartitioningScheme] with the same dividers as the original.
+    ///
+    /// Returns [CheckpointError::Malformed] if the dividers are out of order or duplicated, if a
+    /// present flag is neither 0 nor 1, or if a partition's entities are out of order, duplicated,
+    /// or fall outside the partition.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CheckpointError> {
+        let mut reader = CheckpointReader { bytes };
+        let num_dividers = reader.read_u64()?;
+        let mut dividers = vec![];
+        for _ in 0..num_dividers {
+            dividers.push(reader.read_value::<E>()?);
+        }
+        if dividers.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(CheckpointError::Malformed);
+        }
+        let partitioning: Arc<dyn PartitioningScheme<E>> =
+            Arc::new(VecPartitioningScheme::from(dividers));
+        let num_partitions = reader.read_u64()?;
+        if num_partitions != num_dividers + 1 {
+            return Err(CheckpointError::Malformed);
+        }
+        let mut partitions = vec![];
+        for idx in 0..num_partitions as usize {
+            match reader.read_u64()? {
+                0 => {
+                    partitions.push(None);
+                    continue;
+                }
+                1 => {}
+                _ => return Err(CheckpointError::Malformed),
+            }
+            let num_pairs = reader.read_u64()?;
+            let mut pairs: Vec<(E, T)> = vec![];
+            for _ in 0..num_pairs {
+                let (entity, component) = reader.read_value::<(E, T)>()?;
+                if pairs.last().map(|(prev, _)| *prev >= entity).unwrap_or(false)
+                    || partitioning.partition_of(entity) != idx
+                {
+                    return Err(CheckpointError::Malformed);
+                }
+                pairs.push((entity, component));
+            }
+            partitions.push(Some(C::from_iter(pairs)));
+        }
+        if !reader.bytes.is_empty() {
+            return Err(CheckpointError::Malformed);
+        }
+        Ok(Self::from(&partitioning, partitions))
     }
 }
 
@@ -344,21 +1263,25 @@ mod tests {
     extern crate proptest;
 
     use std::fmt::Debug;
+    use std::future::Future;
     use std::sync::Arc;
 
     use proptest::strategy::Strategy;
 
-    use crate::tests::{arb_entity, is_free_of_duplicates};
-    use crate::{ComponentCollection, Entity, MutableComponentCollection};
-    use crate::component::tests::collection_properties;
+    use crate::tests::{arb_entity, is_free_of_duplicates, with_extremes};
+    use crate::{
+        ComponentChange, ComponentCollection, CopyOnWriteComponentCollection, Entity,
+        MutableComponentCollection, ThreadPool,
+    };
+    use crate::component::tests::{changes_for, collection_properties, reference_apply};
 
-    use super::{NopPartitioningScheme, PartitioningScheme, Partitioned, VecPartitioningScheme};
+    use super::{ApplyError, NopPartitioningScheme, PartitionedChanges, PartitioningScheme, Partitioned, ValidationError, VecPartitioningScheme, WeightedPartitioningScheme};
 
     proptest::prop_compose! {
-        pub fn arb_entities()(mut entities in proptest::collection::vec(arb_entity(), 0..=65536).prop_filter("dedupe", is_free_of_duplicates)) -> Vec<(u128, usize)> {
+        pub fn arb_entities()(mut entities in proptest::collection::vec(arb_entity(), 0..=65536).prop_filter("dedupe", is_free_of_duplicates), default in proptest::bool::ANY, max in proptest::bool::ANY) -> Vec<(u128, usize)> {
             entities.sort();
             entities.dedup();
-            entities.into_iter().enumerate().map(|(i, x)| (x, i)).collect()
+            with_extremes(entities, default, max).into_iter().enumerate().map(|(i, x)| (x, i)).collect()
         }
     }
 
@@ -370,7 +1293,7 @@ mod tests {
         }
     }
 
-    pub fn partition_properties<E: Entity, T: Debug + Clone + Eq, C: ComponentCollection<E, T>>(
+    pub fn partition_properties<E: Entity + Send + Sync, T: Debug + Clone + Eq, C: ComponentCollection<E, T>>(
         collection: Vec<(E, T)>,
         partitioning: Arc<dyn PartitioningScheme<E>>,
     ) {
@@ -392,7 +1315,13 @@ mod tests {
                 assert!(partitioned.get_ref(e.decrement()).is_none());
             }
         }
-        // TODO(apply);
+        let mut applied = Partitioned::from(&partitioning, C::from_iter(collection.clone()).partition(&*partitioning));
+        ComponentCollection::apply(&mut applied, changes_for(&collection));
+        assert!(Arc::ptr_eq(&partitioning, applied.partitioning_scheme()));
+        let expected = reference_apply(collection.clone(), changes_for(&collection));
+        assert_eq!(expected, applied.consume().collect::<Vec<_>>());
+        let iterated: Vec<(E, T)> = partitioned.iter().collect();
+        assert_eq!(collection, iterated);
         let consumed: Vec<(E, T)> = partitioned.consume().collect();
         assert_eq!(collection, consumed);
     }
@@ -406,4 +1335,711 @@ mod tests {
             partition_properties::<u128, usize, MutableComponentCollection<u128, usize>>(entities, partitioning);
         }
     }
+
+    proptest::proptest! {
+        #[test]
+        fn lower_bound_batch(entities in arb_entities(), partitions in arb_partitions(), queries in proptest::collection::vec(arb_entity(), 0..64)) {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> = Arc::new(VecPartitioningScheme::from(partitions));
+            let collection = CopyOnWriteComponentCollection::from_iter(entities.clone());
+            let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+            let mut queries: Vec<u128> = queries.into_iter().chain(entities.iter().step_by(97).flat_map(|(e, _)| [e.decrement(), *e, e.increment()])).collect();
+            queries.sort();
+            let expected: Vec<Option<u128>> = queries.iter().map(|q| partitioned.lower_bound(*q)).collect();
+            assert_eq!(expected, partitioned.lower_bound_batch(&queries));
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn partition_of_lies_within_entities_in_partition(entity in arb_entity(), partitions in arb_partitions()) {
+            let partitioning = VecPartitioningScheme::from(partitions);
+            let partition = partitioning.partition_of(entity);
+            let (lower, upper) = partitioning.entities_in_partition(partition).unwrap();
+            assert!(lower.map(|lower| lower <= entity).unwrap_or(true));
+            assert!(upper.map(|upper| entity < upper).unwrap_or(true));
+        }
+    }
+
+    #[test]
+    fn partition_of() {
+        let partitioning = VecPartitioningScheme::from(vec![10u64, 20, 30]);
+        for (entity, partition) in [(0u64, 0usize), (9, 0), (10, 1), (19, 1), (20, 2), (29, 2), (30, 3), (u64::MAX, 3)] {
+            assert_eq!(partition, partitioning.partition_of(entity));
+        }
+        assert_eq!(Some((None, Some(10))), partitioning.entities_in_partition(0));
+        assert_eq!(Some((Some(10), Some(20))), partitioning.entities_in_partition(1));
+        assert_eq!(Some((Some(20), Some(30))), partitioning.entities_in_partition(2));
+        assert_eq!(Some((Some(30), None)), partitioning.entities_in_partition(3));
+        assert_eq!(None, partitioning.entities_in_partition(4));
+        let partitioning = NopPartitioningScheme;
+        assert_eq!(0, PartitioningScheme::<u64>::partition_of(&partitioning, 42));
+        assert_eq!(Some((None, None)), PartitioningScheme::<u64>::entities_in_partition(&partitioning, 0));
+        assert_eq!(None, PartitioningScheme::<u64>::entities_in_partition(&partitioning, 1));
+    }
+
+    #[test]
+    fn structural_eq() {
+        let partitioning: Arc<dyn PartitioningScheme<u64>> = Arc::new(VecPartitioningScheme::from(vec![10u64, 20]));
+        let same: Arc<dyn PartitioningScheme<u64>> = Arc::new(VecPartitioningScheme::from(vec![10u64, 20]));
+        assert!(!Arc::ptr_eq(&partitioning, &same));
+        assert!(partitioning.structural_eq(&*same));
+        assert!(partitioning.structural_eq(&*partitioning));
+        assert!(!partitioning.structural_eq(&VecPartitioningScheme::from(vec![10u64, 30])));
+        assert!(!partitioning.structural_eq(&VecPartitioningScheme::from(vec![10u64])));
+        assert!(!partitioning.structural_eq(&NopPartitioningScheme));
+        assert!(NopPartitioningScheme.structural_eq(&VecPartitioningScheme::<u64>::from(vec![])));
+        let weighted = WeightedPartitioningScheme::new(vec![(10u64, 1u64), (20, 1), (30, 1)], 3);
+        let dividers: Vec<u64> = (0..weighted.len()).map(|p| weighted.partition(p)).collect();
+        assert!(weighted.structural_eq(&VecPartitioningScheme::from(dividers)));
+    }
+
+    #[test]
+    fn iter() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30, 40]));
+        let pairs: Vec<(u128, usize)> = [0u128, 5, 9, 10, 35, 39, u128::MAX].iter().map(|e| (*e, *e as usize)).collect();
+        let collection = MutableComponentCollection::from_iter(pairs.clone());
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert_eq!((2, 0), partitioned.component_histogram()[2]);
+        let iterated: Vec<(u128, usize)> = partitioned.iter().collect();
+        assert_eq!(pairs, iterated);
+        assert_eq!(pairs, partitioned.consume().collect::<Vec<_>>());
+        let empty = Partitioned::<u128, usize, MutableComponentCollection<u128, usize>>::default();
+        assert_eq!(0, empty.iter().count());
+    }
+
+    #[test]
+    fn iter_holds_no_lock_between_items() {
+        fn check<C: ComponentCollection<u128, usize>>(partitioning: &Arc<dyn PartitioningScheme<u128>>) {
+            let pairs: Vec<(u128, usize)> = (1..40u128).map(|e| (e, e as usize)).collect();
+            let collection = C::from_iter(pairs.clone());
+            let partitioned = Partitioned::from(partitioning, collection.partition(&**partitioning));
+            let mut peekable = partitioned.iter().peekable();
+            assert_eq!(Some(&(1, 1)), peekable.peek());
+            assert_eq!(pairs, peekable.collect::<Vec<_>>());
+            let zipped: Vec<((u128, usize), (u128, usize))> = partitioned.iter().zip(partitioned.iter().skip(1)).collect();
+            assert_eq!(38, zipped.len());
+            assert!(zipped.iter().all(|((lhs, _), (rhs, _))| lhs + 1 == *rhs));
+        }
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30]));
+        check::<MutableComponentCollection<u128, usize>>(&partitioning);
+        check::<crate::InsertOptimizedComponentCollection<u128, usize>>(&partitioning);
+        check::<crate::OrderedComponentCollection<u128, usize>>(&partitioning);
+    }
+
+    #[test]
+    fn unbind_all() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter((1..30u128).map(|e| (e, e as usize)));
+        let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        partitioned.unbind_all(vec![25u128, 10, 5, 100]);
+        assert_eq!(26, partitioned.len());
+        assert!(Arc::ptr_eq(&partitioning, partitioned.partitioning_scheme()));
+        for e in 1..30u128 {
+            assert_eq!(![5, 10, 25].contains(&e), partitioned.contains_entity(e));
+        }
+    }
+
+    #[test]
+    fn split_by_predicate_keeps_partitioning() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter((1..30u128).map(|e| (e, e as usize)));
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let (mut even, odd) = partitioned.split_by_predicate(|_, c| c % 2 == 0);
+        assert!(Arc::ptr_eq(&partitioning, even.partitioning_scheme()));
+        assert!(Arc::ptr_eq(&partitioning, odd.partitioning_scheme()));
+        assert_eq!((14, 15), (even.len(), odd.len()));
+        assert_eq!(vec![(0, 4), (1, 5), (2, 5)], even.component_histogram());
+        let changes = vec![vec![(3, ComponentChange::Value(3))], vec![], vec![(20, ComponentChange::Unbind)]];
+        even.apply(PartitionedChanges::new(&partitioning, changes));
+        assert_eq!(14, even.len());
+        assert_eq!(Some(3), even.get_cloned(3));
+        assert!(!even.contains_entity(20));
+        assert!(odd.iter().all(|(e, _)| e % 2 == 1));
+    }
+
+    #[test]
+    fn truncate_to_and_skip_first_keep_partitioning() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30]));
+        let partitioned = || {
+            let collection = CopyOnWriteComponentCollection::from_iter((1..40u128).map(|e| (e, e as usize)));
+            Partitioned::from(&partitioning, collection.partition(&*partitioning))
+        };
+        for n in [0, 5, 9, 10, 25, 39, 50] {
+            let mut truncated = partitioned();
+            truncated.truncate_to(n);
+            assert!(Arc::ptr_eq(&partitioning, truncated.partitioning_scheme()));
+            assert_eq!(n.min(39), truncated.len());
+            let expected: Vec<(u128, usize)> = (1..40u128).map(|e| (e, e as usize)).take(n).collect();
+            assert_eq!(expected, truncated.iter().collect::<Vec<_>>());
+            let mut skipped = partitioned();
+            skipped.skip_first(n);
+            assert!(Arc::ptr_eq(&partitioning, skipped.partitioning_scheme()));
+            assert_eq!(39usize.saturating_sub(n), skipped.len());
+            let expected: Vec<(u128, usize)> = (1..40u128).map(|e| (e, e as usize)).skip(n).collect();
+            assert_eq!(expected, skipped.iter().collect::<Vec<_>>());
+            let changes = vec![vec![(1, ComponentChange::Value(1))], vec![], vec![], vec![]];
+            skipped.apply(PartitionedChanges::new(&partitioning, changes));
+            assert_eq!(Some(1), skipped.first());
+        }
+    }
+
+    #[test]
+    fn apply_through_trait_keeps_partitioning() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter((1..30u128).map(|e| (e, e as usize)));
+        let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let changes: std::collections::BTreeMap<u128, usize> = [(35, 35), (10, 100), (5, 50)].into_iter().collect();
+        ComponentCollection::apply(&mut partitioned, changes.into_iter().map(|(e, v)| (e, ComponentChange::Value(v))));
+        assert!(Arc::ptr_eq(&partitioning, partitioned.partitioning_scheme()));
+        assert_eq!(30, partitioned.len());
+        assert_eq!(Some(50), partitioned.get_cloned(5));
+        assert_eq!(Some(100), partitioned.get_cloned(10));
+        assert_eq!(Some(35), partitioned.get_cloned(35));
+        assert_eq!(Some(35), partitioned.get_partition_by_index(2).and_then(|p| p.last()));
+    }
+
+    #[test]
+    fn try_consume_while_shared() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter((1..30u128).map(|e| (e, e as usize)));
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let held = partitioned.get_partition_by_index(1).unwrap();
+        let Err(partitioned) = partitioned.try_consume() else {
+            panic!("try_consume should fail while a partition is held");
+        };
+        assert_eq!(29, partitioned.len());
+        assert!(Arc::ptr_eq(&partitioning, partitioned.partitioning_scheme()));
+        assert_eq!(Some(15), partitioned.get_cloned(15));
+        drop(held);
+        let Ok(consumed) = partitioned.try_consume() else {
+            panic!("try_consume should succeed once the partition is released");
+        };
+        assert_eq!((1..30u128).map(|e| (e, e as usize)).collect::<Vec<_>>(), consumed.collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "`consume` method called while someone holds a reference to a partition")]
+    fn consume_while_shared() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10]));
+        let collection = CopyOnWriteComponentCollection::from_iter((1..30u128).map(|e| (e, e as usize)));
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let _held = partitioned.get_partition_by_index(0).unwrap();
+        let _ = partitioned.consume();
+    }
+
+    #[test]
+    fn component_histogram() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![11, 21, 31, 41]));
+        let collection = CopyOnWriteComponentCollection::from_iter((1..=30u128).map(|e| (e, e as usize)));
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let histogram = partitioned.component_histogram();
+        assert_eq!(vec![(0, 10), (1, 10), (2, 10), (3, 0), (4, 0)], histogram);
+        assert_eq!(partitioned.len(), histogram.iter().map(|(_, c)| c).sum::<usize>());
+        assert!(!partitioned.is_balanced(1.0));
+        assert!(!partitioned.is_balanced(1.5));
+        assert!(partitioned.is_balanced(2.0));
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![11, 21]));
+        let collection = CopyOnWriteComponentCollection::from_iter((1..=30u128).map(|e| (e, e as usize)));
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert_eq!(vec![(0, 10), (1, 10), (2, 10)], partitioned.component_histogram());
+        assert!(partitioned.is_balanced(1.0));
+        let collection = CopyOnWriteComponentCollection::from_iter((1..=31u128).map(|e| (e, e as usize)));
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert_eq!(vec![(0, 10), (1, 10), (2, 11)], partitioned.component_histogram());
+        assert!(!partitioned.is_balanced(1.0));
+        assert!(partitioned.is_balanced(1.1));
+        let partitioned: Partitioned<u128, usize, CopyOnWriteComponentCollection<u128, usize>> = Partitioned::default();
+        assert_eq!(0, partitioned.component_histogram().iter().map(|(_, c)| c).sum::<usize>());
+        assert!(partitioned.is_balanced(1.0));
+    }
+
+    #[test]
+    fn weighted_partitioning() {
+        fn totals(scheme: &dyn PartitioningScheme<u128>, weights: &[(u128, u64)]) -> Vec<u64> {
+            let mut totals = vec![0u64; scheme.len() + 1];
+            for (e, w) in weights {
+                totals[scheme.partition_of(*e)] += w;
+            }
+            totals
+        }
+        // A few heavy entities at the front, followed by a long tail of light ones.
+        let weights: Vec<(u128, u64)> = (1..=1000u128).map(|e| (e, if e <= 10 { 1000 } else { 10 })).collect();
+        let total: u64 = weights.iter().map(|(_, w)| w).sum();
+        let scheme = WeightedPartitioningScheme::new(weights.iter().rev().copied(), 4);
+        assert_eq!(3, scheme.len());
+        for t in totals(&scheme, &weights) {
+            assert!(t <= total / 4 + 1000, "{} > {}", t, total / 4 + 1000);
+            assert!(t + 1000 >= total / 4, "{} < {}", t, total / 4 - 1000);
+        }
+        let by_count = VecPartitioningScheme::from(vec![251, 501, 751]);
+        let by_count = totals(&by_count, &weights);
+        assert!(by_count.iter().max().unwrap() - by_count.iter().min().unwrap() > 5000);
+        // Uniform weights divide by count.
+        let scheme = WeightedPartitioningScheme::from_fn(1..=100u128, |_| 1, 4);
+        assert_eq!(vec![26, 51, 76], (0..scheme.len()).map(|p| scheme.partition(p)).collect::<Vec<_>>());
+        // One entity that outweighs everything else.
+        let scheme = WeightedPartitioningScheme::from_fn(1..=100u128, |e| if e == 50 { 1_000_000 } else { 1 }, 4);
+        assert_eq!(vec![51], (0..scheme.len()).map(|p| scheme.partition(p)).collect::<Vec<_>>());
+        let scheme = WeightedPartitioningScheme::new(std::iter::empty::<(u128, u64)>(), 4);
+        assert!(scheme.is_empty());
+    }
+
+    #[test]
+    fn first_last_skip_empty_partitions() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30, 40]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(15u128, 15usize), (17, 17), (33, 33)]);
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert!(partitioned.get_partition_by_index(0).is_none());
+        assert!(partitioned.get_partition_by_index(4).is_none());
+        assert_eq!(Some(15), partitioned.first());
+        assert_eq!(Some(33), partitioned.last());
+        let empty: Partitioned<u128, usize, CopyOnWriteComponentCollection<u128, usize>> =
+            Partitioned::from(&partitioning, vec![None, None, None, None, None]);
+        assert_eq!(None, empty.first());
+        assert_eq!(None, empty.last());
+    }
+
+    #[test]
+    fn get_ref_on_divider() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(10u128, 10usize), (20, 20)]);
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert_eq!(10, *partitioned.get_ref(10).unwrap());
+        assert_eq!(Some(20), partitioned.get_cloned(20));
+    }
+
+    #[test]
+    fn generic_over_scheme() {
+        fn partitions_of<PS: PartitioningScheme<u128>>(scheme: PS, entities: &[u128]) -> Vec<usize> {
+            assert!(!scheme.is_empty());
+            assert_eq!(2, scheme.len());
+            assert_eq!(20, scheme.partition(1));
+            assert_eq!(Some((Some(10), Some(20))), scheme.entities_in_partition(1));
+            entities.iter().map(|e| scheme.partition_of(*e)).collect()
+        }
+        let entities = [0u128, 9, 10, 11, 20, 21, u128::MAX];
+        let expected = vec![0, 0, 1, 1, 2, 2, 2];
+        let scheme = VecPartitioningScheme::from(vec![10u128, 20]);
+        assert_eq!(expected, partitions_of(&scheme, &entities));
+        let arc: Arc<dyn PartitioningScheme<u128>> = Arc::new(VecPartitioningScheme::from(vec![10u128, 20]));
+        assert_eq!(expected, partitions_of(&*arc, &entities));
+        assert_eq!(expected, partitions_of(Arc::clone(&arc), &entities));
+        assert_eq!(expected, partitions_of(scheme, &entities));
+    }
+
+    #[test]
+    fn debug_shows_partition_sizes() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (6, 6), (25, 25), (30, 30), (31, 31), (99, 99)]);
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert_eq!(
+            "Partitioned { scheme: VecPartitioningScheme { entities: [10, 20, 30] }, partitions: [Some(2), None, Some(1), Some(3)] }",
+            format!("{:?}", partitioned)
+        );
+    }
+
+    #[test]
+    fn validate_against() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (10, 10), (19, 19), (20, 20)]);
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert_eq!(Ok(()), partitioned.validate_against(&*partitioning));
+        assert_eq!(Ok(()), partitioned.validate_against(&VecPartitioningScheme::from(vec![10u128, 20])));
+        let misplaced = Partitioned::from(&partitioning, vec![
+            Some(CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (10, 10)])),
+            None,
+            Some(CopyOnWriteComponentCollection::from_iter(vec![(20u128, 20usize)])),
+        ]);
+        assert_eq!(
+            Err(ValidationError::MisplacedEntity { partition: 0, entity: "10".to_string() }),
+            misplaced.validate_against(&*partitioning)
+        );
+        let other: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30]));
+        assert_eq!(
+            Err(ValidationError::PartitionCount { expected: 4, found: 3 }),
+            partitioned.validate_against(&*other)
+        );
+        let nop: Arc<dyn PartitioningScheme<u128>> = Arc::new(NopPartitioningScheme);
+        let unpartitioned = Partitioned::from(&nop, vec![Some(CopyOnWriteComponentCollection::from_iter(vec![(u128::MAX, 1usize)]))]);
+        assert_eq!(Ok(()), unpartitioned.validate_against(&*nop));
+        assert_eq!(Ok(()), unpartitioned.validate_against(&NopPartitioningScheme));
+    }
+
+    #[test]
+    fn get_ref_after_apply_to_empty_partition() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (25, 25)]);
+        let mut partitioned =
+            Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert!(partitioned.get_partition_by_index(1).is_none());
+        assert!(partitioned.get_ref(15).is_none());
+        partitioned.apply(PartitionedChanges::new(&partitioning, vec![
+            vec![],
+            vec![(15, ComponentChange::Value(15))],
+            vec![],
+            vec![(35, ComponentChange::Value(35))],
+        ]));
+        assert!(partitioned.get_partition_by_index(1).is_some());
+        for e in [5u128, 15, 25, 35] {
+            assert_eq!(e as usize, *partitioned.get_ref(e).unwrap());
+        }
+        for e in [0u128, 14, 16, 24, 26, 34, 36] {
+            assert!(partitioned.get_ref(e).is_none());
+        }
+        partitioned.apply(PartitionedChanges::new(&partitioning, vec![
+            vec![],
+            vec![(15, ComponentChange::Unbind)],
+            vec![],
+            vec![],
+        ]));
+        assert!(partitioned.get_partition_by_index(1).is_none());
+        assert!(partitioned.get_ref(15).is_none());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn len_tracks_changes(
+            initial in proptest::collection::btree_set(0u128..512, 0..256),
+            mut dividers in proptest::collection::vec(0u128..512, 0..8),
+            batches in proptest::collection::vec(proptest::collection::vec((0u128..512, proptest::option::of(0usize..8)), 0..64), 0..6),
+        ) {
+            dividers.sort();
+            dividers.dedup();
+            let partitioning: Arc<dyn PartitioningScheme<u128>> = Arc::new(VecPartitioningScheme::from(dividers));
+            let mut model: std::collections::BTreeMap<u128, usize> = initial.iter().map(|e| (*e, 0)).collect();
+            let from_iter: Partitioned<u128, usize, CopyOnWriteComponentCollection<u128, usize>> = Partitioned::from_iter(model.clone());
+            assert_eq!(model.len(), from_iter.len());
+            let collection = CopyOnWriteComponentCollection::from_iter(model.clone());
+            let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+            assert_eq!(model.len(), partitioned.len());
+            let thread_pool = ThreadPool::new("len_tracks_changes", 2);
+            for (idx, mut batch) in batches.into_iter().enumerate() {
+                batch.sort_by_key(|(e, _)| *e);
+                batch.dedup_by_key(|(e, _)| *e);
+                let mut changes: Vec<Vec<(u128, ComponentChange<usize>)>> = (0..=partitioning.len()).map(|_| vec![]).collect();
+                for (e, t) in batch {
+                    let change = match t {
+                        Some(t) => {
+                            model.insert(e, t);
+                            ComponentChange::Value(t)
+                        }
+                        None => {
+                            model.remove(&e);
+                            ComponentChange::Unbind
+                        }
+                    };
+                    changes[partitioning.partition_of(e)].push((e, change));
+                }
+                match idx % 3 {
+                    0 => partitioned.apply(PartitionedChanges::new(&partitioning, changes)),
+                    1 => ComponentCollection::apply(&mut partitioned, changes.into_iter().flatten()),
+                    _ => partitioned.apply_parallel(&thread_pool, PartitionedChanges::new(&partitioning, changes)).wait().unwrap(),
+                }
+                assert_eq!(model.len(), partitioned.len());
+                assert_eq!(model.len(), partitioned.component_histogram().iter().map(|(_, c)| c).sum::<usize>());
+            }
+            let (lower, upper) = partitioned.split_at(256);
+            assert_eq!(model.range(..256).count(), lower.len());
+            assert_eq!(model.range(256..).count(), upper.len());
+            thread_pool.shutdown();
+        }
+    }
+
+    fn apply_parallel_changes(partitioning: &Arc<dyn PartitioningScheme<u128>>) -> PartitionedChanges<u128, usize> {
+        PartitionedChanges::new(partitioning, vec![
+            vec![(5, ComponentChange::Unbind)],
+            vec![(15, ComponentChange::Value(16))],
+            vec![(25, ComponentChange::Value(25))],
+        ])
+    }
+
+    fn check_apply_parallel(partitioned: &Partitioned<u128, usize, CopyOnWriteComponentCollection<u128, usize>>) {
+        assert_eq!(None, partitioned.get_cloned(5));
+        assert_eq!(Some(16), partitioned.get_cloned(15));
+        assert_eq!(Some(25), partitioned.get_cloned(25));
+        assert_eq!(2, partitioned.len());
+    }
+
+    #[test]
+    fn apply_parallel_wait() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (15, 15)]);
+        let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let thread_pool = ThreadPool::new("apply_parallel_wait", 2);
+        partitioned.apply_parallel(&thread_pool, apply_parallel_changes(&partitioning)).wait().unwrap();
+        check_apply_parallel(&partitioned);
+    }
+
+    #[test]
+    fn apply_parallel_inline() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (15, 15)]);
+        let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let thread_pool = ThreadPool::new("apply_parallel_inline", 0);
+        partitioned.apply_parallel(&thread_pool, apply_parallel_changes(&partitioning)).wait().unwrap();
+        check_apply_parallel(&partitioned);
+    }
+
+    #[test]
+    fn apply_parallel_future() {
+        struct Unpark(std::thread::Thread);
+
+        impl std::task::Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (15, 15)]);
+        let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let thread_pool = ThreadPool::new("apply_parallel_future", 2);
+        let waker = std::task::Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = std::task::Context::from_waker(&waker);
+        {
+            let mut handle = std::pin::pin!(partitioned.apply_parallel(&thread_pool, apply_parallel_changes(&partitioning)));
+            let result = loop {
+                if let std::task::Poll::Ready(result) = handle.as_mut().poll(&mut cx) {
+                    break result;
+                }
+                std::thread::park();
+            };
+            assert_eq!(Ok(()), result);
+        }
+        check_apply_parallel(&partitioned);
+    }
+
+    #[test]
+    fn apply_parallel_shared_partition() {
+        for threads in [0, 2] {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+            let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (15, 15)]);
+            let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("apply_parallel_shared_partition", threads);
+            // Holding a partition leaves it as it was.
+            let held = partitioned.get_partition_by_index(1).unwrap();
+            let result = partitioned.apply_parallel(&thread_pool, apply_parallel_changes(&partitioning)).wait();
+            assert_eq!(Err(ApplyError { panicked: vec![], shared: vec![1] }), result);
+            assert_eq!(None, partitioned.get_cloned(5));
+            assert_eq!(Some(15), partitioned.get_cloned(15));
+            assert_eq!(Some(25), partitioned.get_cloned(25));
+            assert_eq!(2, partitioned.len());
+            assert!(Arc::ptr_eq(&held, &partitioned.get_partition_by_index(1).unwrap()));
+            drop(held);
+            // The partition takes the next batch once released.
+            partitioned.apply_parallel(&thread_pool, apply_parallel_changes(&partitioning)).wait().unwrap();
+            check_apply_parallel(&partitioned);
+            thread_pool.shutdown();
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn apply_parallel_panicking_partition() {
+        for threads in [0, 2] {
+            let partitioning: Arc<dyn PartitioningScheme<u128>> =
+                Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+            let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize), (15, 15)]);
+            let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+            let thread_pool = ThreadPool::new("apply_parallel_panicking_partition", threads);
+            // Unsorted changes to the empty partition trip the entity map's debug assertion.
+            let changes = PartitionedChanges::new(&partitioning, vec![
+                vec![(5, ComponentChange::Unbind)],
+                vec![(15, ComponentChange::Value(16))],
+                vec![(26, ComponentChange::Value(26)), (25, ComponentChange::Value(25))],
+            ]);
+            let result = partitioned.apply_parallel(&thread_pool, changes).wait();
+            assert_eq!(Err(ApplyError { panicked: vec![2], shared: vec![] }), result);
+            assert_eq!(None, partitioned.get_cloned(5));
+            assert_eq!(Some(16), partitioned.get_cloned(15));
+            assert_eq!(None, partitioned.get_cloned(25));
+            assert_eq!(1, partitioned.len());
+            // The pool survives to apply the next batch.
+            partitioned.apply_parallel(&thread_pool, apply_parallel_changes(&partitioning)).wait().unwrap();
+            check_apply_parallel(&partitioned);
+            thread_pool.shutdown();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "different partitioning scheme")]
+    fn apply_mismatched_partitioning() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10]));
+        let other: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10]));
+        let collection = CopyOnWriteComponentCollection::from_iter(vec![(5u128, 5usize)]);
+        let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        partitioned.apply(PartitionedChanges::new(&other, vec![vec![(5, ComponentChange::Unbind)], vec![]]));
+    }
+
+    #[test]
+    fn apply_in_place() {
+        // NOTE(rescrv):  InsertOptimized unbinds in place by freeing the slot, where a rebuild
+        // would leave it dense, so fragmentation tells the two apart.
+        type Collection = crate::InsertOptimizedComponentCollection<u128, usize>;
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20]));
+        let fragmentation = |partitioned: &Partitioned<u128, usize, Collection>| {
+            partitioned.get_partition_by_index(0).unwrap().fragmentation()
+        };
+        let unbind = || PartitionedChanges::new(&partitioning, vec![vec![(1, ComponentChange::Unbind)], vec![], vec![]]);
+        let thread_pool = ThreadPool::new("apply_in_place", 2);
+        for method in 0..3 {
+            let collection = Collection::from_iter((1..30u128).map(|e| (e, e as usize)));
+            let mut partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+            match method {
+                0 => partitioned.apply(unbind()),
+                1 => partitioned.apply_parallel(&thread_pool, unbind()).wait().unwrap(),
+                _ => ComponentCollection::apply(&mut partitioned, vec![(1, ComponentChange::Unbind)]),
+            }
+            assert_eq!(None, partitioned.get_cloned(1));
+            assert_eq!(28, partitioned.len());
+            assert!(fragmentation(&partitioned) > 0.0);
+        }
+        thread_pool.shutdown();
+    }
+
+    fn assert_partition_count<C: ComponentCollection<u128, usize>>(
+        partitioned: &Partitioned<u128, usize, C>,
+    ) {
+        assert_eq!(
+            partitioned.partitioning_scheme().len() + 1,
+            partitioned.partitions.len()
+        );
+    }
+
+    #[test]
+    fn partition_count() {
+        type Collection = MutableComponentCollection<u128, usize>;
+        let partitioned = Partitioned::<u128, usize, Collection>::default();
+        assert_partition_count(&partitioned);
+        let partitioned = Partitioned::<u128, usize, Collection>::from_iter(vec![(1u128, 1usize)]);
+        assert_partition_count(&partitioned);
+        let partitioned = Partitioned::<u128, usize, Collection>::from_iter(vec![(
+            1u128,
+            ComponentChange::Value(1usize),
+        )]);
+        assert_partition_count(&partitioned);
+        let partitioning: Arc<dyn PartitioningScheme<u128>> = Arc::new(NopPartitioningScheme);
+        let collection = MutableComponentCollection::from_iter(vec![(1u128, 1usize)]);
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert_partition_count(&partitioned);
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30]));
+        let collection = MutableComponentCollection::from_iter(vec![(1u128, 1usize)]);
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        assert_partition_count(&partitioned);
+    }
+
+    #[test]
+    #[should_panic]
+    fn partition_count_mismatch() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30]));
+        let collection = MutableComponentCollection::from_iter(vec![(1u128, 1usize)]);
+        Partitioned::from(&partitioning, vec![Some(collection)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn checkpoint() {
+        let partitioning: Arc<dyn PartitioningScheme<u128>> =
+            Arc::new(VecPartitioningScheme::from(vec![10, 20, 30]));
+        let entities: Vec<(u128, usize)> = vec![(1, 1), (5, 5), (25, 25), (30, 30), (45, 45)];
+        let collection = MutableComponentCollection::from_iter(entities.clone());
+        let partitioned = Partitioned::from(&partitioning, collection.partition(&*partitioning));
+        let bytes = partitioned.to_bytes();
+        let restored =
+            Partitioned::<u128, usize, MutableComponentCollection<u128, usize>>::from_bytes(&bytes)
+                .unwrap();
+        assert_eq!(3, restored.partitioning_scheme().len());
+        for idx in 0..3 {
+            assert_eq!(
+                partitioning.partition(idx),
+                restored.partitioning_scheme().partition(idx)
+            );
+        }
+        for idx in 0..4 {
+            assert_eq!(
+                partitioned.get_partition_by_index(idx).is_some(),
+                restored.get_partition_by_index(idx).is_some()
+            );
+        }
+        assert_eq!(entities, restored.consume().collect::<Vec<_>>());
+        assert!(
+            Partitioned::<u128, usize, MutableComponentCollection<u128, usize>>::from_bytes(
+                &bytes[..bytes.len() - 1]
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn checkpoint_malformed() {
+        use super::{write_value, CheckpointError};
+
+        type P = Partitioned<u128, usize, MutableComponentCollection<u128, usize>>;
+        fn checkpoint(dividers: &[u128], partitions: &[&[(u128, usize)]]) -> Vec<u8> {
+            let mut buf = vec![];
+            buf.extend_from_slice(&(dividers.len() as u64).to_le_bytes());
+            for divider in dividers {
+                write_value(&mut buf, divider);
+            }
+            buf.extend_from_slice(&(partitions.len() as u64).to_le_bytes());
+            for pairs in partitions {
+                buf.extend_from_slice(&1u64.to_le_bytes());
+                buf.extend_from_slice(&(pairs.len() as u64).to_le_bytes());
+                for pair in pairs.iter() {
+                    write_value(&mut buf, pair);
+                }
+            }
+            buf
+        }
+        let well_formed = checkpoint(&[10, 20], &[&[(1, 1), (5, 5)], &[(10, 10)], &[(25, 25)]]);
+        assert_eq!(4, P::from_bytes(&well_formed).unwrap().len());
+        let malformed = [
+            // Dividers out of order.
+            checkpoint(&[20, 10], &[&[(1, 1)], &[], &[(25, 25)]]),
+            // Duplicate dividers.
+            checkpoint(&[10, 10], &[&[(1, 1)], &[], &[(25, 25)]]),
+            // A present flag other than 0 or 1.
+            {
+                let mut bytes = checkpoint(&[], &[&[]]);
+                bytes[16] = 2;
+                bytes
+            },
+            // Pairs out of order.
+            checkpoint(&[10, 20], &[&[(5, 5), (1, 1)], &[], &[]]),
+            // Duplicate pairs.
+            checkpoint(&[10, 20], &[&[(5, 5), (5, 6)], &[], &[]]),
+            // A pair outside its partition.
+            checkpoint(&[10, 20], &[&[(1, 1)], &[(25, 25)], &[]]),
+            checkpoint(&[10, 20], &[&[(10, 10)], &[], &[]]),
+        ];
+        for bytes in malformed.iter() {
+            assert!(matches!(P::from_bytes(bytes), Err(CheckpointError::Malformed)));
+        }
+    }
 }
@@ -1,4 +1,6 @@
 use std::collections::LinkedList;
+use std::marker::PhantomData;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{Builder, JoinHandle};
@@ -8,20 +10,25 @@ pub type WorkUnit = dyn FnOnce() + Send;
 
 /////////////////////////////////////////// Coordination ///////////////////////////////////////////
 
+#[derive(Default)]
+struct WorkQueue {
+    queued: LinkedList<Box<WorkUnit>>,
+    active: usize,
+}
+
 #[derive(Default)]
 struct Coordination {
     shutdown: AtomicBool,
-    work: Mutex<LinkedList<Box<WorkUnit>>>,
+    work: Mutex<WorkQueue>,
     can_work: Condvar,
+    drained: Condvar,
 }
 
 impl Coordination {
     fn enqueue(&self, work_unit: Box<WorkUnit>) {
-        let mut list = LinkedList::default();
-        list.push_front(work_unit);
         {
             let mut work = self.work.lock().unwrap();
-            work.append(&mut list);
+            work.queued.push_back(work_unit);
         }
         self.can_work.notify_one();
     }
@@ -30,23 +37,48 @@ impl Coordination {
         loop {
             let work_unit = {
                 let mut work = self.work.lock().unwrap();
-                while work.is_empty() && !self.shutdown.load(Ordering::Relaxed) {
+                while work.queued.is_empty() && !self.shutdown.load(Ordering::Relaxed) {
                     work = self.can_work.wait(work).unwrap();
                 }
-                if work.is_empty() && self.shutdown.load(Ordering::Relaxed) {
+                if work.queued.is_empty() && self.shutdown.load(Ordering::Relaxed) {
                     return;
                 }
-                // SAFETY(rescrv):  We checked work.is_empty() and hold a mutex.
+                work.active += 1;
+                // SAFETY(rescrv):  We checked work.queued.is_empty() and hold a mutex.
                 // Shutdown is a stable property false->true, so it will not race.
-                work.pop_front().unwrap()
+                work.queued.pop_front().unwrap()
             };
-            self.do_work(work_unit);
+            let _active = Active(&self);
+            // NOTE(rescrv):  A panicking unit of work must not take its worker with it, or once
+            // every worker is gone nothing drains the queue and shutdown waits forever.
+            let _ = catch_unwind(AssertUnwindSafe(|| self.do_work(work_unit)));
         }
     }
 
     fn do_work(&self, work_unit: Box<WorkUnit>) {
         work_unit()
     }
+
+    fn wait_until_drained(&self) {
+        let work = self.work.lock().unwrap();
+        let _work = self
+            .drained
+            .wait_while(work, |work| !work.queued.is_empty() || work.active > 0)
+            .unwrap();
+    }
+}
+
+/// Marks a worker active for as long as it does one unit of work, even if the work panics.
+struct Active<'a>(&'a Coordination);
+
+impl Drop for Active<'_> {
+    fn drop(&mut self) {
+        let mut work = self.0.work.lock().unwrap_or_else(|err| err.into_inner());
+        work.active -= 1;
+        if work.queued.is_empty() && work.active == 0 {
+            self.0.drained.notify_all();
+        }
+    }
 }
 
 //////////////////////////////////////////// ThreadPool ////////////////////////////////////////////
@@ -60,7 +92,9 @@ pub struct ThreadPool {
 }
 
 impl ThreadPool {
-    /// Create a new thread pool with num-threads identified by `name:num`.
+    /// Create a new thread pool with num-threads identified by `name:num`.  A pool with zero
+    /// threads runs work inline on the thread that enqueues it, which makes parallel code paths
+    /// deterministic for testing.
     pub fn new(name: &str, num: usize) -> Self {
         let coordination = Arc::new(Coordination::default());
         let mut threads = Vec::with_capacity(num);
@@ -79,18 +113,366 @@ impl ThreadPool {
         }
     }
 
+    /// The number of threads in the pool.
+    pub fn num_threads(&self) -> usize {
+        self.threads.len()
+    }
+
     /// Enqueue a unit of work on the threadpool.  It is the caller's responsibility to make the
     /// unit of work signal completion if said completion-signaling is necessary for correctness.
+    /// Work is dequeued in the order it was enqueued.  A unit of work that panics does so without
+    /// taking down the thread that runs it.
+    ///
+    /// If the pool has zero threads, the work runs to completion before this returns, and a panic
+    /// propagates to the caller.
     pub fn enqueue(&self, work_unit: Box<WorkUnit>) {
-        self.coordination.enqueue(work_unit);
+        if self.threads.is_empty() {
+            self.coordination.do_work(work_unit);
+        } else {
+            self.coordination.enqueue(work_unit);
+        }
+    }
+
+    /// Create a scope in which work that borrows from the enclosing stack frame may be enqueued.
+    /// All work enqueued on the scope completes before `scope` returns.  If any of the work
+    /// panics, `scope` panics after all work completes.
+    pub fn scope<'scope, F: FnOnce(&ThreadPoolScope<'scope>) -> R, R>(&'scope self, f: F) -> R {
+        let scope = ThreadPoolScope {
+            thread_pool: self,
+            state: Arc::new(ScopeState::default()),
+            _phantom: PhantomData,
+        };
+        let result = catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.wait();
+        match result {
+            Ok(result) => {
+                if scope.state.panicked.load(Ordering::Relaxed) {
+                    panic!("work enqueued on a thread pool scope panicked");
+                }
+                result
+            }
+            Err(err) => resume_unwind(err),
+        }
+    }
+
+    /// Block until the queue is empty and no thread is doing work, including work enqueued by work
+    /// that ran in the meantime.  Work enqueued by other threads while this waits extends the
+    /// wait.  Calling this from work running on the pool deadlocks, as that work never finishes.
+    pub fn wait_until_idle(&self) {
+        if !self.threads.is_empty() {
+            self.coordination.wait_until_drained();
+        }
+    }
+
+    /// Shutdown the threadpool.  This will wait for all enqueued work to finish before it returns,
+    /// including work that enqueued work enqueues, by draining the queue before it signals the
+    /// threads to exit.  Dropping the thread pool does the same.
+    ///
+    /// If the pool shuts down from work running on one of its own threads, as when that work
+    /// drops the last reference to the pool, the other threads drain the queue and are joined,
+    /// and the current thread exits once its work returns.
+    pub fn shutdown(mut self) {
+        self.shutdown_and_join();
     }
 
-    /// Shutdown the threadpool.  This will wait for all enqueued work to finish before it returns.
-    pub fn shutdown(self) {
+    fn shutdown_and_join(&mut self) {
+        // NOTE(rescrv):  A worker cannot wait for itself to go idle or join itself, so a pool
+        // shut down from its own work skips both for the current thread.  Workers exit only once
+        // the queue is empty, so joining the others still drains the queue.
+        let current = std::thread::current().id();
+        let on_worker = self.threads.iter().any(|jh| jh.thread().id() == current);
+        if !self.threads.is_empty() && !on_worker {
+            self.coordination.wait_until_drained();
+        }
         self.coordination.shutdown.store(true, Ordering::Relaxed);
         self.coordination.can_work.notify_all();
-        for jh in self.threads.into_iter() {
-            let _ = jh.join();
+        for jh in std::mem::take(&mut self.threads).into_iter() {
+            if jh.thread().id() != current {
+                let _ = jh.join();
+            }
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shutdown_and_join();
+    }
+}
+
+////////////////////////////////////////// ThreadPoolScope /////////////////////////////////////////
+
+#[derive(Default)]
+struct ScopeState {
+    pending: Mutex<usize>,
+    done: Condvar,
+    panicked: AtomicBool,
+}
+
+/// A scope created by [ThreadPool::scope].  Work enqueued on the scope may borrow anything that
+/// outlives the scope.
+pub struct ThreadPoolScope<'scope> {
+    thread_pool: &'scope ThreadPool,
+    state: Arc<ScopeState>,
+    _phantom: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope> ThreadPoolScope<'scope> {
+    /// Enqueue a unit of work on the thread pool.  The work will complete before the scope ends.
+    pub fn enqueue<F: FnOnce() + Send + 'scope>(&self, work_unit: F) {
+        *self.state.pending.lock().unwrap() += 1;
+        let state = Arc::clone(&self.state);
+        let work_unit: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            if catch_unwind(AssertUnwindSafe(work_unit)).is_err() {
+                state.panicked.store(true, Ordering::Relaxed);
+            }
+            let mut pending = state.pending.lock().unwrap();
+            *pending -= 1;
+            if *pending == 0 {
+                state.done.notify_all();
+            }
+        });
+        // SAFETY(rescrv):  ThreadPool::scope waits for pending to reach zero before returning,
+        // so the work unit never runs after 'scope ends, even if the work unit panics.
+        let work_unit: Box<WorkUnit> = unsafe {
+            std::mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Box<WorkUnit>>(work_unit)
+        };
+        self.thread_pool.enqueue(work_unit);
+    }
+
+    fn wait(&self) {
+        let mut pending = self.state.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = self.state.done.wait(pending).unwrap();
         }
     }
 }
+
+/////////////////////////////////////////////// tests //////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test]
+    fn scope_borrows_stack() {
+        let thread_pool = ThreadPool::new("scope", 4);
+        let values: Vec<u64> = (0..1000).collect();
+        let sums: Vec<AtomicUsize> = (0..10).map(|_| AtomicUsize::new(0)).collect();
+        let returned = thread_pool.scope(|scope| {
+            for (chunk, sum) in std::iter::zip(values.chunks(100), sums.iter()) {
+                scope.enqueue(move || {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    let total: u64 = chunk.iter().sum();
+                    sum.store(total as usize, Ordering::Relaxed);
+                });
+            }
+            42
+        });
+        assert_eq!(42, returned);
+        let total: usize = sums.iter().map(|s| s.load(Ordering::Relaxed)).sum();
+        assert_eq!(499500, total);
+        thread_pool.shutdown();
+    }
+
+    #[test]
+    fn fifo() {
+        let thread_pool = ThreadPool::new("fifo", 1);
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let order = Mutex::new(vec![]);
+        thread_pool.scope(|scope| {
+            scope.enqueue(move || blocked.recv().unwrap());
+            for idx in 0..100 {
+                let order = &order;
+                scope.enqueue(move || order.lock().unwrap().push(idx));
+            }
+            release.send(()).unwrap();
+            for idx in 100..1000 {
+                let order = &order;
+                scope.enqueue(move || order.lock().unwrap().push(idx));
+            }
+        });
+        let order = order.into_inner().unwrap();
+        assert_eq!((0..1000).collect::<Vec<_>>(), order);
+        thread_pool.shutdown();
+    }
+
+    #[test]
+    fn inline() {
+        let thread_pool = ThreadPool::new("inline", 0);
+        assert_eq!(0, thread_pool.num_threads());
+        let caller = std::thread::current().id();
+        let (tx, rx) = std::sync::mpsc::channel();
+        thread_pool.enqueue(Box::new(move || {
+            tx.send(std::thread::current().id()).unwrap()
+        }));
+        assert_eq!(Ok(caller), rx.try_recv());
+        let order = Mutex::new(vec![]);
+        thread_pool.scope(|scope| {
+            for idx in 0..10 {
+                let order = &order;
+                scope.enqueue(move || order.lock().unwrap().push(idx));
+                assert_eq!(idx + 1, order.lock().unwrap().len());
+            }
+        });
+        assert_eq!((0..10).collect::<Vec<_>>(), order.into_inner().unwrap());
+        thread_pool.shutdown();
+    }
+
+    #[test]
+    fn drop_joins_threads() {
+        struct SignalExit(std::sync::mpsc::Sender<()>);
+
+        impl Drop for SignalExit {
+            fn drop(&mut self) {
+                let _ = self.0.send(());
+            }
+        }
+
+        thread_local! {
+            static SIGNAL_EXIT: std::cell::RefCell<Option<SignalExit>> = const { std::cell::RefCell::new(None) };
+        }
+
+        let thread_pool = ThreadPool::new("drop", 1);
+        let (exited, exits) = std::sync::mpsc::channel();
+        let (ran, runs) = std::sync::mpsc::channel();
+        thread_pool.enqueue(Box::new(move || {
+            SIGNAL_EXIT.with(|s| *s.borrow_mut() = Some(SignalExit(exited)));
+            ran.send(()).unwrap();
+        }));
+        runs.recv().unwrap();
+        assert!(exits.try_recv().is_err());
+        drop(thread_pool);
+        assert_eq!(Ok(()), exits.try_recv());
+    }
+
+    #[test]
+    fn drop_on_worker() {
+        let thread_pool = Arc::new(ThreadPool::new("drop_on_worker", 2));
+        let completed = Arc::new(AtomicUsize::new(0));
+        let (go, gos) = std::sync::mpsc::channel::<()>();
+        let (dropped, drops) = std::sync::mpsc::channel();
+        let thread_pool_clone = Arc::clone(&thread_pool);
+        thread_pool.enqueue(Box::new(move || {
+            gos.recv().unwrap();
+            drop(thread_pool_clone);
+            dropped.send(()).unwrap();
+        }));
+        for _ in 0..8 {
+            let completed = Arc::clone(&completed);
+            thread_pool.enqueue(Box::new(move || {
+                completed.fetch_add(1, Ordering::Relaxed);
+            }));
+        }
+        drop(thread_pool);
+        go.send(()).unwrap();
+        assert_eq!(
+            Ok(()),
+            drops.recv_timeout(std::time::Duration::from_secs(10))
+        );
+        assert_eq!(8, completed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn shutdown_drains_queue() {
+        let thread_pool = ThreadPool::new("drain", 4);
+        let completed = Arc::new(AtomicUsize::new(0));
+        for _ in 0..1000 {
+            let completed = Arc::clone(&completed);
+            thread_pool.enqueue(Box::new(move || {
+                completed.fetch_add(1, Ordering::Relaxed);
+            }));
+        }
+        thread_pool.shutdown();
+        assert_eq!(1000, completed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn shutdown_drains_work_enqueued_by_work() {
+        let thread_pool = ThreadPool::new("drain", 2);
+        let coordination = Arc::clone(&thread_pool.coordination);
+        let completed = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100 {
+            let coordination = Arc::clone(&coordination);
+            let completed = Arc::clone(&completed);
+            thread_pool.enqueue(Box::new(move || {
+                std::thread::sleep(std::time::Duration::from_micros(100));
+                let inner = Arc::clone(&completed);
+                coordination.enqueue(Box::new(move || {
+                    inner.fetch_add(1, Ordering::Relaxed);
+                }));
+                completed.fetch_add(1, Ordering::Relaxed);
+            }));
+        }
+        thread_pool.shutdown();
+        assert_eq!(200, completed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn wait_until_idle() {
+        let thread_pool = Arc::new(ThreadPool::new("idle", 4));
+        let completed = Arc::new(AtomicUsize::new(0));
+        for round in 1..=3 {
+            for _ in 0..16 {
+                let thread_pool_clone = Arc::clone(&thread_pool);
+                let completed = Arc::clone(&completed);
+                thread_pool.enqueue(Box::new(move || {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                    let completed_clone = Arc::clone(&completed);
+                    thread_pool_clone.enqueue(Box::new(move || {
+                        std::thread::sleep(std::time::Duration::from_millis(5));
+                        completed_clone.fetch_add(1, Ordering::Relaxed);
+                    }));
+                    completed.fetch_add(1, Ordering::Relaxed);
+                }));
+            }
+            thread_pool.wait_until_idle();
+            assert_eq!(round * 32, completed.load(Ordering::Relaxed));
+        }
+        // Idle already; this returns immediately.
+        thread_pool.wait_until_idle();
+        ThreadPool::new("idle_inline", 0).wait_until_idle();
+    }
+
+    #[test]
+    fn workers_survive_panics() {
+        let thread_pool = ThreadPool::new("survive", 2);
+        let completed = Arc::new(AtomicUsize::new(0));
+        for _ in 0..4 {
+            thread_pool.enqueue(Box::new(|| panic!("work panics")));
+        }
+        for _ in 0..16 {
+            let completed = Arc::clone(&completed);
+            thread_pool.enqueue(Box::new(move || {
+                completed.fetch_add(1, Ordering::Relaxed);
+            }));
+        }
+        thread_pool.wait_until_idle();
+        assert_eq!(16, completed.load(Ordering::Relaxed));
+        for _ in 0..4 {
+            thread_pool.enqueue(Box::new(|| panic!("work panics")));
+        }
+        thread_pool.shutdown();
+    }
+
+    #[test]
+    fn scope_panics_after_completion() {
+        let thread_pool = ThreadPool::new("scope", 2);
+        let completed = AtomicUsize::new(0);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            thread_pool.scope(|scope| {
+                scope.enqueue(|| panic!("scoped work panics"));
+                for _ in 0..10 {
+                    scope.enqueue(|| {
+                        completed.fetch_add(1, Ordering::Relaxed);
+                    });
+                }
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(10, completed.load(Ordering::Relaxed));
+        thread_pool.shutdown();
+    }
+}
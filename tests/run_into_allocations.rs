@@ -0,0 +1,81 @@
+//! system!'s run_into does not allocate once the caller's buffers are warm.
+//!
+//! This lives in its own test binary because it replaces the global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use tnaps::{
+    system, ComponentChange, ComponentCollection, ComponentRef, CopyOnWriteComponentCollection,
+    CopyOnWriteComponentRef, Entity,
+};
+
+// NOTE(rescrv):  Count per thread so that tests running concurrently don't interfere.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|a| a.get())
+}
+
+struct Transfer;
+
+system! {
+    Transfer<u128> {
+        from: CopyOnWriteComponentCollection<u64>,
+        to: CopyOnWriteComponentCollection<u64>,
+    }
+}
+
+impl Transfer {
+    fn process(
+        &self,
+        _: u128,
+        from: &mut CopyOnWriteComponentRef<u64>,
+        to: &mut CopyOnWriteComponentRef<u64>,
+    ) {
+        if **from > 0 {
+            from.update(|f| *f -= 1);
+            to.update(|t| *t += 1);
+        }
+    }
+}
+
+type Changes = Vec<(u128, ComponentChange<u64>)>;
+
+#[test]
+fn no_allocation_after_warmup() {
+    let mut from = CopyOnWriteComponentCollection::from_iter((1..=256u128).map(|e| (e, 1u64)));
+    let mut to = CopyOnWriteComponentCollection::from_iter((1..=256u128).map(|e| (e, 0u64)));
+    let mut bufs: (Changes, Changes) = (vec![], vec![]);
+    Transfer.run_into(&mut bufs, &mut from, &mut to);
+    assert_eq!(256, bufs.0.len());
+    let before = allocations();
+    for _ in 0..4 {
+        Transfer.run_into(&mut bufs, &mut from, &mut to);
+        assert_eq!(256, bufs.1.len());
+    }
+    assert_eq!(before, allocations());
+}